@@ -1,5 +1,6 @@
 import { extend, useTick } from "@pixi/react";
-import type { Particle, Trail, Universe } from "physics-engine";
+import type { Polylines, Universe } from "physics-engine";
+import { FieldKind, FieldLineOptions } from "physics-engine";
 import { Container, Graphics, Text, SCALE_MODES } from "pixi.js";
 import { useCallback, useEffect, useRef, useState } from "react";
 import { useSimulation } from "../contexts/SimulationContext";
@@ -10,6 +11,90 @@ extend({
   Text,
 });
 
+// Strokes every line of an engine Polylines batch, styled by the value stored with each line
+function strokePolylines(
+  graphics: any,
+  lines: Polylines,
+  style: (value: number) => object
+) {
+  const points = lines.get_points();
+  const offsets = lines.get_offsets();
+  const values = lines.get_values();
+  for (let l = 0; l < values.length; l++) {
+    const start = offsets[l];
+    const end = offsets[l + 1];
+    if (end - start < 2) continue;
+    graphics.setStrokeStyle(style(values[l]));
+    graphics.moveTo(points[2 * start], points[2 * start + 1]);
+    for (let v = start + 1; v < end; v++) {
+      graphics.lineTo(points[2 * v], points[2 * v + 1]);
+    }
+    graphics.stroke();
+  }
+}
+
+// Views over the engine's render buffers (see render.rs). They alias wasm memory rather than
+// copying it, and growing that memory detaches them, so they are read again after any engine
+// call that may allocate instead of being kept around.
+interface RenderViews {
+  particleCount: number;
+  positions: Float64Array; // interleaved x, y
+  velocities: Float64Array; // interleaved x, y
+  radii: Float64Array;
+  charges: Float64Array;
+  colors: Uint32Array;
+  trailPoints: Float64Array; // interleaved x, y
+  trailColors: Uint32Array;
+  trailOffsets: Uint32Array; // particle i's trail is trailOffsets[i]..trailOffsets[i + 1]
+  magnetCount: number;
+  magnetPoses: Float64Array; // interleaved x, y, angle
+  magnetDimensions: Float64Array; // interleaved size, thickness
+  magnetColors: Uint32Array; // interleaved north, south
+}
+
+// While paused nothing calls time_step, so edits are copied into the buffers here first
+function readRenderViews(universe: Universe): RenderViews {
+  if (universe.get_is_paused()) {
+    universe.refresh_render_buffers();
+  }
+  return {
+    particleCount: universe.get_render_particle_count(),
+    positions: universe.render_positions(),
+    velocities: universe.render_velocities(),
+    radii: universe.render_radii(),
+    charges: universe.render_charges(),
+    colors: universe.render_colors(),
+    trailPoints: universe.render_trail_points(),
+    trailColors: universe.render_trail_colors(),
+    trailOffsets: universe.render_trail_offsets(),
+    magnetCount: universe.get_render_magnet_count(),
+    magnetPoses: universe.render_magnet_poses(),
+    magnetDimensions: universe.render_magnet_dimensions(),
+    magnetColors: universe.render_magnet_colors(),
+  };
+}
+
+// Index of the top-most magnet or particle under a world-space point
+function hitTest(views: RenderViews, x: number, y: number) {
+  for (let i = views.magnetCount - 1; i >= 0; i--) {
+    const dx = x - views.magnetPoses[3 * i];
+    const dy = y - views.magnetPoses[3 * i + 1];
+    const halfW = (views.magnetDimensions[2 * i] || 60) / 2;
+    const halfH = (views.magnetDimensions[2 * i + 1] || 20) / 2;
+    if (Math.abs(dx) <= halfW + 10 && Math.abs(dy) <= halfH + 10) {
+      return { magnet: i, particle: null };
+    }
+  }
+  for (let i = views.particleCount - 1; i >= 0; i--) {
+    const dx = x - views.positions[2 * i];
+    const dy = y - views.positions[2 * i + 1];
+    if (Math.sqrt(dx * dx + dy * dy) <= views.radii[i] + 10) {
+      return { magnet: null, particle: i };
+    }
+  }
+  return { magnet: null, particle: null };
+}
+
 interface SandBoxProps {
   universe: Universe;
 }
@@ -43,20 +128,6 @@ export default function SandBox({ universe }: SandBoxProps) {
   const pixiContainerRef = useRef<any>(null);
   const magnetTextsRef = useRef<any[]>([]);
   const magnetDragOffsetRef = useRef<{ x: number; y: number } | null>(null);
-  const equipSegmentsRef = useRef<
-    Record<number, Array<{ x1: number; y1: number; x2: number; y2: number }>>
-  >({});
-  const equipParticlesSnapshotRef = useRef<{
-    px: Float64Array;
-    py: Float64Array;
-    cq: Float64Array;
-  } | null>(null);
-  const equipViewportSnapshotRef = useRef<{
-    minX: number;
-    minY: number;
-    maxX: number;
-    maxY: number;
-  } | null>(null);
 
   // Apply Y-flip to the container instead of viewport
   if (pixiContainerRef.current && pixiContainerRef.current.scale.y !== -1) {
@@ -79,12 +150,11 @@ export default function SandBox({ universe }: SandBoxProps) {
     // Remember offset so the magnet doesn't snap to cursor
     if (pixiContainerRef.current) {
       const localPos = pixiContainerRef.current.toLocal(event.data.global);
-      const magnets = (universe as any).get_magnets() as any[];
-      const m = magnets[index];
-      if (m) {
+      const views = readRenderViews(universe);
+      if (index < views.magnetCount) {
         magnetDragOffsetRef.current = {
-          x: localPos.x - m.pos.x,
-          y: localPos.y - m.pos.y,
+          x: localPos.x - views.magnetPoses[3 * index],
+          y: localPos.y - views.magnetPoses[3 * index + 1],
         };
       } else {
         magnetDragOffsetRef.current = null;
@@ -117,60 +187,66 @@ export default function SandBox({ universe }: SandBoxProps) {
 
   const handleParticleDragEnd = () => {
     setIsDraggingParticle(false);
+    // End the drag's undo step so the next drag can be undone on its own
+    universe.commit_edit();
     // Don't automatically resume - let user control pause state
   };
 
   const handleMagnetDragEnd = () => {
     setIsDraggingMagnet(false);
     magnetDragOffsetRef.current = null;
+    universe.commit_edit();
   };
 
   const drawCallback = useCallback(
     (graphics: any) => {
       graphics.clear();
 
-      const particles = universe.get_particles() as Particle[];
-      const trails = universe.get_trails() as Trail[][];
+      let views = readRenderViews(universe);
+      const particleCount = views.particleCount;
       const isPaused = universe.get_is_paused();
 
-      // Draw trails first (behind particles)
-      if (universe.get_show_trails()) {
-        for (let i = 0; i < trails.length; i++) {
-          const trail = trails[i];
+      // Draw trails first (behind particles); the offsets are empty while trails are hidden
+      for (let i = 0; i + 1 < views.trailOffsets.length; i++) {
+        const start = views.trailOffsets[i];
+        const length = views.trailOffsets[i + 1] - start;
 
-          // Trail alpha is constant now (auto-fade feature removed)
-          let trailFadeAlpha = 1.0;
+        // Trail alpha is constant now (auto-fade feature removed)
+        let trailFadeAlpha = 1.0;
 
-          for (let j = 0; j < trail.length; j++) {
-            graphics.setStrokeStyle({
-              width: 2,
-              color: trail[j].color,
-              alpha: ((j + 1) / trail.length) * 0.7 * trailFadeAlpha,
-            });
-            if (j === 0) {
-              graphics.moveTo(trail[j].pos.x, trail[j].pos.y);
-            } else {
-              graphics.lineTo(trail[j].pos.x, trail[j].pos.y);
-            }
-            graphics.stroke();
+        for (let j = 0; j < length; j++) {
+          const point = start + j;
+          graphics.setStrokeStyle({
+            width: 2,
+            color: views.trailColors[point],
+            alpha: ((j + 1) / length) * 0.7 * trailFadeAlpha,
+          });
+          const x = views.trailPoints[2 * point];
+          const y = views.trailPoints[2 * point + 1];
+          if (j === 0) {
+            graphics.moveTo(x, y);
+          } else {
+            graphics.lineTo(x, y);
           }
+          graphics.stroke();
         }
       }
 
       // Draw force vectors when showVelocityVectors is enabled
       if (showVelocityVectors) {
-        for (let i = 0; i < particles.length; i++) {
-          const particle = particles[i];
-          const velocityMag = Math.sqrt(
-            particle.vel.x * particle.vel.x + particle.vel.y * particle.vel.y
-          );
+        for (let i = 0; i < particleCount; i++) {
+          const x = views.positions[2 * i];
+          const y = views.positions[2 * i + 1];
+          const vx = views.velocities[2 * i];
+          const vy = views.velocities[2 * i + 1];
+          const velocityMag = Math.sqrt(vx * vx + vy * vy);
 
           // Draw velocity vector (gray)
           const minVel = isPaused ? 0.001 : 0.01; // show smaller vectors when paused for inspection
           if (velocityMag > minVel) {
             const velScale = 2;
-            const velX = particle.vel.x * velScale;
-            const velY = particle.vel.y * velScale;
+            const velX = vx * velScale;
+            const velY = vy * velScale;
 
             graphics.setStrokeStyle({
               width: 3,
@@ -179,15 +255,15 @@ export default function SandBox({ universe }: SandBoxProps) {
               cap: "round",
               join: "round",
             });
-            graphics.moveTo(particle.pos.x, particle.pos.y);
-            graphics.lineTo(particle.pos.x + velX, particle.pos.y + velY);
+            graphics.moveTo(x, y);
+            graphics.lineTo(x + velX, y + velY);
             graphics.stroke();
 
             const arrowSize = 15;
             const arrowAngle = Math.PI / 6;
             const velAngleDir = Math.atan2(velY, velX);
-            const tipX = particle.pos.x + velX;
-            const tipY = particle.pos.y + velY;
+            const tipX = x + velX;
+            const tipY = y + velY;
             const arrowOffset = -8;
             const arrowTipX = tipX - Math.cos(velAngleDir) * arrowOffset;
             const arrowTipY = tipY - Math.sin(velAngleDir) * arrowOffset;
@@ -210,33 +286,15 @@ export default function SandBox({ universe }: SandBoxProps) {
           }
         }
 
-        // Draw acceleration for particles due to other particles (exclude magnets)
-        for (let i = 0; i < particles.length; i++) {
-          const p = particles[i];
-          const mi = universe.get_mass_calculation()
-            ? p.mass
-            : universe.get_default_mass();
-          let ax = 0;
-          let ay = 0;
-          const k = universe.get_coulomb_constant();
-          const minDist = universe.get_min_interaction_distance();
-          const minDistSq = minDist * minDist;
-
-          for (let j = 0; j < particles.length; j++) {
-            if (i === j) continue;
-            const qj = particles[j].charge;
-            const rx = p.pos.x - particles[j].pos.x;
-            const ry = p.pos.y - particles[j].pos.y;
-            const distSq = rx * rx + ry * ry;
-            const safeDistSq = distSq < minDistSq ? minDistSq : distSq;
-            const dist = Math.sqrt(safeDistSq);
-            if (dist > 1e-8) {
-              const forceMagnitude = (k * p.charge * qj) / safeDistSq;
-              const accMagnitude = forceMagnitude / mi;
-              ax += accMagnitude * (rx / dist);
-              ay += accMagnitude * (ry / dist);
-            }
-          }
+        // Draw acceleration for particles due to other particles (exclude magnets), as summed
+        // by the engine's active force backend
+        const coulombAcc = universe.get_coulomb_accelerations();
+        views = readRenderViews(universe);
+        for (let i = 0; i < particleCount; i++) {
+          const x = views.positions[2 * i];
+          const y = views.positions[2 * i + 1];
+          const ax = coulombAcc[2 * i];
+          const ay = coulombAcc[2 * i + 1];
 
           const amag = Math.sqrt(ax * ax + ay * ay);
           const minAcc = isPaused ? 1e-6 : 1e-4;
@@ -251,15 +309,15 @@ export default function SandBox({ universe }: SandBoxProps) {
               cap: "round",
               join: "round",
             });
-            graphics.moveTo(p.pos.x, p.pos.y);
-            graphics.lineTo(p.pos.x + accX, p.pos.y + accY);
+            graphics.moveTo(x, y);
+            graphics.lineTo(x + accX, y + accY);
             graphics.stroke();
 
             const arrowSize = 12;
             const arrowAngle = Math.PI / 6;
             const angle = Math.atan2(accY, accX);
-            const tipX = p.pos.x + accX;
-            const tipY = p.pos.y + accY;
+            const tipX = x + accX;
+            const tipY = y + accY;
             const arrowOffset = -8;
             const arrowTipX = tipX - Math.cos(angle) * arrowOffset;
             const arrowTipY = tipY - Math.sin(angle) * arrowOffset;
@@ -279,17 +337,14 @@ export default function SandBox({ universe }: SandBoxProps) {
         }
       }
 
-      // Draw quadtree visualization when enabled (only if quadtree is actually being used)
-      if (
-        viewQuadtree &&
-        (universe as any).get_use_quadtree &&
-        universe.get_use_quadtree()
-      ) {
+      // Draw quadtree visualization when enabled (only if quadtree is actually being used,
+      // which under the Auto backend depends on the particle count)
+      if (viewQuadtree && universe.get_use_quadtree()) {
         try {
-          const quadtreeData = (universe as any).get_quadtree();
+          const quadtreeNodes = (universe as any).get_quadtree();
 
-          const drawQuadNode = (node: any) => {
-            if (!node) return;
+          // flat list of node rectangles
+          for (const node of quadtreeNodes) {
             const halfWidth = node.dimensions.x / 2;
             const halfHeight = node.dimensions.y / 2;
             const left = node.center.x - halfWidth;
@@ -301,23 +356,18 @@ export default function SandBox({ universe }: SandBoxProps) {
               color: 0x00ff00,
               alpha: 0.6,
             });
-
-            if (node.children) {
-              for (const child of node.children) {
-                drawQuadNode(child);
-              }
-            }
-          };
-
-          drawQuadNode(quadtreeData);
+          }
         } catch (err) {
           // ignore if quadtree serialization is not available
         }
+        views = readRenderViews(universe);
       }
 
       // Draw particles
-      for (let i = 0; i < particles.length; i++) {
-        const particle = particles[i];
+      for (let i = 0; i < particleCount; i++) {
+        const x = views.positions[2 * i];
+        const y = views.positions[2 * i + 1];
+        const radius = views.radii[i];
         const isSelected = selectedParticleIndex === i;
         const isHovered = hoveredParticleIndex === i && isPaused; // Only show hover when paused
 
@@ -329,7 +379,7 @@ export default function SandBox({ universe }: SandBoxProps) {
         // Draw selection/hover highlight (only when paused)
         if ((isSelected || isHovered) && isPaused) {
           const highlightColor = 0xff8a00; // orange
-          graphics.circle(particle.pos.x, particle.pos.y, particle.radius + 8);
+          graphics.circle(x, y, radius + 8);
           graphics.fill({ color: highlightColor, alpha: 0.14 * fadeAlpha });
           graphics.stroke({
             width: 3,
@@ -339,22 +389,21 @@ export default function SandBox({ universe }: SandBoxProps) {
         }
 
         // Draw particle with fade effect
-        graphics.circle(particle.pos.x, particle.pos.y, particle.radius);
+        graphics.circle(x, y, radius);
         // If particle has no net charge, render it grey to indicate neutrality
-        const hasCharge = (particle.charge ?? 0) !== 0;
-        const displayColor = hasCharge ? particle.color : 0x9ca3af; // gray-400
+        const hasCharge = views.charges[i] !== 0;
+        const displayColor = hasCharge ? views.colors[i] : 0x9ca3af; // gray-400
         graphics.fill({ color: displayColor, alpha: fadeAlpha });
       }
 
       // Draw magnets
-      const magnets = (universe as any).get_magnets() as any[];
-      for (let i = 0; i < magnets.length; i++) {
-        const m = magnets[i];
-        const cx = m.pos.x;
-        const cy = m.pos.y;
-        const angle = m.angle || 0;
-        const halfW = (m.size || 60) / 2;
-        const halfH = (m.thickness || 20) / 2;
+      const magnetCount = views.magnetCount;
+      for (let i = 0; i < magnetCount; i++) {
+        const cx = views.magnetPoses[3 * i];
+        const cy = views.magnetPoses[3 * i + 1];
+        const angle = views.magnetPoses[3 * i + 2];
+        const halfW = (views.magnetDimensions[2 * i] || 60) / 2;
+        const halfH = (views.magnetDimensions[2 * i + 1] || 20) / 2;
 
         const ux = Math.cos(angle);
         const uy = Math.sin(angle);
@@ -382,7 +431,7 @@ export default function SandBox({ universe }: SandBoxProps) {
         const mbottom = { x: cx - vx * halfH, y: cy - vy * halfH };
 
         // North half (positive ux direction) - red
-        graphics.beginFill(m.color_north || 0xff0000, 1);
+        graphics.beginFill(views.magnetColors[2 * i] || 0xff0000, 1);
         graphics.poly([
           { x: p1.x, y: p1.y },
           { x: mtop.x, y: mtop.y },
@@ -392,7 +441,7 @@ export default function SandBox({ universe }: SandBoxProps) {
         graphics.fill();
 
         // South half (negative ux direction) - blue
-        graphics.beginFill(m.color_south || 0x0000ff, 1);
+        graphics.beginFill(views.magnetColors[2 * i + 1] || 0x0000ff, 1);
         graphics.poly([
           { x: p2.x, y: p2.y },
           { x: p3.x, y: p3.y },
@@ -426,7 +475,7 @@ export default function SandBox({ universe }: SandBoxProps) {
       // Manage magnet text labels (N/S)
       const container = pixiContainerRef.current;
       if (container) {
-        const desiredTextCount = magnets.length * 2;
+        const desiredTextCount = magnetCount * 2;
         // Create missing text objects
         while (magnetTextsRef.current.length < desiredTextCount) {
           const idx = magnetTextsRef.current.length;
@@ -462,7 +511,7 @@ export default function SandBox({ universe }: SandBoxProps) {
         }
 
         // Remove extra texts
-        while (magnetTextsRef.current.length > magnets.length * 2) {
+        while (magnetTextsRef.current.length > magnetCount * 2) {
           const t = magnetTextsRef.current.pop();
           if (t) {
             container.removeChild(t);
@@ -471,13 +520,15 @@ export default function SandBox({ universe }: SandBoxProps) {
         }
 
         // Update positions and rotation
-        for (let i = 0; i < magnets.length; i++) {
-          const m = magnets[i];
-          const ux = Math.cos(m.angle || 0);
-          const uy = Math.sin(m.angle || 0);
-          const half = (m.size || 60) / 2;
-          const north = { x: m.pos.x + ux * half, y: m.pos.y + uy * half };
-          const south = { x: m.pos.x - ux * half, y: m.pos.y - uy * half };
+        for (let i = 0; i < magnetCount; i++) {
+          const cx = views.magnetPoses[3 * i];
+          const cy = views.magnetPoses[3 * i + 1];
+          const angle = views.magnetPoses[3 * i + 2];
+          const ux = Math.cos(angle);
+          const uy = Math.sin(angle);
+          const half = (views.magnetDimensions[2 * i] || 60) / 2;
+          const north = { x: cx + ux * half, y: cy + uy * half };
+          const south = { x: cx - ux * half, y: cy - uy * half };
 
           const nText = magnetTextsRef.current[i * 2];
           const sText = magnetTextsRef.current[i * 2 + 1];
@@ -486,33 +537,40 @@ export default function SandBox({ universe }: SandBoxProps) {
             nText.x = north.x;
             nText.y = north.y;
             // rotation needs to account for the inverted y-scale on container
-            nText.rotation = -(m.angle || 0);
+            nText.rotation = -angle;
           }
           if (sText) {
             sText.text = "S";
             sText.x = south.x;
             sText.y = south.y;
-            sText.rotation = -(m.angle || 0);
+            sText.rotation = -angle;
           }
         }
       }
-      // Draw equipotential contour lines if enabled
-      if (showEquipotentialLines && pixiContainerRef.current) {
+
+      // World-space rectangle currently visible on screen
+      const visibleBounds = () => {
         const app = (window as any).pixiApp;
-        if (app) {
-          // Potential levels to contour
-          const potentialLevels = [-2, -0.5, -0.2, 0.2, 0.5, 2];
-
-          // Determine world bounds and a reasonable grid step in world space
-          const tl = pixiContainerRef.current.toLocal({ x: 0, y: 0 });
-          const br = pixiContainerRef.current.toLocal({
-            x: app.view.width,
-            y: app.view.height,
-          });
-          const minX = Math.min(tl.x, br.x);
-          const maxX = Math.max(tl.x, br.x);
-          const minY = Math.min(tl.y, br.y);
-          const maxY = Math.max(tl.y, br.y);
+        if (!app || !pixiContainerRef.current) return null;
+        const tl = pixiContainerRef.current.toLocal({ x: 0, y: 0 });
+        const br = pixiContainerRef.current.toLocal({
+          x: app.view.width,
+          y: app.view.height,
+        });
+        return {
+          minX: Math.min(tl.x, br.x),
+          maxX: Math.max(tl.x, br.x),
+          minY: Math.min(tl.y, br.y),
+          maxY: Math.max(tl.y, br.y),
+        };
+      };
+
+      // Draw equipotential contour lines if enabled. The engine contours its own potential,
+      // so the lines always agree with the forces.
+      if (showEquipotentialLines) {
+        const bounds = visibleBounds();
+        if (bounds) {
+          const { minX, minY, maxX, maxY } = bounds;
 
           // Convert a screen step (in pixels) to world-space step to keep contour density roughly constant
           const screenStep = 20;
@@ -531,379 +589,68 @@ export default function SandBox({ universe }: SandBoxProps) {
             2;
           if (stepWorld < 5) stepWorld = 5; // avoid extremely fine grids when zoomed in
 
-          // Precompute source arrays (particles + magnet poles)
-          const magnetsList = (universe as any).get_magnets() as any[];
-          const pCount = particles.length;
-          const mCount = magnetsList.length;
-          const totalSources = pCount + mCount * 2;
-
-          const px = new Float64Array(totalSources);
-          const py = new Float64Array(totalSources);
-          const cq = new Float64Array(totalSources);
-
-          let idx = 0;
-          for (let i = 0; i < pCount; i++) {
-            px[idx] = particles[i].pos.x;
-            py[idx] = particles[i].pos.y;
-            cq[idx] = particles[i].charge ?? 0;
-            idx++;
-          }
-
-          for (let j = 0; j < mCount; j++) {
-            const m = magnetsList[j];
-            const ux = Math.cos(m.angle || 0);
-            const uy = Math.sin(m.angle || 0);
-            const half = (m.size || 60) / 2;
-            const north = { x: m.pos.x + ux * half, y: m.pos.y + uy * half };
-            const south = { x: m.pos.x - ux * half, y: m.pos.y - uy * half };
-            px[idx] = north.x;
-            py[idx] = north.y;
-            cq[idx] = m.strength || 0;
-            idx++;
-            px[idx] = south.x;
-            py[idx] = south.y;
-            cq[idx] = -(m.strength || 0);
-            idx++;
-          }
-
-          // Make grid adaptive: when more sources or larger views are present, increase stepWorld
+          // Make grid adaptive: when more sources are present, increase stepWorld
+          const totalSources =
+            universe.get_particle_count() + universe.get_magnet_count() * 2;
           const sourceScale = Math.min(1 + totalSources / 100, 4); // scale up spacing when many sources
           stepWorld *= sourceScale;
 
           // Limit grid resolution to avoid huge allocations
-          const estimatedGridX = Math.ceil((maxX - minX) / stepWorld) + 1;
-          const estimatedGridY = Math.ceil((maxY - minY) / stepWorld) + 1;
           const maxDim = 300;
-          if (estimatedGridX > maxDim || estimatedGridY > maxDim) {
-            const scaleX = estimatedGridX / maxDim;
-            const scaleY = estimatedGridY / maxDim;
-            const scale = Math.max(scaleX, scaleY);
-            stepWorld *= scale;
-          }
+          const nx = Math.min(
+            maxDim,
+            Math.max(2, Math.ceil((maxX - minX) / stepWorld) + 1)
+          );
+          const ny = Math.min(
+            maxDim,
+            Math.max(2, Math.ceil((maxY - minY) / stepWorld) + 1)
+          );
 
           // Reduce level complexity on very dense scenes
-          let levelsToUse = potentialLevels;
-          if (totalSources > 200) levelsToUse = [-0.2, 0.2];
-          else if (totalSources > 80) levelsToUse = [-0.5, -0.2, 0.2, 0.5];
-
-          // Recompute heavy geometry occasionally and cache it. Also only recompute when particles moved significantly.
-          const shouldRecomputeHeavy = render % 60 === 0;
-
-          let movedSignificantly = true;
-          if (
-            equipParticlesSnapshotRef.current &&
-            equipParticlesSnapshotRef.current.px.length === totalSources
-          ) {
-            const old = equipParticlesSnapshotRef.current;
-            let maxDistSq = 0;
-            for (let k = 0; k < totalSources; k++) {
-              const dx = px[k] - old.px[k];
-              const dy = py[k] - old.py[k];
-              const distSq = dx * dx + dy * dy;
-              if (distSq > maxDistSq) maxDistSq = distSq;
-              if (Math.abs(cq[k] - old.cq[k]) > 1e-6) {
-                maxDistSq = Math.max(maxDistSq, 1e-4);
-              }
-            }
-            movedSignificantly = maxDistSq > 0.25; // moved more than 0.5 world units
-          }
-
-          // Detect viewport changes (pan/zoom)
-          let viewportChanged = true;
-          if (equipViewportSnapshotRef.current) {
-            const oldV = equipViewportSnapshotRef.current;
-            const dx =
-              Math.abs(oldV.minX - minX) +
-              Math.abs(oldV.maxX - maxX) +
-              Math.abs(oldV.minY - minY) +
-              Math.abs(oldV.maxY - maxY);
-            viewportChanged = dx > 1; // threshold in world units
-          }
-
-          movedSignificantly = movedSignificantly || viewportChanged;
-
-          if (
-            shouldRecomputeHeavy ||
-            Object.keys(equipSegmentsRef.current).length === 0 ||
-            movedSignificantly
-          ) {
-            const segsByLevel: Record<
-              number,
-              Array<{ x1: number; y1: number; x2: number; y2: number }>
-            > = {};
-
-            // Build a grid of potentials at node points to avoid repeated evaluations
-            const gridXCount = Math.max(
-              2,
-              Math.ceil((maxX - minX) / stepWorld) + 1
-            );
-            const gridYCount = Math.max(
-              2,
-              Math.ceil((maxY - minY) / stepWorld) + 1
-            );
-            const grid: Array<number | null> = new Array(
-              gridXCount * gridYCount
-            ).fill(null);
-
-            for (let gy = 0; gy < gridYCount; gy++) {
-              const wy = minY + gy * stepWorld;
-              const rowBase = gy * gridXCount;
-              for (let gx = 0; gx < gridXCount; gx++) {
-                const wx = minX + gx * stepWorld;
-                // Compute potential at this node
-                let V = 0;
-                let tooClose = false;
-                for (let k = 0; k < totalSources; k++) {
-                  const dx = wx - px[k];
-                  const dy = wy - py[k];
-                  const distSq = dx * dx + dy * dy;
-                  if (distSq < 15 * 15) {
-                    tooClose = true;
-                    break;
-                  }
-                  // Use Coulomb potential: V = k * q / r
-                  V += cq[k] / Math.sqrt(distSq);
-                }
-                grid[rowBase + gx] = tooClose ? null : V;
-              }
-            }
-
-            for (const level of potentialLevels) {
-              const segs: Array<{
-                x1: number;
-                y1: number;
-                x2: number;
-                y2: number;
-              }> = [];
-              for (let gy = 0; gy < gridYCount - 1; gy++) {
-                for (let gx = 0; gx < gridXCount - 1; gx++) {
-                  const p00 = grid[gy * gridXCount + gx];
-                  const p10 = grid[gy * gridXCount + gx + 1];
-                  const p01 = grid[(gy + 1) * gridXCount + gx];
-                  const p11 = grid[(gy + 1) * gridXCount + gx + 1];
-
-                  if (
-                    p00 === null ||
-                    p10 === null ||
-                    p01 === null ||
-                    p11 === null
-                  )
-                    continue;
-
-                  const wx = minX + gx * stepWorld;
-                  const wy = minY + gy * stepWorld;
-
-                  const above = [
-                    p00 > level,
-                    p10 > level,
-                    p01 > level,
-                    p11 > level,
-                  ];
-                  const allSame = above.every((v) => v === above[0]);
-                  if (allSame) continue;
-
-                  const crossings: { x: number; y: number }[] = [];
-
-                  if (above[0] !== above[1]) {
-                    const t =
-                      (level - (p00 as number)) /
-                      ((p10 as number) - (p00 as number));
-                    crossings.push({ x: wx + t * stepWorld, y: wy });
-                  }
-                  if (above[2] !== above[3]) {
-                    const t =
-                      (level - (p01 as number)) /
-                      ((p11 as number) - (p01 as number));
-                    crossings.push({
-                      x: wx + t * stepWorld,
-                      y: wy + stepWorld,
-                    });
-                  }
-                  if (above[0] !== above[2]) {
-                    const t =
-                      (level - (p00 as number)) /
-                      ((p01 as number) - (p00 as number));
-                    crossings.push({ x: wx, y: wy + t * stepWorld });
-                  }
-                  if (above[1] !== above[3]) {
-                    const t =
-                      (level - (p10 as number)) /
-                      ((p11 as number) - (p10 as number));
-                    crossings.push({
-                      x: wx + stepWorld,
-                      y: wy + t * stepWorld,
-                    });
-                  }
-
-                  if (crossings.length >= 2) {
-                    segs.push({
-                      x1: crossings[0].x,
-                      y1: crossings[0].y,
-                      x2: crossings[1].x,
-                      y2: crossings[1].y,
-                    });
-                  }
-                }
-              }
-              segsByLevel[level] = segs;
-            }
-
-            equipSegmentsRef.current = segsByLevel;
-            // Save snapshot for change detection
-            equipParticlesSnapshotRef.current = {
-              px: new Float64Array(px),
-              py: new Float64Array(py),
-              cq: new Float64Array(cq),
-            };
-            equipViewportSnapshotRef.current = { minX, minY, maxX, maxY };
-          }
-
-          for (const level of levelsToUse) {
-            const segs = equipSegmentsRef.current[level] || [];
-            if (segs.length === 0) continue;
-            graphics.setStrokeStyle({
-              width: 1,
-              color: level > 0 ? 0xe53935 : 0x1e88e5,
-              alpha: 0.25,
-            });
-            for (let s = 0; s < segs.length; s++) {
-              const seg = segs[s];
-              graphics.moveTo(seg.x1, seg.y1);
-              graphics.lineTo(seg.x2, seg.y2);
-            }
-            graphics.stroke();
-          }
+          let levelCount = 6;
+          if (totalSources > 200) levelCount = 2;
+          else if (totalSources > 80) levelCount = 4;
+
+          const contours = universe.equipotential_contours(
+            FieldKind.Combined,
+            minX,
+            minY,
+            maxX,
+            maxY,
+            nx,
+            ny,
+            new Float64Array(0),
+            levelCount
+          );
+          strokePolylines(graphics, contours, (level) => ({
+            width: 1,
+            color: level > 0 ? 0xe53935 : 0x1e88e5,
+            alpha: 0.25,
+          }));
+          contours.free();
         }
       }
 
-      // Draw electric field lines if enabled
-      if (showFieldLines && pixiContainerRef.current) {
-        const app = (window as any).pixiApp;
-        if (app) {
-          // Determine world bounds for out-of-bounds checking
-          const tl = pixiContainerRef.current.toLocal({ x: 0, y: 0 });
-          const br = pixiContainerRef.current.toLocal({
-            x: app.view.width,
-            y: app.view.height,
-          });
-          const minX = Math.min(tl.x, br.x);
-          const maxX = Math.max(tl.x, br.x);
-          const minY = Math.min(tl.y, br.y);
-          const maxY = Math.max(tl.y, br.y);
-
-          const drawFieldLineFrom = (
-            startX: number,
-            startY: number,
-            angle: number,
-            outward: boolean
-          ) => {
-            const stepSize = 10;
-            const maxSteps = 120;
-
-            let x = startX + 20 * Math.cos(angle);
-            let y = startY + 20 * Math.sin(angle);
-
-            graphics.setStrokeStyle({ width: 1, color: 0x64748b, alpha: 0.3 });
-            graphics.moveTo(x, y);
-
-            for (let s = 0; s < maxSteps; s++) {
-              let ex = 0;
-              let ey = 0;
-
-              for (const charge of particles) {
-                const dx = x - charge.pos.x;
-                const dy = y - charge.pos.y;
-                const distSq = dx * dx + dy * dy;
-                const dist = Math.sqrt(distSq);
-                if (dist < 15) {
-                  graphics.stroke();
-                  return;
-                }
-                // Electric field from point charge: E = k q / r^2
-                const fieldMag =
-                  (charge.charge * universe.get_coulomb_constant()) / distSq;
-                ex += fieldMag * (dx / dist);
-                ey += fieldMag * (dy / dist);
-              }
-
-              // Include magnets as dipoles (two poles)
-              for (const m of magnets) {
-                const ux = Math.cos(m.angle || 0);
-                const uy = Math.sin(m.angle || 0);
-                const half = (m.size || 60) / 2;
-                const north = {
-                  x: m.pos.x + ux * half,
-                  y: m.pos.y + uy * half,
-                };
-                const south = {
-                  x: m.pos.x - ux * half,
-                  y: m.pos.y - uy * half,
-                };
-
-                const poles = [
-                  { pos: north, s: m.strength || 0 },
-                  { pos: south, s: -(m.strength || 0) },
-                ];
-
-                for (const pole of poles) {
-                  const dx = x - pole.pos.x;
-                  const dy = y - pole.pos.y;
-                  const distSq = dx * dx + dy * dy;
-                  const dist = Math.sqrt(distSq);
-                  if (dist < 15) {
-                    graphics.stroke();
-                    return;
-                  }
-                  const fieldMag =
-                    (pole.s * universe.get_coulomb_constant()) / distSq;
-                  ex += fieldMag * (dx / dist);
-                  ey += fieldMag * (dy / dist);
-                }
-              }
-
-              const mag = Math.sqrt(ex * ex + ey * ey);
-              if (mag < 1e-5) break;
-
-              const direction = outward ? 1 : -1;
-              x += direction * stepSize * (ex / mag);
-              y += direction * stepSize * (ey / mag);
-
-              if (x < minX || x > maxX || y < minY || y > maxY) break;
-
-              graphics.lineTo(x, y);
-            }
-            graphics.stroke();
-          };
-
-          // Draw lines for each charge
-          for (const charge of particles) {
-            const numLines = 10;
-            for (let i = 0; i < numLines; i++) {
-              const angle = (2 * Math.PI * i) / numLines;
-              drawFieldLineFrom(
-                charge.pos.x,
-                charge.pos.y,
-                angle,
-                charge.charge > 0
-              );
-            }
-          }
-
-          // Also draw lines for magnets (treat poles as sources/sinks)
-          for (const m of magnets) {
-            const ux = Math.cos(m.angle || 0);
-            const uy = Math.sin(m.angle || 0);
-            const half = (m.size || 60) / 2;
-            const north = { x: m.pos.x + ux * half, y: m.pos.y + uy * half };
-            const south = { x: m.pos.x - ux * half, y: m.pos.y - uy * half };
-
-            const numLines = 10;
-            for (let i = 0; i < numLines; i++) {
-              const angle = (2 * Math.PI * i) / numLines;
-              drawFieldLineFrom(north.x, north.y, angle, true);
-              drawFieldLineFrom(south.x, south.y, angle, false);
-            }
-          }
+      // Draw field lines if enabled: E lines seeded around the charges and B lines around the
+      // magnet poles, traced by the engine through its own fields
+      if (showFieldLines) {
+        const bounds = visibleBounds();
+        if (bounds) {
+          const options = new FieldLineOptions(
+            bounds.minX,
+            bounds.minY,
+            bounds.maxX,
+            bounds.maxY
+          );
+          options.max_length = 1200;
+          const style = () => ({ width: 1, color: 0x64748b, alpha: 0.3 });
+          const electric = universe.trace_electric_field_lines(options);
+          strokePolylines(graphics, electric, style);
+          electric.free();
+          const magnetic = universe.trace_magnetic_field_lines(options);
+          strokePolylines(graphics, magnetic, style);
+          magnetic.free();
+          options.free();
         }
       }
     },
@@ -967,46 +714,24 @@ export default function SandBox({ universe }: SandBoxProps) {
           if (!universe.get_is_paused()) return;
 
           const localPos = pixiContainerRef.current.toLocal(event.data.global);
-          const particles = universe.get_particles() as Particle[];
-          const magnets = (universe as any).get_magnets() as any[];
-
-          // Check if clicked on a magnet (top-most first)
-          for (let i = magnets.length - 1; i >= 0; i--) {
-            const m = magnets[i];
-            const dx = localPos.x - m.pos.x;
-            const dy = localPos.y - m.pos.y;
-            const halfW = (m.size || 60) / 2;
-            const halfH = (m.thickness || 20) / 2;
-            if (Math.abs(dx) <= halfW + 10 && Math.abs(dy) <= halfH + 10) {
-              // clear particle selection when selecting a magnet
-              setSelectedParticleIndex(null);
-              setSelectedMagnetIndex(i);
-              if (universe.get_is_paused()) {
-                setIsPropertyEditorOpen(true);
-              }
-              handleMagnetDragStart(i, event);
-              return;
-            }
+          const hit = hitTest(readRenderViews(universe), localPos.x, localPos.y);
+
+          if (hit.magnet !== null) {
+            // clear particle selection when selecting a magnet
+            setSelectedParticleIndex(null);
+            setSelectedMagnetIndex(hit.magnet);
+            setIsPropertyEditorOpen(true);
+            handleMagnetDragStart(hit.magnet, event);
+            return;
           }
 
-          // Check if clicked on a particle
-          for (let i = particles.length - 1; i >= 0; i--) {
-            const particle = particles[i];
-            const dx = localPos.x - particle.pos.x;
-            const dy = localPos.y - particle.pos.y;
-            const distance = Math.sqrt(dx * dx + dy * dy);
-
-            if (distance <= particle.radius + 10) {
-              // clear magnet selection when selecting a particle
-              setSelectedMagnetIndex(null);
-              setSelectedParticleIndex(i);
-              // Only open property editor when paused
-              if (universe.get_is_paused()) {
-                setIsPropertyEditorOpen(true);
-              }
-              handleParticleDragStart(i, event);
-              return;
-            }
+          if (hit.particle !== null) {
+            // clear magnet selection when selecting a particle
+            setSelectedMagnetIndex(null);
+            setSelectedParticleIndex(hit.particle);
+            setIsPropertyEditorOpen(true);
+            handleParticleDragStart(hit.particle, event);
+            return;
           }
 
           // Clicked on empty space - deselect
@@ -1030,45 +755,14 @@ export default function SandBox({ universe }: SandBoxProps) {
           }
 
           if (!isDraggingParticle) {
-            // Check if hovering over a particle and update hover state
-            const particles = universe.get_particles() as Particle[];
-            const magnets = (universe as any).get_magnets() as any[];
-            let hoveringOverParticle = false;
-            let hoveredIndex: number | null = null;
-            let hoveringOverMagnet = false;
-            let hoveredMagIndex: number | null = null;
+            // Check if hovering over a magnet or particle and update hover state
             const localPos = pixiContainerRef.current.toLocal(
               event.data.global
             );
-
-            // Check magnets first
-            for (let i = magnets.length - 1; i >= 0; i--) {
-              const m = magnets[i];
-              const dx = localPos.x - m.pos.x;
-              const dy = localPos.y - m.pos.y;
-              const halfW = (m.size || 60) / 2;
-              const halfH = (m.thickness || 20) / 2;
-              if (Math.abs(dx) <= halfW + 10 && Math.abs(dy) <= halfH + 10) {
-                hoveringOverMagnet = true;
-                hoveredMagIndex = i;
-                break;
-              }
-            }
-
-            if (!hoveringOverMagnet) {
-              for (let i = particles.length - 1; i >= 0; i--) {
-                const particle = particles[i];
-                const dx = localPos.x - particle.pos.x;
-                const dy = localPos.y - particle.pos.y;
-                const distance = Math.sqrt(dx * dx + dy * dy);
-
-                if (distance <= particle.radius + 10) {
-                  hoveringOverParticle = true;
-                  hoveredIndex = i;
-                  break;
-                }
-              }
-            }
+            const hit = hitTest(readRenderViews(universe), localPos.x, localPos.y);
+            const hoveredMagIndex = hit.magnet;
+            const hoveredIndex = hit.particle;
+            const hoveringOverParticle = hoveredIndex !== null;
 
             setHoveredMagnetIndex(hoveredMagIndex);
             setHoveredParticleIndex(hoveredIndex);
@@ -20,10 +20,21 @@ import {
   CircleDashed,
   Eye,
 } from "lucide-react";
-import { Implementation } from "physics-engine";
+import { ForceBackend, Implementation } from "physics-engine";
 import { useEffect, useState, useRef } from "react";
 import { useSimulation } from "../contexts/SimulationContext";
 
+const forceBackends = [
+  { backend: ForceBackend.Auto, label: "Auto" },
+  { backend: ForceBackend.Direct, label: "Direct" },
+  { backend: ForceBackend.Quadtree, label: "Quadtree" },
+  { backend: ForceBackend.Fmm, label: "FMM" },
+  { backend: ForceBackend.ParticleMesh, label: "Particle Mesh" },
+];
+
+const backendLabel = (backend: ForceBackend) =>
+  forceBackends.find((entry) => entry.backend === backend)?.label ?? "";
+
 export default function SettingsBar() {
   const {
     universe,
@@ -50,7 +61,6 @@ export default function SettingsBar() {
     universe.get_implementation()
   );
   const [showTrails, setShowTrails] = useState(universe.get_show_trails());
-  const [useQuadtree, setUseQuadtree] = useState(universe.get_use_quadtree());
   const [useMass, setUseMass] = useState(universe.get_mass_calculation());
   const [quadtreeTheta, setQuadtreeTheta] = useState(
     universe.get_quadtree_theta()
@@ -58,9 +68,14 @@ export default function SettingsBar() {
   const [collisionsEnabled, setCollisionsEnabled] = useState(
     universe.get_collisions_enabled()
   );
+  const [forceBackend, setForceBackend] = useState<ForceBackend>(
+    universe.get_force_backend()
+  );
+  // Auto can switch backends as the particle count changes, so read it on every render
+  const activeBackend = universe.get_active_force_backend();
+  const usingQuadtree = activeBackend === ForceBackend.Quadtree;
 
   const previousVelocityVectorsRef = useRef(showVelocityVectors);
-  const quadtreeManuallyDisabledRef = useRef(false);
 
   // Rust uses speed_multiplier * 10 substeps, so 1x speed = 1.0 in Rust
   const multipliers = [-4, -2, -1, -0.5, -0.25, 0, 0.25, 0.5, 1, 2, 4];
@@ -95,11 +110,6 @@ export default function SettingsBar() {
     }
   }, [showVelocityVectors, isPaused]);
 
-  useEffect(() => {
-    universe.set_use_quadtree(useQuadtree);
-    console.log("Quadtree", useQuadtree ? "enabled" : "disabled");
-  }, [useQuadtree]);
-
   useEffect(() => {
     universe.set_collisions_enabled(collisionsEnabled);
     console.log("Collisions", collisionsEnabled ? "enabled" : "disabled");
@@ -111,6 +121,20 @@ export default function SettingsBar() {
     setCollisionsEnabled(universe.get_collisions_enabled());
   }, [render]);
 
+  const changeForceBackend = (backend: ForceBackend) => {
+    setForceBackend(backend);
+    universe.set_force_backend(backend);
+    if (universe.get_active_force_backend() !== ForceBackend.Quadtree) {
+      setViewQuadtree(false);
+    }
+    setRender((prev) => prev + 1);
+  };
+
+  // Keep the selector in sync when undo, redo or a loaded scene changes the backend
+  useEffect(() => {
+    setForceBackend(universe.get_force_backend());
+  }, [render]);
+
   useEffect(() => {
     universe.set_quadtree_theta(quadtreeTheta);
     console.log("Quadtree theta set to", quadtreeTheta);
@@ -122,27 +146,6 @@ export default function SettingsBar() {
     setRender((prev) => prev + 1);
   }, [useMass]);
 
-  // Auto-enable quadtree when particle count reaches 150 (only if not manually disabled)
-  useEffect(() => {
-    const currentParticleCount = universe.get_particle_count();
-    if (
-      currentParticleCount >= 150 &&
-      !useQuadtree &&
-      !quadtreeManuallyDisabledRef.current
-    ) {
-      setUseQuadtree(true);
-      console.log(
-        "Quadtree auto-enabled at",
-        currentParticleCount,
-        "particles"
-      );
-    }
-    // Reset manual disable flag if particle count drops below 150
-    if (currentParticleCount < 150) {
-      quadtreeManuallyDisabledRef.current = false;
-    }
-  }, [render]);
-
   const rewind = () => {
     const currentIndex = multipliers.indexOf(multiplier);
     const newIndex = Math.max(0, currentIndex - 1);
@@ -171,6 +174,7 @@ export default function SettingsBar() {
     // Restore toggles user expects to persist
     universe.set_collisions_enabled(prevCollisions);
     universe.set_mass_calculation(prevUseMass);
+    universe.set_force_backend(forceBackend);
     universe.set_is_paused(isPaused);
     setMultiplier(1);
     universe.set_speed(1.0);
@@ -350,33 +354,23 @@ export default function SettingsBar() {
             </button>
           </div>
 
-          {/* Quadtree Controls
-          <div className="flex items-center gap-1 sm:gap-2 px-2 sm:px-4 border-l border-gray-200 pl-3">
-            <button
-              onClick={() => {
-                const newValue = !useQuadtree;
-                setUseQuadtree(newValue);
-                console.log(
-                  `Quadtree manually ${newValue ? "enabled" : "disabled"}`
-                );
-                // Track if user manually disabled quadtree when particle count >= 150
-                if (!newValue && universe.get_particle_count() >= 150) {
-                  quadtreeManuallyDisabledRef.current = true;
-                  // If the quadtree was disabled, also turn off the visualizer
-                  setViewQuadtree(false);
-                } else if (newValue) {
-                  // Reset flag when manually enabled
-                  quadtreeManuallyDisabledRef.current = false;
-                }
-                setRender((prev) => prev + 1);
-              }}
-              className={`p-1.5 sm:p-2 rounded cursor-pointer transition-all duration-200 ${
-                useQuadtree ? "bg-blue-100" : "hover:bg-gray-100"
-              }`}
-              title={useQuadtree ? "Quadtree Enabled" : "Quadtree Disabled"}
+          {/* Force Backend Controls */}
+          <div className="flex items-center gap-1 sm:gap-2 px-2 sm:px-4">
+            <Grid3x3 className="w-4 h-4 sm:w-5 sm:h-5 text-gray-500" />
+            <select
+              value={forceBackend}
+              onChange={(e) => changeForceBackend(Number(e.target.value))}
+              className="px-1 py-0.5 text-xs sm:text-sm border border-gray-300 rounded cursor-pointer focus:outline-none focus:ring-1 focus:ring-blue-500"
+              title="Force backend (Auto picks one from the particle count)"
             >
-              <Grid3x3 className="w-4 h-4 sm:w-5 sm:h-5" />
-            </button>
+              {forceBackends.map(({ backend, label }) => (
+                <option key={backend} value={backend}>
+                  {backend === ForceBackend.Auto
+                    ? `${label} (${backendLabel(activeBackend)})`
+                    : label}
+                </option>
+              ))}
+            </select>
             <button
               onClick={() => {
                 setViewQuadtree(!viewQuadtree);
@@ -386,7 +380,7 @@ export default function SettingsBar() {
                 viewQuadtree ? "bg-blue-100" : "hover:bg-gray-100"
               }`}
               title={viewQuadtree ? "Hide Quadtree" : "Show Quadtree"}
-              disabled={!useQuadtree}
+              disabled={!usingQuadtree}
             >
               <Eye className="w-4 h-4 sm:w-5 sm:h-5" />
             </button>
@@ -403,10 +397,10 @@ export default function SettingsBar() {
                 max="2"
                 className="w-12 sm:w-16 px-1 py-0.5 text-xs sm:text-sm border border-gray-300 rounded focus:outline-none focus:ring-1 focus:ring-blue-500"
                 title="Quadtree Theta (Barnes-Hut threshold)"
-                disabled={!useQuadtree}
+                disabled={!usingQuadtree}
               />
             </div>
-          </div> */}
+          </div>
 
           <div className="flex items-center gap-1 sm:gap-2 px-2 sm:px-4">
             <div className="w-8 sm:w-12 text-center text-xs sm:text-base">
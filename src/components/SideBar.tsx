@@ -1,6 +1,11 @@
 import { useState, useRef, useEffect } from "react";
 import { useSimulation } from "../contexts/SimulationContext";
 import { Atom, Magnet, Plus, Minus } from "lucide-react";
+import {
+  SpawnOptions,
+  SpawnShape,
+  VelocityDistribution,
+} from "physics-engine";
 
 type AddType = "particle" | "magnet";
 
@@ -32,37 +37,26 @@ export default function SideBar() {
   };
 
   const addParticles = (count: number) => {
-    for (let i = 0; i < count; i++) {
-      // Add particles using explicit API to set mass/radius/charge
-      const px =
-        Math.random() * universe.get_spawn_range() -
-        universe.get_spawn_range() / 2;
-      const py =
-        Math.random() * universe.get_spawn_range() -
-        universe.get_spawn_range() / 2;
-      const vx = Math.random() * 50 - 25;
-      const vy = Math.random() * 50 - 25;
-      // color 0 lets the engine choose based on charge sign
-      universe.add_particle(px, py, vx, vy, addRadius, addMass, 0, addCharge);
-      // Apply fixed property if requested
-      if (addFixed) {
-        (universe as any).update_particle_fixed(
-          (universe.get_particle_count() as number) - 1,
-          true
-        );
-      }
-    }
+    // Spawned engine-side in one call (one undo step), uniform over the spawn square
+    const options = new SpawnOptions(count, SpawnShape.Square);
+    options.velocity = VelocityDistribution.Uniform;
+    options.speed = 25;
+    options.charge_value = addCharge;
+    options.mass_value = addMass;
+    options.radius = addRadius;
+    options.fixed = addFixed;
+    universe.spawn_particles(options);
+    options.free();
     setRender((prev) => prev + 1);
   };
 
   const addMagnets = (count: number) => {
     for (let i = 0; i < count; i++) {
-      const px =
-        Math.random() * universe.get_spawn_range() -
-        universe.get_spawn_range() / 2;
-      const py =
-        Math.random() * universe.get_spawn_range() -
-        universe.get_spawn_range() / 2;
+      // draw from the engine's seeded generator so runs are reproducible
+      const spawn = universe.random_spawn_position();
+      const px = spawn.x;
+      const py = spawn.y;
+      spawn.free();
       // Use the wasm API added for magnets (full constructor)
       (universe as any).add_magnet(
         px,
@@ -83,21 +77,22 @@ export default function SideBar() {
 
   const randomizeFields = () => {
     if (addType === "particle") {
-      setAddMass(Math.random() * 4.9 + 0.1);
-      setAddCharge((Math.random() - 0.5) * 100);
-      setAddRadius(Math.random() * 18 + 2);
-      setAddFixed(Math.random() > 0.5);
+      setAddMass(universe.random_range(0.1, 5));
+      setAddCharge(universe.random_range(-50, 50));
+      setAddRadius(universe.random_range(2, 20));
+      setAddFixed(universe.random_range(0, 1) > 0.5);
     } else {
-      setAddMagnetAngle(Math.random() * 360);
+      setAddMagnetAngle(universe.random_range(0, 360));
       setAddMagnetStrength(
-        (Math.random() * 45 + 5) * (Math.random() > 0.5 ? 1 : -1)
+        universe.random_range(5, 50) *
+          (universe.random_range(0, 1) > 0.5 ? 1 : -1)
       );
-      setAddMagnetSize(Math.random() * 90 + 30);
-      setAddMagnetThickness(Math.random() * 35 + 5);
-      setAddMass(Math.random() * 4.9 + 0.1);
-      setAddMagnetColorNorth(Math.floor(Math.random() * 0xffffff));
-      setAddMagnetColorSouth(Math.floor(Math.random() * 0xffffff));
-      setAddFixed(Math.random() > 0.5);
+      setAddMagnetSize(universe.random_range(30, 120));
+      setAddMagnetThickness(universe.random_range(5, 40));
+      setAddMass(universe.random_range(0.1, 5));
+      setAddMagnetColorNorth(Math.floor(universe.random_range(0, 0xffffff)));
+      setAddMagnetColorSouth(Math.floor(universe.random_range(0, 0xffffff)));
+      setAddFixed(universe.random_range(0, 1) > 0.5);
     }
     setRender((prev) => prev + 1);
   };
@@ -60,11 +60,12 @@ export default function Error() {
       const count = universe.get_particles().length;
       if (count < 10) {
         for (let i = 0; i < 1; i++) {
-          const x = Math.random() * width - width / 2;
+          // draw from the engine's seeded generator so runs stay reproducible
+          const x = universe.random_range(-width / 2, width / 2);
           const y = 0;
-          const vx = (Math.random() - 0.5) * 100;
-          const vy = Math.random() * 50 + 50;
-          universe.add_particle_simple(x, y, vx, vy, Math.random() * 10 - 5);
+          const vx = universe.random_range(-50, 50);
+          const vy = universe.random_range(50, 100);
+          universe.add_particle_simple(x, y, vx, vy, universe.random_range(-5, 5));
         }
       }
     };
@@ -88,9 +88,10 @@ export default function Home() {
       const count = universe.get_particles().length;
       if (count < 10) {
         for (let i = 0; i < 1; i++) {
-          const x = Math.random() * width - width / 2;
-          const y = Math.random() * 200;
-          universe.add_particle_simple(x, y, 0.0, 0.0, Math.random() * 40 - 20);
+          // draw from the engine's seeded generator so runs stay reproducible
+          const x = universe.random_range(-width / 2, width / 2);
+          const y = universe.random_range(0, 200);
+          universe.add_particle_simple(x, y, 0.0, 0.0, universe.random_range(-20, 20));
         }
       }
     };
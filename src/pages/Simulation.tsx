@@ -1,4 +1,4 @@
-import { Universe } from "physics-engine";
+import { ForceBackend, Universe } from "physics-engine";
 import Transitions from "../utils/Transitions";
 import { Application, extend } from "@pixi/react";
 import { useWindowDimension } from "../utils/useWindowDimension";
@@ -76,6 +76,10 @@ function SimulationContent() {
         <div className="absolute top-4 right-4 bg-black/70 text-white p-4 rounded-lg text-sm font-mono space-y-1 pointer-events-none">
           <div>FPS: {fps}</div>
           <div>Particles: {universe.get_particle_count()}</div>
+          <div>
+            Forces: {ForceBackend[universe.get_active_force_backend()]}
+            {universe.get_force_backend() === ForceBackend.Auto && " (auto)"}
+          </div>
         </div>
       )}
 
@@ -0,0 +1,456 @@
+use wasm_bindgen::prelude::*;
+use serde::{ Serialize, Deserialize };
+
+use crate::{ parallel, Universe, Vec2 };
+use crate::fmm::{ MAX_FMM_ORDER, MIN_FMM_ORDER };
+
+// Substeps each candidate runs for when Auto measures costs
+const PROBE_STEPS: usize = 100;
+// Substeps before the measured choice is re-checked
+const REPROBE_STEPS: usize = 20_000;
+// Relative change in particle count that triggers a re-check
+const REPROBE_DRIFT: f64 = 0.25;
+
+pub(crate) fn default_fmm_threshold() -> usize {
+    40_000
+}
+
+// How the Coulomb forces between particles are summed
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum ForceBackend {
+    // Exact pairwise sum, O(n^2)
+    Direct,
+    // Barnes-Hut tree with quadrupole moments, O(n log n), accuracy set by quadtree_theta
+    Quadtree,
+    // Fast multipole method, O(n), accuracy set by fmm_order
+    Fmm,
+    // FFT on a charge mesh, O(n + m log m) for m cells; forces are smoothed over about a cell
+    // unless the short-range (P3M) correction is on
+    ParticleMesh,
+    // Picks Direct, Quadtree or Fmm from the particle count (quadtree_threshold, fmm_threshold),
+    // or from measured step costs when auto_backend_timing is on
+    #[default]
+    Auto,
+}
+
+// Measured-cost state for ForceBackend::Auto; rebuilt on demand, never saved
+#[derive(Clone, Default)]
+pub(crate) struct AutoBackend {
+    // backend in use, None until the first probe starts
+    current: Option<ForceBackend>,
+    // backends being compared and the mean step cost measured for each so far
+    candidates: Vec<ForceBackend>,
+    costs: Vec<f64>,
+    // steps timed for the candidate being probed and their total cost
+    probe_steps: usize,
+    probe_total: f64,
+    // particle count and steps taken since the choice was made
+    particles: usize,
+    steps: usize,
+}
+
+impl AutoBackend {
+    fn probing(&self) -> bool {
+        self.costs.len() < self.candidates.len()
+    }
+
+    fn is_stale(&self, particles: usize) -> bool {
+        let drift = particles.abs_diff(self.particles) as f64;
+        self.current.is_none() ||
+            drift > REPROBE_DRIFT * (self.particles.max(1) as f64) ||
+            (!self.probing() && self.steps >= REPROBE_STEPS)
+    }
+
+    fn start_probe(&mut self, candidates: Vec<ForceBackend>, particles: usize) {
+        self.current = candidates.first().copied();
+        self.candidates = candidates;
+        self.costs.clear();
+        self.probe_steps = 0;
+        self.probe_total = 0.0;
+        self.particles = particles;
+        self.steps = 0;
+    }
+
+    fn record(&mut self, elapsed: f64) {
+        if !self.probing() {
+            self.steps += 1;
+            return;
+        }
+        self.probe_steps += 1;
+        self.probe_total += elapsed;
+        if self.probe_steps < PROBE_STEPS {
+            return;
+        }
+        self.costs.push(self.probe_total / (PROBE_STEPS as f64));
+        self.probe_steps = 0;
+        self.probe_total = 0.0;
+        self.current = if self.probing() {
+            Some(self.candidates[self.costs.len()])
+        } else {
+            // ties keep the earlier, more accurate candidate
+            let mut best = 0;
+            for (i, cost) in self.costs.iter().enumerate() {
+                if *cost < self.costs[best] {
+                    best = i;
+                }
+            }
+            Some(self.candidates[best])
+        };
+    }
+}
+
+// Milliseconds from a monotonic-enough clock; only differences are used
+#[cfg(target_arch = "wasm32")]
+fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1e3
+}
+
+// Error of a backend against the direct sum, measured on a sample of particles. Errors are
+// relative to the mean direct acceleration of the sample, so particles that happen to feel
+// almost no force do not dominate.
+#[derive(Serialize)]
+pub(crate) struct AccuracyReport {
+    backend: ForceBackend,
+    samples: usize,
+    pub(crate) mean_error: f64,
+    pub(crate) max_error: f64,
+    // particle index with the largest error
+    worst_particle: Option<usize>,
+}
+
+impl Universe {
+    // Coulomb accelerations between particles, with particle i at positions[i]
+    pub(crate) fn coulomb_accelerations(&self, backend: ForceBackend, positions: &[Vec2]) -> Vec<Vec2> {
+        match self.resolve_backend(backend) {
+            ForceBackend::Direct => self.direct_accelerations(positions),
+            ForceBackend::Quadtree => self.quadtree_accelerations(positions),
+            ForceBackend::Fmm => self.fmm_accelerations(positions),
+            ForceBackend::ParticleMesh => self.mesh_accelerations(positions),
+            ForceBackend::Auto => unreachable!("Auto resolves to a concrete backend"),
+        }
+    }
+
+    // Backend that actually sums the Coulomb forces right now
+    pub(crate) fn active_backend(&self) -> ForceBackend {
+        self.resolve_backend(self.force_backend)
+    }
+
+    fn resolve_backend(&self, backend: ForceBackend) -> ForceBackend {
+        if backend != ForceBackend::Auto {
+            return backend;
+        }
+        match self.auto_backend.current {
+            Some(current) if self.auto_backend_timing => current,
+            _ => self.backend_for_count(self.particles.len()),
+        }
+    }
+
+    // Count-based choice: the direct sum is fastest for small scenes, the tree up to tens of
+    // thousands of particles and the FMM beyond that
+    fn backend_for_count(&self, particles: usize) -> ForceBackend {
+        if particles < self.quadtree_threshold {
+            ForceBackend::Direct
+        } else if particles < self.fmm_threshold {
+            ForceBackend::Quadtree
+        } else {
+            ForceBackend::Fmm
+        }
+    }
+
+    // Starts timing a physics step when Auto is choosing by measured cost. Returns the start
+    // time to hand to finish_backend_timing, or None when nothing is measured.
+    pub(crate) fn start_backend_timing(&mut self) -> Option<f64> {
+        if self.force_backend != ForceBackend::Auto || !self.auto_backend_timing {
+            return None;
+        }
+        let n = self.particles.len();
+        if self.auto_backend.is_stale(n) {
+            // compare the count-based pick with its neighbours; the direct sum is never
+            // probed on scenes where a single step would take far too long
+            let order = [ForceBackend::Direct, ForceBackend::Quadtree, ForceBackend::Fmm];
+            let pick = order
+                .iter()
+                .position(|b| *b == self.backend_for_count(n))
+                .unwrap_or(0);
+            let candidates = order[pick.saturating_sub(1)..(pick + 2).min(order.len())].to_vec();
+            self.auto_backend.start_probe(candidates, n);
+        }
+        Some(now_ms())
+    }
+
+    pub(crate) fn finish_backend_timing(&mut self, started: Option<f64>) {
+        if let Some(started) = started {
+            self.auto_backend.record(now_ms() - started);
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    pub fn set_force_backend(&mut self, backend: ForceBackend) {
+        self.edit_settings(|u| {
+            u.force_backend = backend;
+        });
+    }
+
+    pub fn get_force_backend(&self) -> ForceBackend {
+        self.force_backend
+    }
+
+    // Backend summing the forces this step; differs from get_force_backend only under Auto
+    pub fn get_active_force_backend(&self) -> ForceBackend {
+        self.active_backend()
+    }
+
+    // Under Auto, particle counts from here up use the quadtree instead of the direct sum
+    pub fn set_quadtree_threshold(&mut self, threshold: usize) {
+        self.edit_settings(|u| {
+            u.quadtree_threshold = threshold;
+        });
+    }
+
+    pub fn get_quadtree_threshold(&self) -> usize {
+        self.quadtree_threshold
+    }
+
+    // Under Auto, particle counts from here up use the FMM instead of the quadtree
+    pub fn set_fmm_threshold(&mut self, threshold: usize) {
+        self.edit_settings(|u| {
+            u.fmm_threshold = threshold;
+        });
+    }
+
+    pub fn get_fmm_threshold(&self) -> usize {
+        self.fmm_threshold
+    }
+
+    // When on, Auto times a few hundred steps with each nearby backend and keeps the fastest,
+    // checking again as the particle count changes. The choice depends on the machine, so
+    // rewinding or replaying a seeded scene may no longer reproduce it exactly.
+    pub fn set_auto_backend_timing(&mut self, timing: bool) {
+        self.edit_settings(|u| {
+            u.auto_backend_timing = timing;
+        });
+        self.auto_backend = AutoBackend::default();
+    }
+
+    pub fn get_auto_backend_timing(&self) -> bool {
+        self.auto_backend_timing
+    }
+
+    // Threads the force loops are spread over; 1 unless built with the `parallel` feature on a
+    // target with threads
+    pub fn get_force_threads(&self) -> usize {
+        parallel::threads()
+    }
+
+    // Number of expansion terms per axis; clamped to 2..=12
+    pub fn set_fmm_order(&mut self, order: usize) {
+        self.edit_settings(|u| {
+            u.fmm_order = order.clamp(MIN_FMM_ORDER, MAX_FMM_ORDER);
+        });
+    }
+
+    pub fn get_fmm_order(&self) -> usize {
+        self.fmm_order
+    }
+
+    // Compares `backend` (Auto meaning the active one) with the direct sum on up to `samples`
+    // evenly spaced particles at the current positions. Returns
+    // { backend, samples, mean_error, max_error, worst_particle }.
+    // Uses no randomness, so checking accuracy never changes how a seeded scene plays out.
+    pub fn force_accuracy_report(&self, backend: ForceBackend, samples: usize) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.accuracy_report(backend, samples)).unwrap()
+    }
+}
+
+impl Universe {
+    pub(crate) fn accuracy_report(&self, backend: ForceBackend, samples: usize) -> AccuracyReport {
+        let positions: Vec<Vec2> = self.particles
+            .iter()
+            .map(|p| p.pos)
+            .collect();
+        let n = positions.len();
+        let count = samples.min(n);
+        let backend = self.resolve_backend(backend);
+        let approximate = self.coulomb_accelerations(backend, &positions);
+        let sources = self.source_indices();
+
+        let mut report = AccuracyReport {
+            backend,
+            samples: count,
+            mean_error: 0.0,
+            max_error: 0.0,
+            worst_particle: None,
+        };
+        let mut total_error = 0.0;
+        let mut total_magnitude = 0.0;
+        let mut worst = 0.0;
+        for s in 0..count {
+            let i = (s * n) / count;
+            let exact = self.direct_acceleration(i, &positions, &sources);
+            let error = (approximate[i] - exact).magnitude();
+            total_error += error;
+            total_magnitude += exact.magnitude();
+            if report.worst_particle.is_none() || error > worst {
+                worst = error;
+                report.worst_particle = Some(i);
+            }
+        }
+        if total_magnitude > 0.0 {
+            let mean_magnitude = total_magnitude / (count as f64);
+            report.mean_error = total_error / (count as f64) / mean_magnitude;
+            report.max_error = worst / mean_magnitude;
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn::{ SpawnOptions, SpawnShape, ValueDistribution };
+
+    // Mixed-charge Gaussian blob, dense enough that every backend takes its far-field path
+    fn blob(count: usize) -> Universe {
+        let mut universe = Universe::new_empty();
+        universe.set_seed(7);
+        let mut options = SpawnOptions::new(count, SpawnShape::Gaussian);
+        options.size = 400.0;
+        options.charge = ValueDistribution::Alternating;
+        options.radius = 2.0;
+        universe.spawn_particles(&options);
+        universe
+    }
+
+    #[test]
+    fn fmm_error_falls_with_order() {
+        let mut universe = blob(3000);
+        let mut previous = f64::INFINITY;
+        for (order, bound) in [(2, 1e-2), (4, 1e-3), (8, 1e-5), (12, 1e-6)] {
+            universe.set_fmm_order(order);
+            let report = universe.accuracy_report(ForceBackend::Fmm, 500);
+            assert!(report.mean_error < bound, "order {}: {}", order, report.mean_error);
+            assert!(report.mean_error < previous, "order {}: {}", order, report.mean_error);
+            previous = report.mean_error;
+        }
+    }
+
+    #[test]
+    fn quadtree_error_is_bounded() {
+        let universe = blob(3000);
+        let report = universe.accuracy_report(ForceBackend::Quadtree, 500);
+        assert!(report.mean_error < 2e-3, "{}", report.mean_error);
+    }
+
+    // Feeds one candidate's worth of probe steps at `cost` each
+    fn probe(auto: &mut AutoBackend, cost: f64) {
+        for _ in 0..PROBE_STEPS {
+            auto.record(cost);
+        }
+    }
+
+    #[test]
+    fn auto_follows_the_count_thresholds() {
+        let mut universe = Universe::new_empty();
+        universe.set_quadtree_threshold(4);
+        universe.set_fmm_threshold(8);
+        let mut expected = vec![ForceBackend::Direct; 4];
+        expected.extend([ForceBackend::Quadtree; 4]);
+        expected.extend([ForceBackend::Fmm; 2]);
+        for (i, backend) in expected.into_iter().enumerate() {
+            assert_eq!(universe.get_active_force_backend(), backend, "{} particles", i);
+            universe.add_particle_simple((i as f64) * 50.0, 0.0, 0.0, 0.0, 1.0);
+        }
+
+        // explicit backends ignore the count
+        universe.set_force_backend(ForceBackend::Direct);
+        assert_eq!(universe.get_active_force_backend(), ForceBackend::Direct);
+        universe.set_force_backend(ForceBackend::ParticleMesh);
+        assert_eq!(universe.get_active_force_backend(), ForceBackend::ParticleMesh);
+    }
+
+    #[test]
+    fn probe_keeps_the_cheapest_candidate() {
+        let mut auto = AutoBackend::default();
+        let candidates = vec![ForceBackend::Direct, ForceBackend::Quadtree, ForceBackend::Fmm];
+        auto.start_probe(candidates.clone(), 1000);
+        assert_eq!(auto.current, Some(ForceBackend::Direct));
+        probe(&mut auto, 3.0);
+        assert_eq!(auto.current, Some(ForceBackend::Quadtree));
+        probe(&mut auto, 1.0);
+        assert_eq!(auto.current, Some(ForceBackend::Fmm));
+        probe(&mut auto, 2.0);
+        assert!(!auto.probing());
+        assert_eq!(auto.current, Some(ForceBackend::Quadtree));
+
+        // ties keep the earlier candidate
+        auto.start_probe(candidates, 1000);
+        probe(&mut auto, 2.0);
+        probe(&mut auto, 2.0);
+        probe(&mut auto, 2.0);
+        assert_eq!(auto.current, Some(ForceBackend::Direct));
+    }
+
+    #[test]
+    fn probe_is_redone_on_count_drift_or_age() {
+        let mut auto = AutoBackend::default();
+        assert!(auto.is_stale(1000));
+        auto.start_probe(vec![ForceBackend::Quadtree, ForceBackend::Fmm], 1000);
+        probe(&mut auto, 1.0);
+        probe(&mut auto, 1.0);
+        assert!(!auto.is_stale(1000));
+        assert!(!auto.is_stale(800));
+        assert!(!auto.is_stale(1250));
+        assert!(auto.is_stale(1251));
+        assert!(auto.is_stale(749));
+
+        for _ in 0..REPROBE_STEPS - 1 {
+            auto.record(1.0);
+        }
+        assert!(!auto.is_stale(1000));
+        auto.record(1.0);
+        assert!(auto.is_stale(1000));
+    }
+
+    #[test]
+    fn timed_auto_probes_around_the_count_pick() {
+        let mut universe = Universe::new_empty();
+        universe.set_quadtree_threshold(4);
+        universe.set_auto_backend_timing(true);
+        for i in 0..6 {
+            universe.add_particle_simple((i as f64) * 50.0, 0.0, 0.0, 0.0, 1.0);
+        }
+
+        assert!(universe.start_backend_timing().is_some());
+        let neighbours = vec![ForceBackend::Direct, ForceBackend::Quadtree, ForceBackend::Fmm];
+        assert_eq!(universe.auto_backend.candidates, neighbours);
+        assert_eq!(universe.get_active_force_backend(), ForceBackend::Direct);
+        probe(&mut universe.auto_backend, 5.0);
+        probe(&mut universe.auto_backend, 9.0);
+        probe(&mut universe.auto_backend, 1.0);
+        assert_eq!(universe.get_active_force_backend(), ForceBackend::Fmm);
+
+        // a quarter more particles starts a new probe from the count pick's neighbours
+        for i in 0..2 {
+            universe.add_particle_simple((i as f64) * 50.0, 100.0, 0.0, 0.0, 1.0);
+        }
+        universe.start_backend_timing();
+        assert!(universe.auto_backend.probing());
+        assert_eq!(universe.get_active_force_backend(), ForceBackend::Direct);
+
+        // without timing Auto goes back to the count
+        universe.set_auto_backend_timing(false);
+        assert_eq!(universe.start_backend_timing(), None);
+        assert_eq!(universe.get_active_force_backend(), ForceBackend::Quadtree);
+    }
+}
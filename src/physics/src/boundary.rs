@@ -0,0 +1,107 @@
+use wasm_bindgen::prelude::*;
+use serde::{ Serialize, Deserialize };
+
+use crate::{ Universe, Vec2 };
+
+// What happens at the edges of the simulation box (centred on the origin)
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Boundary {
+    // No box: entities fly off forever
+    #[default]
+    Open,
+    // Walls bounce entities back, scaled by the restitution
+    Reflecting,
+    // Leaving one side re-enters from the opposite side. Only the particle-mesh backend
+    // includes periodic images; the others ignore charges across the edges.
+    Periodic,
+}
+
+pub(crate) fn default_boundary_size() -> f64 {
+    2000.0
+}
+
+// Keeps one coordinate inside [-half + r, half - r], flipping the velocity on a bounce
+fn reflect_axis(pos: &mut f64, vel: &mut f64, half: f64, radius: f64, restitution: f64) {
+    let limit = (half - radius).max(0.0);
+    if *pos > limit {
+        *pos = (2.0 * limit - *pos).max(-limit);
+        *vel = -vel.abs() * restitution;
+    } else if *pos < -limit {
+        *pos = (-2.0 * limit - *pos).min(limit);
+        *vel = vel.abs() * restitution;
+    }
+}
+
+fn wrap_axis(pos: &mut f64, half: f64) {
+    if *pos >= half || *pos < -half {
+        *pos = (*pos + half).rem_euclid(2.0 * half) - half;
+    }
+}
+
+impl Universe {
+    fn constrain(&self, pos: &mut Vec2, vel: &mut Vec2, radius: f64) {
+        let half_w = self.boundary_width * 0.5;
+        let half_h = self.boundary_height * 0.5;
+        match self.boundary {
+            Boundary::Open => {}
+            Boundary::Reflecting => {
+                reflect_axis(&mut pos.x, &mut vel.x, half_w, radius, self.restitution);
+                reflect_axis(&mut pos.y, &mut vel.y, half_h, radius, self.restitution);
+            }
+            Boundary::Periodic => {
+                wrap_axis(&mut pos.x, half_w);
+                wrap_axis(&mut pos.y, half_h);
+            }
+        }
+    }
+
+    // Applied after every substep to free particles and magnets
+    pub(crate) fn apply_boundary(&mut self) {
+        if self.boundary == Boundary::Open {
+            return;
+        }
+        for i in 0..self.particles.len() {
+            if self.particles[i].fixed {
+                continue;
+            }
+            let (mut pos, mut vel) = (self.particles[i].pos, self.particles[i].vel);
+            self.constrain(&mut pos, &mut vel, self.particles[i].radius as f64);
+            self.particles[i].pos = pos;
+            self.particles[i].vel = vel;
+        }
+        for i in 0..self.magnets.len() {
+            if self.magnets[i].fixed {
+                continue;
+            }
+            let (mut pos, mut vel) = (self.magnets[i].pos, self.magnets[i].vel);
+            self.constrain(&mut pos, &mut vel, (self.magnets[i].size as f64) * 0.5);
+            self.magnets[i].pos = pos;
+            self.magnets[i].vel = vel;
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Box of `width` x `height` simulation units centred on the origin
+    pub fn set_boundary(&mut self, boundary: Boundary, width: f64, height: f64) {
+        self.edit_settings(|u| {
+            u.boundary = boundary;
+            u.boundary_width = width.max(1.0);
+            u.boundary_height = height.max(1.0);
+        });
+    }
+
+    pub fn get_boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn get_boundary_width(&self) -> f64 {
+        self.boundary_width
+    }
+
+    pub fn get_boundary_height(&self) -> f64 {
+        self.boundary_height
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{ Universe, Vec2 };
+use crate::fields::{ FieldKind, Polylines };
+
+// Regular grid of sampled values, row-major with y as the outer index
+struct ScalarGrid<'a> {
+    values: &'a [f64],
+    nx: usize,
+    ny: usize,
+    origin: Vec2,
+    step: Vec2,
+}
+
+impl ScalarGrid<'_> {
+    fn value(&self, gx: usize, gy: usize) -> f64 {
+        self.values[gy * self.nx + gx]
+    }
+
+    // Edge keys: 2 * node for the edge towards +x, 2 * node + 1 for the edge towards +y
+    fn horizontal_edge(&self, gx: usize, gy: usize) -> usize {
+        (gy * self.nx + gx) * 2
+    }
+
+    fn vertical_edge(&self, gx: usize, gy: usize) -> usize {
+        (gy * self.nx + gx) * 2 + 1
+    }
+
+    // Point where the level crosses the given edge (linear interpolation between its nodes)
+    fn crossing(&self, edge: usize, level: f64) -> Vec2 {
+        let node = edge / 2;
+        let gx = node % self.nx;
+        let gy = node / self.nx;
+        let (ox, oy) = if edge.is_multiple_of(2) { (1, 0) } else { (0, 1) };
+        let a = self.value(gx, gy);
+        let b = self.value(gx + ox, gy + oy);
+        let t = if b != a { ((level - a) / (b - a)).clamp(0.0, 1.0) } else { 0.5 };
+        Vec2::new(
+            self.origin.x + ((gx as f64) + t * (ox as f64)) * self.step.x,
+            self.origin.y + ((gy as f64) + t * (oy as f64)) * self.step.y
+        )
+    }
+
+    // Marching squares for one level, returning segments as pairs of edge keys
+    fn segments(&self, level: f64) -> Vec<[usize; 2]> {
+        let mut segments = Vec::new();
+        for gy in 0..self.ny - 1 {
+            for gx in 0..self.nx - 1 {
+                let v00 = self.value(gx, gy);
+                let v10 = self.value(gx + 1, gy);
+                let v11 = self.value(gx + 1, gy + 1);
+                let v01 = self.value(gx, gy + 1);
+                let case =
+                    ((v00 > level) as u8) |
+                    (((v10 > level) as u8) << 1) |
+                    (((v11 > level) as u8) << 2) |
+                    (((v01 > level) as u8) << 3);
+
+                let bottom = self.horizontal_edge(gx, gy);
+                let top = self.horizontal_edge(gx, gy + 1);
+                let left = self.vertical_edge(gx, gy);
+                let right = self.vertical_edge(gx + 1, gy);
+                let centre_above = (v00 + v10 + v11 + v01) * 0.25 > level;
+
+                match case {
+                    1 | 14 => segments.push([left, bottom]),
+                    2 | 13 => segments.push([bottom, right]),
+                    3 | 12 => segments.push([left, right]),
+                    4 | 11 => segments.push([right, top]),
+                    6 | 9 => segments.push([bottom, top]),
+                    7 | 8 => segments.push([left, top]),
+                    // Saddles: the centre value decides which diagonal pair is connected
+                    5 => {
+                        if centre_above {
+                            segments.push([bottom, right]);
+                            segments.push([top, left]);
+                        } else {
+                            segments.push([left, bottom]);
+                            segments.push([right, top]);
+                        }
+                    }
+                    10 => {
+                        if centre_above {
+                            segments.push([left, bottom]);
+                            segments.push([right, top]);
+                        } else {
+                            segments.push([bottom, right]);
+                            segments.push([top, left]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        segments
+    }
+
+    // Joins segments that share an edge crossing into polylines (open chains first, then loops)
+    fn stitch(&self, segments: &[[usize; 2]], level: f64) -> Vec<Vec<Vec2>> {
+        let mut by_edge: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, segment) in segments.iter().enumerate() {
+            by_edge.entry(segment[0]).or_default().push(i);
+            by_edge.entry(segment[1]).or_default().push(i);
+        }
+
+        let mut used = vec![false; segments.len()];
+        let mut lines = Vec::new();
+        let open_starts = segments
+            .iter()
+            .enumerate()
+            .flat_map(|(i, s)| [(i, s[0]), (i, s[1])])
+            .filter(|(_, edge)| by_edge[edge].len() == 1);
+        let loop_starts = segments
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (i, s[0]));
+        let starts: Vec<(usize, usize)> = open_starts.chain(loop_starts).collect();
+
+        for (first, start_edge) in starts {
+            if used[first] {
+                continue;
+            }
+            let mut line = vec![self.crossing(start_edge, level)];
+            let mut segment = first;
+            let mut edge = start_edge;
+            loop {
+                used[segment] = true;
+                let [a, b] = segments[segment];
+                edge = if a == edge { b } else { a };
+                line.push(self.crossing(edge, level));
+                match by_edge[&edge].iter().find(|&&s| !used[s]) {
+                    Some(&next) => {
+                        segment = next;
+                    }
+                    None => {
+                        break;
+                    }
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+// Evenly spaced quantiles of the sampled values, so automatic levels follow where the
+// potential actually varies in view instead of being dominated by the peaks at charges
+fn automatic_levels(values: &[f64], count: usize) -> Vec<f64> {
+    let mut sorted: Vec<f64> = values
+        .iter()
+        .copied()
+        .filter(|v| v.is_finite())
+        .collect();
+    if sorted.is_empty() || count == 0 {
+        return vec![];
+    }
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mut levels: Vec<f64> = (1..=count)
+        .map(|i| {
+            let q = (i as f64) / ((count + 1) as f64);
+            sorted[((q * ((sorted.len() - 1) as f64)).round() as usize).min(sorted.len() - 1)]
+        })
+        .collect();
+    levels.dedup();
+    levels
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Equipotential contours over the view rectangle, sampled on an nx * ny node grid from the
+    // engine's own potential. Pass explicit `levels`, or leave it empty to get
+    // `auto_level_count` levels placed at evenly spaced quantiles of the sampled potential.
+    // The value stored with each polyline is its potential level.
+    #[allow(clippy::too_many_arguments)]
+    pub fn equipotential_contours(
+        &self,
+        kind: FieldKind,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        nx: usize,
+        ny: usize,
+        levels: Vec<f64>,
+        auto_level_count: usize
+    ) -> Polylines {
+        let mut contours = Polylines::default();
+        if nx < 2 || ny < 2 {
+            return contours;
+        }
+
+        let values = self.sample_potential_grid(kind, min_x, min_y, max_x, max_y, nx, ny);
+        let grid = ScalarGrid {
+            values: &values,
+            nx,
+            ny,
+            origin: Vec2::new(min_x, min_y),
+            step: Vec2::new((max_x - min_x) / ((nx - 1) as f64), (max_y - min_y) / ((ny - 1) as f64)),
+        };
+        let levels = if levels.is_empty() {
+            automatic_levels(&values, auto_level_count)
+        } else {
+            levels
+        };
+
+        for level in levels {
+            let segments = grid.segments(level);
+            for line in grid.stitch(&segments, level) {
+                contours.push_line(&line, level);
+            }
+        }
+        contours
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(polylines: &Polylines) -> Vec<Vec<Vec2>> {
+        let (points, offsets) = (polylines.get_points(), polylines.get_offsets());
+        offsets
+            .windows(2)
+            .map(|w| {
+                (w[0] as usize..w[1] as usize)
+                    .map(|i| Vec2::new(points[2 * i], points[2 * i + 1]))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn point_charge() -> Universe {
+        let mut universe = Universe::new_empty();
+        universe.add_particle_simple(0.0, 0.0, 0.0, 0.0, 1.0);
+        universe
+    }
+
+    #[test]
+    fn point_charge_contour_is_a_closed_circle() {
+        let universe = point_charge();
+        let radius = 60.0;
+        let level = universe.potential_at(FieldKind::Electric, radius, 0.0);
+        let contours = universe.equipotential_contours(
+            FieldKind::Electric,
+            -100.0,
+            -100.0,
+            100.0,
+            100.0,
+            101,
+            101,
+            vec![level],
+            0
+        );
+        let lines = lines(&contours);
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        assert!(line.len() > 20);
+        let (first, last) = (line[0], line[line.len() - 1]);
+        assert!(first.distance_from(last) < 1e-9, "contour is not closed");
+        for p in line {
+            // linear interpolation between nodes 2 units apart
+            assert!((p.magnitude() - radius).abs() < 0.1, "point at radius {}", p.magnitude());
+        }
+        assert_eq!(contours.get_values(), vec![level]);
+    }
+
+    #[test]
+    fn contour_leaving_the_view_is_open() {
+        let universe = point_charge();
+        let level = universe.potential_at(FieldKind::Electric, 60.0, 0.0);
+        // the circle of radius 60 crosses the left and bottom edges of this view
+        let contours = universe.equipotential_contours(
+            FieldKind::Electric,
+            -30.0,
+            -30.0,
+            100.0,
+            100.0,
+            131,
+            131,
+            vec![level],
+            0
+        );
+        let lines = lines(&contours);
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        let on_edge = |p: Vec2| (p.x + 30.0).abs() < 1e-9 || (p.y + 30.0).abs() < 1e-9;
+        assert!(on_edge(line[0]) && on_edge(line[line.len() - 1]));
+        assert!(line[0].distance_from(line[line.len() - 1]) > 10.0);
+    }
+
+    #[test]
+    fn saddle_cells_follow_the_centre_value() {
+        // opposite corners high: v00 = v11 = 1, v10 = v01 = 0, centre value 0.5
+        let values = [1.0, 0.0, 0.0, 1.0];
+        let grid = ScalarGrid {
+            values: &values,
+            nx: 2,
+            ny: 2,
+            origin: Vec2::new(0.0, 0.0),
+            step: Vec2::new(1.0, 1.0),
+        };
+        let (bottom, top) = (grid.horizontal_edge(0, 0), grid.horizontal_edge(0, 1));
+        let (left, right) = (grid.vertical_edge(0, 0), grid.vertical_edge(1, 0));
+
+        // centre above the level: the high corners are connected, cutting off the low ones
+        assert_eq!(grid.segments(0.25), vec![[bottom, right], [top, left]]);
+        // centre below the level: the low corners are connected instead
+        assert_eq!(grid.segments(0.75), vec![[left, bottom], [right, top]]);
+
+        let stitched = grid.stitch(&grid.segments(0.25), 0.25);
+        assert_eq!(stitched.len(), 2);
+        assert!(stitched.iter().all(|line| line.len() == 2));
+    }
+
+    #[test]
+    fn automatic_levels_are_quantiles() {
+        let values: Vec<f64> = (0..=100).map(|v| v as f64).collect();
+        assert_eq!(automatic_levels(&values, 3), vec![25.0, 50.0, 75.0]);
+        assert!(automatic_levels(&[f64::NAN], 3).is_empty());
+    }
+}
@@ -0,0 +1,303 @@
+use crate::{ parallel, Universe, Vec2 };
+
+// Pairs handled together in the inner loop; written lane by lane so it compiles to SIMD
+// (wasm simd128, SSE/AVX natively) without relying on float reassociation
+const LANES: usize = 8;
+
+// Rows of pairs are split into blocks of roughly this many pairs, at most MAX_BLOCKS of them.
+// The split depends only on the source count, never on the thread count, so the summation
+// order and therefore the result are always the same.
+const PAIRS_PER_BLOCK: usize = 1 << 16;
+const MAX_BLOCKS: usize = 16;
+
+// Structure-of-arrays copy of the sources and their accumulated fields, reused by every
+// direct-sum evaluation
+#[derive(Clone, Default)]
+pub(crate) struct DirectSum {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    charge: Vec<f64>,
+    blocks: Vec<Block>,
+    field_x: Vec<f64>,
+    field_y: Vec<f64>,
+}
+
+// Sources i in start..end paired with every later source; fields are indexed from `start`
+#[derive(Clone, Default)]
+struct Block {
+    start: usize,
+    end: usize,
+    field_x: Vec<f64>,
+    field_y: Vec<f64>,
+}
+
+struct Sources<'a> {
+    x: &'a [f64],
+    y: &'a [f64],
+    charge: &'a [f64],
+}
+
+impl DirectSum {
+    fn load(&mut self, universe: &Universe, positions: &[Vec2], sources: &[usize]) {
+        self.x.clear();
+        self.y.clear();
+        self.charge.clear();
+        for &j in sources {
+            self.x.push(positions[j].x);
+            self.y.push(positions[j].y);
+            self.charge.push(universe.particles[j].charge);
+        }
+
+        // later rows are shorter, so block boundaries bunch towards the end
+        let n = sources.len();
+        let pairs = (n * n.saturating_sub(1)) / 2;
+        let count = (pairs / PAIRS_PER_BLOCK).clamp(1, MAX_BLOCKS);
+        self.blocks.resize_with(count, Block::default);
+        let boundary = |b: usize| {
+            let remaining = (1.0 - (b as f64) / (count as f64)).sqrt();
+            n - ((n as f64) * remaining).round() as usize
+        };
+        for (b, block) in self.blocks.iter_mut().enumerate() {
+            block.start = boundary(b);
+            block.end = if b + 1 == count { n } else { boundary(b + 1) };
+            block.field_x.clear();
+            block.field_x.resize(n - block.start, 0.0);
+            block.field_y.clear();
+            block.field_y.resize(n - block.start, 0.0);
+        }
+    }
+
+    // Adds the field of every source on every other source, computing each pair once and
+    // applying it to both ends. Matches coulomb_field summed over j != i.
+    fn accumulate_pairs(&mut self, k: f64, min_dist_sq: f64) {
+        let DirectSum { x, y, charge, blocks, field_x, field_y } = self;
+        let sources = Sources { x, y, charge };
+        parallel::for_each_mut(blocks, |block| block.accumulate(&sources, k, min_dist_sq));
+
+        // blocks are combined in order whichever thread finished first
+        let n = x.len();
+        field_x.clear();
+        field_x.resize(n, 0.0);
+        field_y.clear();
+        field_y.resize(n, 0.0);
+        for block in blocks.iter() {
+            for (total, part) in field_x[block.start..].iter_mut().zip(&block.field_x) {
+                *total += part;
+            }
+            for (total, part) in field_y[block.start..].iter_mut().zip(&block.field_y) {
+                *total += part;
+            }
+        }
+    }
+
+    // Field of all sources at a point that is not itself a source
+    fn field_at(&self, k: f64, point: Vec2, min_dist_sq: f64) -> Vec2 {
+        let mut sum_x = 0.0;
+        let mut sum_y = 0.0;
+        for j in 0..self.x.len() {
+            let dx = point.x - self.x[j];
+            let dy = point.y - self.y[j];
+            let scale = pair_scale(k, dx, dy, min_dist_sq);
+            sum_x += self.charge[j] * scale * dx;
+            sum_y += self.charge[j] * scale * dy;
+        }
+        Vec2::new(sum_x, sum_y)
+    }
+}
+
+impl Block {
+    fn accumulate(&mut self, sources: &Sources, k: f64, min_dist_sq: f64) {
+        let Sources { x, y, charge } = *sources;
+        let n = x.len();
+        let (field_x, field_y) = (&mut self.field_x, &mut self.field_y);
+        for i in self.start..self.end {
+            let (xi, yi, qi) = (x[i], y[i], charge[i]);
+            let mut sum_x = [0.0; LANES];
+            let mut sum_y = [0.0; LANES];
+            let rest = i + 1;
+            let offset = rest - self.start;
+            let chunks = x[rest..]
+                .chunks_exact(LANES)
+                .zip(y[rest..].chunks_exact(LANES))
+                .zip(charge[rest..].chunks_exact(LANES))
+                .zip(field_x[offset..].chunks_exact_mut(LANES))
+                .zip(field_y[offset..].chunks_exact_mut(LANES));
+            for ((((xj, yj), qj), fx), fy) in chunks {
+                // copy each stage into lane arrays and write the fields back whole: the buffers
+                // could alias as far as LLVM knows, and interleaved loads and stores would
+                // keep it from vectorising
+                let (xj, yj, qj) = (*lanes(xj), *lanes(yj), *lanes(qj));
+                let (old_x, old_y) = (*lanes(fx), *lanes(fy));
+                let dx: [f64; LANES] = std::array::from_fn(|l| xi - xj[l]);
+                let dy: [f64; LANES] = std::array::from_fn(|l| yi - yj[l]);
+                let dist_sq: [f64; LANES] = std::array::from_fn(|l| {
+                    softened_dist_sq(dx[l], dy[l], min_dist_sq)
+                });
+                let scale: [f64; LANES] = std::array::from_fn(|l| inverse_cube(k, dist_sq[l]));
+                for l in 0..LANES {
+                    sum_x[l] += qj[l] * scale[l] * dx[l];
+                    sum_y[l] += qj[l] * scale[l] * dy[l];
+                }
+                *lanes_mut(fx) = std::array::from_fn(|l| old_x[l] - qi * scale[l] * dx[l]);
+                *lanes_mut(fy) = std::array::from_fn(|l| old_y[l] - qi * scale[l] * dy[l]);
+            }
+            for j in rest + ((n - rest) / LANES) * LANES..n {
+                let dx = xi - x[j];
+                let dy = yi - y[j];
+                let scale = pair_scale(k, dx, dy, min_dist_sq);
+                sum_x[0] += charge[j] * scale * dx;
+                sum_y[0] += charge[j] * scale * dy;
+                field_x[j - self.start] -= qi * scale * dx;
+                field_y[j - self.start] -= qi * scale * dy;
+            }
+            field_x[i - self.start] += sum_x.iter().sum::<f64>();
+            field_y[i - self.start] += sum_y.iter().sum::<f64>();
+        }
+    }
+}
+
+fn lanes(chunk: &[f64]) -> &[f64; LANES] {
+    chunk.try_into().unwrap()
+}
+
+fn lanes_mut(chunk: &mut [f64]) -> &mut [f64; LANES] {
+    chunk.try_into().unwrap()
+}
+
+// k / d^3 with d the softened distance, so k*q*scale*r is the field of charge q at offset r;
+// zero for coincident points when there is no softening
+#[inline(always)]
+fn pair_scale(k: f64, dx: f64, dy: f64, min_dist_sq: f64) -> f64 {
+    inverse_cube(k, softened_dist_sq(dx, dy, min_dist_sq))
+}
+
+// Both helpers select rather than branch so the lanes stay vectorised
+#[inline(always)]
+fn softened_dist_sq(dx: f64, dy: f64, min_dist_sq: f64) -> f64 {
+    let r_sq = dx * dx + dy * dy;
+    if r_sq > min_dist_sq { r_sq } else { min_dist_sq }
+}
+
+#[inline(always)]
+fn inverse_cube(k: f64, dist_sq: f64) -> f64 {
+    let scale = k / (dist_sq * dist_sq.sqrt());
+    if dist_sq > 1e-16 { scale } else { 0.0 }
+}
+
+impl Universe {
+    // Exact Coulomb accelerations with particle i at positions[i]. Each source pair is visited
+    // once; tracers only feel the sources.
+    pub(crate) fn direct_accelerations(&self, positions: &[Vec2]) -> Vec<Vec2> {
+        let sources = self.source_indices();
+        let min_dist_sq = self.min_interaction_distance * self.min_interaction_distance;
+        let mut direct = self.direct.borrow_mut();
+        direct.load(self, positions, &sources);
+        direct.accumulate_pairs(self.coulomb_constant, min_dist_sq);
+
+        let mut fields = vec![Vec2::new(0.0, 0.0); positions.len()];
+        for (s, &i) in sources.iter().enumerate() {
+            fields[i] = Vec2::new(direct.field_x[s], direct.field_y[s]);
+        }
+        if sources.len() < positions.len() {
+            let tracers: Vec<usize> = (0..positions.len())
+                .filter(|&i| self.particles[i].tracer)
+                .collect();
+            let k = self.coulomb_constant;
+            let direct = &*direct;
+            let tracer_fields = parallel::map(tracers.len(), |t| {
+                direct.field_at(k, positions[tracers[t]], min_dist_sq)
+            });
+            for (&i, field) in tracers.iter().zip(tracer_fields) {
+                fields[i] = field;
+            }
+        }
+        self.fields_to_accelerations(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ Universe, Vec2 };
+    use crate::spawn::{ SpawnOptions, SpawnShape, ValueDistribution };
+
+    // The blocked, lane-wise pair sum must agree with the plain per-particle loop, for sources
+    // and tracers alike
+    #[test]
+    fn pairwise_sum_matches_naive_sum() {
+        let mut universe = Universe::new_empty();
+        universe.set_seed(3);
+        // enough sources for several blocks and a partial last lane, some closer than the
+        // softening distance
+        let mut options = SpawnOptions::new(613, SpawnShape::Gaussian);
+        options.size = 150.0;
+        options.charge = ValueDistribution::Uniform;
+        options.charge_spread = 2.0;
+        options.mass = ValueDistribution::Uniform;
+        options.mass_spread = 0.5;
+        universe.spawn_particles(&options);
+        options.count = 20;
+        options.tracer = true;
+        universe.spawn_particles(&options);
+
+        let positions: Vec<Vec2> = universe.particles
+            .iter()
+            .map(|p| p.pos)
+            .collect();
+        let sources = universe.source_indices();
+        let pairwise = universe.direct_accelerations(&positions);
+        let naive: Vec<Vec2> = (0..positions.len())
+            .map(|i| universe.direct_acceleration(i, &positions, &sources))
+            .collect();
+
+        let scale = naive
+            .iter()
+            .map(|a| a.magnitude())
+            .sum::<f64>() / (naive.len() as f64);
+        for (i, (a, b)) in pairwise.iter().zip(&naive).enumerate() {
+            let error = (*a - *b).magnitude();
+            assert!(error <= 1e-12 * scale.max(b.magnitude()), "particle {}: {}", i, error / scale);
+        }
+    }
+
+    // Every piece of work writes only its own output, so the thread count must not change a bit
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn forces_do_not_depend_on_thread_count() {
+        use crate::ForceBackend;
+
+        // built inside the pool, as a Universe cannot be sent between threads
+        let accelerations = |backend: ForceBackend| {
+            let mut universe = Universe::new_empty();
+            universe.set_seed(5);
+            let mut options = SpawnOptions::new(2000, SpawnShape::Gaussian);
+            options.size = 300.0;
+            options.charge = ValueDistribution::Alternating;
+            universe.spawn_particles(&options);
+            options.count = 50;
+            options.tracer = true;
+            universe.spawn_particles(&options);
+            let positions: Vec<Vec2> = universe.particles
+                .iter()
+                .map(|p| p.pos)
+                .collect();
+            universe.coulomb_accelerations(backend, &positions)
+        };
+
+        let pool = |threads| rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let (serial, threaded) = (pool(1), pool(4));
+        assert_eq!(threaded.install(crate::parallel::threads), 4);
+        for backend in [
+            ForceBackend::Direct,
+            ForceBackend::Quadtree,
+            ForceBackend::Fmm,
+            ForceBackend::ParticleMesh,
+        ] {
+            let one = serial.install(|| accelerations(backend));
+            let four = threaded.install(|| accelerations(backend));
+            for (a, b) in one.iter().zip(&four) {
+                let bits = |v: &Vec2| (v.x.to_bits(), v.y.to_bits());
+                assert_eq!(bits(a), bits(b), "{:?}", backend);
+            }
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use std::fmt;
+
+use crate::SceneError;
+
+// Dimension of a quantity as exponents of the engine's base units
+// [length (px), time (s), charge (e), mass (u)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Dim([i8; 4]);
+
+impl Dim {
+    pub(crate) const NONE: Dim = Dim([0, 0, 0, 0]);
+    pub(crate) const LENGTH: Dim = Dim([1, 0, 0, 0]);
+    pub(crate) const TIME: Dim = Dim([0, 1, 0, 0]);
+    pub(crate) const CHARGE: Dim = Dim([0, 0, 1, 0]);
+    pub(crate) const MASS: Dim = Dim([0, 0, 0, 1]);
+    pub(crate) const VELOCITY: Dim = Dim([1, -1, 0, 0]);
+    pub(crate) const RATE: Dim = Dim([0, -1, 0, 0]);
+    // k_B = 1, so temperatures are energies
+    pub(crate) const ENERGY: Dim = Dim([2, -2, 0, 1]);
+    // force per charge
+    pub(crate) const ELECTRIC_FIELD: Dim = Dim([1, -2, -1, 1]);
+    // force per (charge * velocity)
+    pub(crate) const MAGNETIC_FIELD: Dim = Dim([0, -1, -1, 1]);
+
+    fn combine(self, other: Dim, sign: i8) -> Dim {
+        let mut exps = self.0;
+        for (e, o) in exps.iter_mut().zip(other.0) {
+            *e += sign * o;
+        }
+        Dim(exps)
+    }
+
+    fn scale(self, factor: i8) -> Dim {
+        Dim(self.0.map(|e| e * factor))
+    }
+}
+
+impl fmt::Display for Dim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let named = [
+            (Dim::NONE, "a plain number"),
+            (Dim::LENGTH, "a length"),
+            (Dim::TIME, "a time"),
+            (Dim::CHARGE, "a charge"),
+            (Dim::MASS, "a mass"),
+            (Dim::VELOCITY, "a velocity"),
+            (Dim::RATE, "a rate"),
+            (Dim::ENERGY, "an energy"),
+            (Dim::ELECTRIC_FIELD, "an electric field"),
+            (Dim::MAGNETIC_FIELD, "a magnetic field"),
+        ];
+        if let Some((_, name)) = named.iter().find(|(dim, _)| dim == self) {
+            return write!(f, "{}", name);
+        }
+        let parts: Vec<String> = ["px", "s", "e", "u"]
+            .iter()
+            .zip(self.0)
+            .filter(|(_, exp)| *exp != 0)
+            .map(|(unit, exp)| if exp == 1 { unit.to_string() } else { format!("{}^{}", unit, exp) })
+            .collect();
+        write!(f, "a quantity in {}", parts.join(" "))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Quantity {
+    pub(crate) value: f64,
+    pub(crate) dim: Dim,
+}
+
+impl Quantity {
+    pub(crate) fn number(value: f64) -> Quantity {
+        Quantity { value, dim: Dim::NONE }
+    }
+}
+
+// Units understood in expressions, as multiples of the engine's base units
+fn unit(name: &str) -> Option<Quantity> {
+    let (value, dim) = match name {
+        "px" => (1.0, Dim::LENGTH),
+        "s" => (1.0, Dim::TIME),
+        "ms" => (1e-3, Dim::TIME),
+        "e" => (1.0, Dim::CHARGE),
+        "u" => (1.0, Dim::MASS),
+        "rad" => (1.0, Dim::NONE),
+        "deg" => (std::f64::consts::PI / 180.0, Dim::NONE),
+        "pi" => (std::f64::consts::PI, Dim::NONE),
+        "tau" => (std::f64::consts::TAU, Dim::NONE),
+        _ => {
+            return None;
+        }
+    };
+    Some(Quantity { value, dim })
+}
+
+pub(crate) fn is_reserved(name: &str) -> bool {
+    unit(name).is_some() || FUNCTIONS.contains(&name) || name == "true" || name == "false"
+}
+
+const FUNCTIONS: &[&str] = &["sqrt", "abs", "sin", "cos", "tan", "min", "max"];
+
+// 1-based position in the source text
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Pos {
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+pub(crate) fn error_at(pos: Pos, message: impl Into<String>) -> SceneError {
+    SceneError::Syntax {
+        line: pos.line,
+        column: pos.column,
+        message: message.into(),
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum Token {
+    Number(f64),
+    Ident(String),
+    Str(String),
+    Symbol(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "number {}", n),
+            Token::Ident(name) => write!(f, "`{}`", name),
+            Token::Str(_) => write!(f, "string"),
+            Token::Symbol(c) => write!(f, "`{}`", c),
+        }
+    }
+}
+
+// Splits one value into tokens; `start` is the position of its first character
+pub(crate) fn tokenize(text: &str, start: Pos) -> Result<Vec<(Token, Pos)>, SceneError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let pos = Pos { line: start.line, column: start.column + i };
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => {
+                        return Err(error_at(pos, "unterminated string"));
+                    }
+                    Some('"') => {
+                        i += 1;
+                        break;
+                    }
+                    Some('\\') => {
+                        match chars.get(i + 1) {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(&escaped @ ('"' | '\\')) => value.push(escaped),
+                            _ => {
+                                let at = Pos { line: start.line, column: start.column + i };
+                                return Err(error_at(at, "unknown escape sequence"));
+                            }
+                        }
+                        i += 2;
+                    }
+                    Some(&other) => {
+                        value.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((Token::Str(value), pos));
+        } else if c == '0' && matches!(chars.get(i + 1), Some('x' | 'X')) {
+            let digits_start = i + 2;
+            let mut end = digits_start;
+            while end < chars.len() && (chars[end].is_ascii_hexdigit() || chars[end] == '_') {
+                end += 1;
+            }
+            let digits: String = chars[digits_start..end]
+                .iter()
+                .filter(|c| **c != '_')
+                .collect();
+            let value = u64
+                ::from_str_radix(&digits, 16)
+                .map_err(|_| error_at(pos, "malformed hexadecimal number"))?;
+            tokens.push((Token::Number(value as f64), pos));
+            i = end;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            let mut end = i;
+            while end < chars.len() && (chars[end].is_ascii_digit() || "._".contains(chars[end])) {
+                end += 1;
+            }
+            // an exponent only when digits follow, so `2e` reads as two elementary charges
+            if matches!(chars.get(end), Some('e' | 'E')) {
+                let mut exp = end + 1;
+                if matches!(chars.get(exp), Some('+' | '-')) {
+                    exp += 1;
+                }
+                if chars.get(exp).is_some_and(|d| d.is_ascii_digit()) {
+                    end = exp;
+                    while end < chars.len() && chars[end].is_ascii_digit() {
+                        end += 1;
+                    }
+                }
+            }
+            let literal: String = chars[i..end]
+                .iter()
+                .filter(|c| **c != '_')
+                .collect();
+            let value = literal.parse::<f64>().map_err(|_| error_at(pos, "malformed number"))?;
+            tokens.push((Token::Number(value), pos));
+            i = end;
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = i;
+            while end < chars.len() && (chars[end].is_alphanumeric() || chars[end] == '_') {
+                end += 1;
+            }
+            tokens.push((Token::Ident(chars[i..end].iter().collect()), pos));
+            i = end;
+        } else if "+-*/^()[],".contains(c) {
+            tokens.push((Token::Symbol(c), pos));
+            i += 1;
+        } else {
+            return Err(error_at(pos, format!("unexpected character `{}`", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive-descent evaluator over a token slice. Names resolve to `variables` first, then to
+// units and constants. A number directly followed by a name or parenthesis multiplies it,
+// so `-1 e`, `300 px/s` and `2 pi` read as expected.
+pub(crate) struct ExprParser<'a> {
+    tokens: &'a [(Token, Pos)],
+    index: usize,
+    end: Pos,
+    variables: &'a dyn Fn(&str) -> Option<Quantity>,
+}
+
+impl<'a> ExprParser<'a> {
+    pub(crate) fn new(
+        tokens: &'a [(Token, Pos)],
+        end: Pos,
+        variables: &'a dyn Fn(&str) -> Option<Quantity>
+    ) -> ExprParser<'a> {
+        ExprParser { tokens, index: 0, end, variables }
+    }
+
+    pub(crate) fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    // Position of the next token, or just past the value when there is none
+    pub(crate) fn pos(&self) -> Pos {
+        self.tokens.get(self.index).map_or(self.end, |(_, pos)| *pos)
+    }
+
+    pub(crate) fn skip(&mut self) {
+        self.index += 1;
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.index >= self.tokens.len()
+    }
+
+    pub(crate) fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn expect(&mut self, symbol: char) -> Result<(), SceneError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", symbol)))
+        }
+    }
+
+    pub(crate) fn unexpected(&self, wanted: &str) -> SceneError {
+        match self.peek() {
+            Some(token) => error_at(self.pos(), format!("expected {}, found {}", wanted, token)),
+            None => error_at(self.pos(), format!("expected {}", wanted)),
+        }
+    }
+
+    pub(crate) fn expression(&mut self) -> Result<Quantity, SceneError> {
+        let mut lhs = self.term()?;
+        loop {
+            let pos = self.pos();
+            let sign = if self.eat('+') {
+                1.0
+            } else if self.eat('-') {
+                -1.0
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.term()?;
+            if lhs.dim != rhs.dim {
+                return Err(error_at(pos, format!("cannot add {} and {}", lhs.dim, rhs.dim)));
+            }
+            lhs.value += sign * rhs.value;
+        }
+    }
+
+    fn term(&mut self) -> Result<Quantity, SceneError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let pos = self.pos();
+            let divide = if self.eat('*') {
+                false
+            } else if self.eat('/') {
+                true
+            } else if matches!(self.peek(), Some(Token::Ident(_)) | Some(Token::Symbol('('))) {
+                // implicit multiplication by a unit or parenthesised factor
+                false
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.unary()?;
+            if divide {
+                if rhs.value == 0.0 {
+                    return Err(error_at(pos, "division by zero"));
+                }
+                lhs = Quantity { value: lhs.value / rhs.value, dim: lhs.dim.combine(rhs.dim, -1) };
+            } else {
+                lhs = Quantity { value: lhs.value * rhs.value, dim: lhs.dim.combine(rhs.dim, 1) };
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Quantity, SceneError> {
+        if self.eat('-') {
+            let mut operand = self.unary()?;
+            operand.value = -operand.value;
+            return Ok(operand);
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Quantity, SceneError> {
+        let base = self.primary()?;
+        let pos = self.pos();
+        if !self.eat('^') {
+            return Ok(base);
+        }
+        let exponent = self.unary()?;
+        if exponent.dim != Dim::NONE {
+            return Err(error_at(pos, format!("exponent must be a plain number, found {}", exponent.dim)));
+        }
+        let dim = if base.dim == Dim::NONE {
+            Dim::NONE
+        } else if exponent.value.fract() == 0.0 && exponent.value.abs() <= 8.0 {
+            base.dim.scale(exponent.value as i8)
+        } else {
+            return Err(error_at(pos, format!("{} can only be raised to a whole power", base.dim)));
+        };
+        Ok(Quantity { value: base.value.powf(exponent.value), dim })
+    }
+
+    fn primary(&mut self) -> Result<Quantity, SceneError> {
+        let pos = self.pos();
+        match self.tokens.get(self.index).map(|(token, _)| token.clone()) {
+            Some(Token::Number(value)) => {
+                self.index += 1;
+                Ok(Quantity::number(value))
+            }
+            Some(Token::Symbol('(')) => {
+                self.index += 1;
+                let inner = self.expression()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) => {
+                self.index += 1;
+                if FUNCTIONS.contains(&name.as_str()) {
+                    return self.call(&name, pos);
+                }
+                if let Some(value) = (self.variables)(&name) {
+                    return Ok(value);
+                }
+                unit(&name).ok_or_else(|| error_at(pos, format!("unknown name `{}`", name)))
+            }
+            _ => Err(self.unexpected("a number, name or `(`")),
+        }
+    }
+
+    fn call(&mut self, name: &str, pos: Pos) -> Result<Quantity, SceneError> {
+        self.expect('(')?;
+        let mut args = vec![self.expression()?];
+        while self.eat(',') {
+            args.push(self.expression()?);
+        }
+        self.expect(')')?;
+
+        let arity = if name == "min" || name == "max" { 2 } else { 1 };
+        if args.len() != arity {
+            return Err(error_at(pos, format!("`{}` takes {} argument(s)", name, arity)));
+        }
+        let x = args[0];
+        let plain = |value: f64| -> Result<Quantity, SceneError> {
+            if x.dim != Dim::NONE {
+                return Err(error_at(pos, format!("`{}` needs a plain number, found {}", name, x.dim)));
+            }
+            Ok(Quantity::number(value))
+        };
+        match name {
+            "sqrt" => {
+                if x.dim.0.iter().any(|e| e % 2 != 0) {
+                    return Err(error_at(pos, format!("cannot take the square root of {}", x.dim)));
+                }
+                if x.value < 0.0 {
+                    return Err(error_at(pos, "square root of a negative number"));
+                }
+                Ok(Quantity { value: x.value.sqrt(), dim: Dim(x.dim.0.map(|e| e / 2)) })
+            }
+            "abs" => Ok(Quantity { value: x.value.abs(), dim: x.dim }),
+            "sin" => plain(x.value.sin()),
+            "cos" => plain(x.value.cos()),
+            "tan" => plain(x.value.tan()),
+            _ => {
+                let y = args[1];
+                if x.dim != y.dim {
+                    return Err(error_at(pos, format!("cannot compare {} and {}", x.dim, y.dim)));
+                }
+                let value = if name == "min" { x.value.min(y.value) } else { x.value.max(y.value) };
+                Ok(Quantity { value, dim: x.dim })
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{ Universe, Vec2 };
+
+// Uniform applied fields and linear drag. The magnetic field points out of the simulation
+// plane (B = Bz z-hat), so the Lorentz force q v x B stays in the plane and bends paths into
+// cyclotron orbits with angular frequency qBz / m.
+impl Universe {
+    pub(crate) fn has_external_forces(&self) -> bool {
+        self.has_external_field() || self.damping != 0.0
+    }
+
+    fn has_external_field(&self) -> bool {
+        self.external_field_x != 0.0 || self.external_field_y != 0.0 || self.magnetic_field_z != 0.0
+    }
+
+    pub(crate) fn add_external_accelerations(
+        &self,
+        velocities: &[Vec2],
+        accelerations: &mut [Vec2]
+    ) {
+        let has_field = self.has_external_field();
+        if !has_field && self.damping == 0.0 {
+            return;
+        }
+        for i in 0..accelerations.len() {
+            let v = velocities[i];
+            if has_field {
+                let q_over_m = self.particles[i].charge / self.effective_mass(i);
+                let force_per_charge = Vec2::new(
+                    self.external_field_x + v.y * self.magnetic_field_z,
+                    self.external_field_y - v.x * self.magnetic_field_z
+                );
+                accelerations[i] += force_per_charge * q_over_m;
+            }
+            accelerations[i] += v * -self.damping;
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Uniform electric field applied to every particle
+    pub fn set_external_field(&mut self, ex: f64, ey: f64) {
+        self.edit_settings(|u| {
+            u.external_field_x = ex;
+            u.external_field_y = ey;
+        });
+    }
+
+    pub fn get_external_field_x(&self) -> f64 {
+        self.external_field_x
+    }
+
+    pub fn get_external_field_y(&self) -> f64 {
+        self.external_field_y
+    }
+
+    // Uniform magnetic field perpendicular to the plane (positive = out of the screen)
+    pub fn set_magnetic_field_z(&mut self, bz: f64) {
+        self.edit_settings(|u| {
+            u.magnetic_field_z = bz;
+        });
+    }
+
+    pub fn get_magnetic_field_z(&self) -> f64 {
+        self.magnetic_field_z
+    }
+
+    // Linear drag coefficient: a -= damping * v (0 = no drag). Useful to let a system
+    // relax into its minimum-energy configuration.
+    pub fn set_damping(&mut self, damping: f64) {
+        self.edit_settings(|u| {
+            u.damping = damping.max(0.0);
+        });
+    }
+
+    pub fn get_damping(&self) -> f64 {
+        self.damping
+    }
+}
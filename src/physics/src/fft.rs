@@ -0,0 +1,103 @@
+use nalgebra::Complex;
+
+type C = Complex<f64>;
+
+// In-place iterative radix-2 FFT; data.len() must be a power of two. The inverse transform
+// includes the 1/n factor, so fft(inverse) undoes fft(forward).
+pub(crate) fn fft(data: &mut [C], inverse: bool) {
+    let n = data.len();
+    if n < 2 {
+        return;
+    }
+    debug_assert!(n.is_power_of_two());
+
+    // bit-reversal permutation
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = (sign * 2.0 * std::f64::consts::PI) / (len as f64);
+        let step = C::new(angle.cos(), angle.sin());
+        for start in (0..n).step_by(len) {
+            let mut w = C::new(1.0, 0.0);
+            for k in 0..len / 2 {
+                let a = data[start + k];
+                let b = data[start + k + len / 2] * w;
+                data[start + k] = a + b;
+                data[start + k + len / 2] = a - b;
+                w *= step;
+            }
+        }
+        len *= 2;
+    }
+
+    if inverse {
+        let scale = 1.0 / (n as f64);
+        for value in data.iter_mut() {
+            *value *= scale;
+        }
+    }
+}
+
+// 2D FFT of an nx * ny row-major grid (y outer); `column` is scratch of at least ny values
+pub(crate) fn fft2(data: &mut [C], nx: usize, ny: usize, inverse: bool, column: &mut Vec<C>) {
+    for row in data.chunks_mut(nx) {
+        fft(row, inverse);
+    }
+    column.resize(ny, C::new(0.0, 0.0));
+    for x in 0..nx {
+        for y in 0..ny {
+            column[y] = data[y * nx + x];
+        }
+        fft(&mut column[..ny], inverse);
+        for y in 0..ny {
+            data[y * nx + x] = column[y];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(i: usize) -> C {
+        C::new(((i * 7) % 11) as f64 - 5.0, ((i * 3) % 5) as f64 * 0.25)
+    }
+
+    #[test]
+    fn forward_matches_the_plain_dft() {
+        let n = 16;
+        let mut data: Vec<C> = (0..n).map(sample).collect();
+        let input = data.clone();
+        fft(&mut data, false);
+        for (k, value) in data.iter().enumerate() {
+            let mut expected = C::new(0.0, 0.0);
+            for (j, x) in input.iter().enumerate() {
+                let angle = (-2.0 * std::f64::consts::PI * ((j * k) as f64)) / (n as f64);
+                expected += x * C::new(angle.cos(), angle.sin());
+            }
+            assert!((value - expected).norm() < 1e-9, "bin {}", k);
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_forward_in_2d() {
+        let (nx, ny) = (8, 32);
+        let mut data: Vec<C> = (0..nx * ny).map(sample).collect();
+        let input = data.clone();
+        let mut column = Vec::new();
+        fft2(&mut data, nx, ny, false, &mut column);
+        assert!((data[0] - input.iter().sum::<C>()).norm() < 1e-9);
+        fft2(&mut data, nx, ny, true, &mut column);
+        for (value, expected) in data.iter().zip(&input) {
+            assert!((value - expected).norm() < 1e-12);
+        }
+    }
+}
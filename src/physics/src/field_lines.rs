@@ -0,0 +1,337 @@
+use wasm_bindgen::prelude::*;
+use serde::{ Serialize, Deserialize };
+
+use crate::{ Universe, Vec2 };
+use crate::fields::{ FieldKind, Polylines };
+
+// Hard cap on integration steps per line, independent of max_length
+const MAX_STEPS_PER_LINE: usize = 20_000;
+
+// Settings for streamline tracing. Radii <= 0 fall back to the universe's minimum
+// interaction distance so lines start and stop just outside the softened core.
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct FieldLineOptions {
+    // Domain edge: lines stop when they leave this rectangle
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+    // Maximum arc length of a single line
+    pub max_length: f64,
+    // Adaptive step control (arc-length units)
+    pub initial_step: f64,
+    pub min_step: f64,
+    pub max_step: f64,
+    // Allowed local position error per step
+    pub tolerance: f64,
+    // Automatic seeding: lines per unit of |charge| (or pole strength), capped per source
+    pub lines_per_unit_charge: f64,
+    pub min_lines_per_source: u32,
+    pub max_lines_per_source: u32,
+    // Distance from a source at which automatic seeds are placed
+    pub seed_radius: f64,
+    // Lines end when they come this close to any source (sink)
+    pub sink_radius: f64,
+}
+
+#[wasm_bindgen]
+impl FieldLineOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> FieldLineOptions {
+        FieldLineOptions {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+            max_length: 4000.0,
+            initial_step: 5.0,
+            min_step: 0.25,
+            max_step: 25.0,
+            tolerance: 0.05,
+            lines_per_unit_charge: 0.5,
+            min_lines_per_source: 4,
+            max_lines_per_source: 32,
+            seed_radius: 0.0,
+            sink_radius: 0.0,
+        }
+    }
+}
+
+impl Universe {
+    fn field_line_radii(&self, options: &FieldLineOptions) -> (f64, f64) {
+        let fallback = if self.min_interaction_distance > 0.0 {
+            self.min_interaction_distance
+        } else {
+            10.0
+        };
+        let seed_radius = if options.seed_radius > 0.0 { options.seed_radius } else { fallback };
+        let sink_radius = if options.sink_radius > 0.0 {
+            options.sink_radius
+        } else {
+            seed_radius * 0.5
+        };
+        (seed_radius, sink_radius)
+    }
+
+    // Unit tangent of the field (scaled by direction = +1 downstream, -1 upstream)
+    fn field_direction(&self, sources: &[(Vec2, f64)], at: Vec2, direction: f64) -> Option<Vec2> {
+        let field = self.field_from_sources(sources, at);
+        let magnitude = field.magnitude();
+        if magnitude > 1e-12 && magnitude.is_finite() {
+            Some(field * (direction / magnitude))
+        } else {
+            None
+        }
+    }
+
+    // Integrates one streamline with an adaptive Bogacki-Shampine 3(2) pair.
+    // Returns the polyline and the index of the source it ended on, if any.
+    fn trace_streamline(
+        &self,
+        sources: &[(Vec2, f64)],
+        start: Vec2,
+        direction: f64,
+        options: &FieldLineOptions,
+        sink_radius: f64
+    ) -> (Vec<Vec2>, Option<usize>) {
+        let mut line = vec![start];
+        let mut pos = start;
+        let mut length = 0.0;
+        let mut h = options.initial_step.max(options.min_step).min(options.max_step);
+        let sink_radius_sq = sink_radius * sink_radius;
+
+        for _ in 0..MAX_STEPS_PER_LINE {
+            let k1 = match self.field_direction(sources, pos, direction) {
+                Some(k) => k,
+                None => {
+                    return (line, None);
+                }
+            };
+            let k2 = match self.field_direction(sources, pos + k1 * (h * 0.5), direction) {
+                Some(k) => k,
+                None => {
+                    return (line, None);
+                }
+            };
+            let k3 = match self.field_direction(sources, pos + k2 * (h * 0.75), direction) {
+                Some(k) => k,
+                None => {
+                    return (line, None);
+                }
+            };
+            let next = pos + (k1 * (2.0 / 9.0) + k2 * (1.0 / 3.0) + k3 * (4.0 / 9.0)) * h;
+            let k4 = self.field_direction(sources, next, direction).unwrap_or(k3);
+            let lower =
+                pos + (k1 * (7.0 / 24.0) + k2 * 0.25 + k3 * (1.0 / 3.0) + k4 * 0.125) * h;
+            let error = next.distance_from(lower);
+
+            if error > options.tolerance && h > options.min_step {
+                let factor = (0.9 * (options.tolerance / error).powf(1.0 / 3.0)).max(0.2);
+                h = (h * factor).max(options.min_step);
+                continue;
+            }
+
+            length += next.distance_from(pos);
+            pos = next;
+            line.push(pos);
+
+            for (index, (source_pos, _)) in sources.iter().enumerate() {
+                let dx = pos.x - source_pos.x;
+                let dy = pos.y - source_pos.y;
+                if dx * dx + dy * dy < sink_radius_sq {
+                    line.push(*source_pos);
+                    return (line, Some(index));
+                }
+            }
+            if
+                pos.x < options.min_x ||
+                pos.x > options.max_x ||
+                pos.y < options.min_y ||
+                pos.y > options.max_y ||
+                length >= options.max_length
+            {
+                return (line, None);
+            }
+
+            let factor = if error > 0.0 {
+                (0.9 * (options.tolerance / error).powf(1.0 / 3.0)).clamp(0.2, 5.0)
+            } else {
+                5.0
+            };
+            h = (h * factor).max(options.min_step).min(options.max_step);
+        }
+
+        (line, None)
+    }
+
+    // Seeds lines around every source in proportion to |strength|. Positive sources are
+    // traced downstream and negative ones upstream; an upstream line that ends on a positive
+    // source duplicates one already traced from that source, so it is dropped.
+    fn trace_auto_seeded(&self, kind: FieldKind, options: &FieldLineOptions) -> Polylines {
+        let sources = self.field_sources(kind);
+        let (seed_radius, sink_radius) = self.field_line_radii(options);
+        let mut lines = Polylines::default();
+
+        for &(source_pos, strength) in &sources {
+            if strength == 0.0 {
+                continue;
+            }
+            let count = (strength.abs() * options.lines_per_unit_charge)
+                .round()
+                .max(options.min_lines_per_source as f64)
+                .min(options.max_lines_per_source as f64) as usize;
+            let direction = if strength > 0.0 { 1.0 } else { -1.0 };
+            for i in 0..count {
+                let angle = (2.0 * std::f64::consts::PI * (i as f64)) / (count as f64);
+                let seed =
+                    source_pos + Vec2::new(f64::cos(angle), f64::sin(angle)) * seed_radius;
+                let (line, end) = self.trace_streamline(
+                    &sources,
+                    seed,
+                    direction,
+                    options,
+                    sink_radius
+                );
+                if direction < 0.0 && end.is_some_and(|e| sources[e].1 > 0.0) {
+                    continue;
+                }
+                if line.len() > 1 {
+                    lines.push_line(&line, strength);
+                }
+            }
+        }
+
+        lines
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Field lines through explicit seed points (interleaved x, y). Each line is traced both
+    // upstream and downstream from its seed and returned as a single polyline.
+    pub fn trace_field_lines(
+        &self,
+        kind: FieldKind,
+        seeds: Vec<f64>,
+        options: &FieldLineOptions
+    ) -> Polylines {
+        let sources = self.field_sources(kind);
+        let (_, sink_radius) = self.field_line_radii(options);
+        let mut lines = Polylines::default();
+
+        for seed in seeds.chunks_exact(2) {
+            let start = Vec2::new(seed[0], seed[1]);
+            let (mut upstream, _) = self.trace_streamline(
+                &sources,
+                start,
+                -1.0,
+                options,
+                sink_radius
+            );
+            let (downstream, _) = self.trace_streamline(&sources, start, 1.0, options, sink_radius);
+            upstream.reverse();
+            upstream.extend_from_slice(&downstream[1..]);
+            if upstream.len() > 1 {
+                lines.push_line(&upstream, 0.0);
+            }
+        }
+
+        lines
+    }
+
+    // Electric field lines seeded automatically around each charged (non-tracer) particle
+    pub fn trace_electric_field_lines(&self, options: &FieldLineOptions) -> Polylines {
+        self.trace_auto_seeded(FieldKind::Electric, options)
+    }
+
+    // Magnetic field lines seeded automatically around each magnet pole. Magnets are the only
+    // magnetic sources in the engine: a charge moving in the plane has no in-plane B field, and
+    // out-of-plane currents (wires), whose in-plane B circles them, are not modelled.
+    pub fn trace_magnetic_field_lines(&self, options: &FieldLineOptions) -> Polylines {
+        self.trace_auto_seeded(FieldKind::Magnetic, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arc_length(line: &[Vec2]) -> f64 {
+        line.windows(2)
+            .map(|w| w[1].distance_from(w[0]))
+            .sum()
+    }
+
+    #[test]
+    fn lines_end_on_the_opposite_charge() {
+        let mut universe = Universe::new_empty();
+        universe.add_particle_simple(-100.0, 0.0, 0.0, 0.0, 1.0);
+        universe.add_particle_simple(100.0, 0.0, 0.0, 0.0, -1.0);
+        let options = FieldLineOptions::new(-1000.0, -1000.0, 1000.0, 1000.0);
+        let sources = universe.field_sources(FieldKind::Electric);
+        let (_, sink_radius) = universe.field_line_radii(&options);
+
+        let start = Vec2::new(-90.0, 1.0);
+        let (line, end) = universe.trace_streamline(&sources, start, 1.0, &options, sink_radius);
+        assert_eq!(end, Some(1));
+        let last = line[line.len() - 1];
+        assert_eq!((last.x, last.y), (100.0, 0.0));
+    }
+
+    #[test]
+    fn lines_stop_at_the_domain_edge() {
+        let mut universe = Universe::new_empty();
+        universe.add_particle_simple(0.0, 0.0, 0.0, 0.0, 1.0);
+        let options = FieldLineOptions::new(-100.0, -100.0, 100.0, 100.0);
+        let sources = universe.field_sources(FieldKind::Electric);
+
+        let (line, end) = universe.trace_streamline(&sources, Vec2::new(10.0, 3.0), 1.0, &options, 5.0);
+        assert_eq!(end, None);
+        let (last, before) = (line[line.len() - 1], line[line.len() - 2]);
+        assert!(last.x > 100.0, "stopped at ({}, {})", last.x, last.y);
+        assert!(before.x <= 100.0);
+    }
+
+    #[test]
+    fn lines_are_capped_at_max_length() {
+        let mut universe = Universe::new_empty();
+        universe.add_particle_simple(0.0, 0.0, 0.0, 0.0, 1.0);
+        let mut options = FieldLineOptions::new(-1e6, -1e6, 1e6, 1e6);
+        options.max_length = 300.0;
+        let sources = universe.field_sources(FieldKind::Electric);
+
+        let (line, end) = universe.trace_streamline(&sources, Vec2::new(10.0, 0.0), 1.0, &options, 5.0);
+        assert_eq!(end, None);
+        let length = arc_length(&line);
+        assert!(length >= 300.0 && length < 300.0 + options.max_step, "length {}", length);
+    }
+
+    #[test]
+    fn seed_count_follows_charge() {
+        let mut universe = Universe::new_empty();
+        universe.add_particle_simple(-5000.0, 0.0, 0.0, 0.0, 8.0);
+        universe.add_particle_simple(5000.0, 0.0, 0.0, 0.0, 16.0);
+        universe.add_particle_simple(0.0, 5000.0, 0.0, 0.0, 0.5);
+        universe.add_particle_simple(0.0, -5000.0, 0.0, 0.0, 200.0);
+        let mut options = FieldLineOptions::new(-1e4, -1e4, 1e4, 1e4);
+        options.max_length = 200.0;
+        options.lines_per_unit_charge = 1.0;
+        options.min_lines_per_source = 4;
+        options.max_lines_per_source = 32;
+
+        let lines = universe.trace_electric_field_lines(&options);
+        let count = |q: f64| {
+            lines
+                .get_values()
+                .iter()
+                .filter(|&&v| v == q)
+                .count()
+        };
+        assert_eq!(count(8.0), 8);
+        assert_eq!(count(16.0), 16);
+        // clamped to the per-source minimum and maximum
+        assert_eq!(count(0.5), 4);
+        assert_eq!(count(200.0), 32);
+    }
+}
@@ -0,0 +1,310 @@
+use wasm_bindgen::prelude::*;
+use serde::{ Serialize, Deserialize };
+
+use crate::{ Universe, Vec2 };
+
+// Which sources contribute to a sampled field or potential
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum FieldKind {
+    // Charged particles (tracers excluded, exactly like the force code)
+    Electric,
+    // Magnet pseudo-poles
+    Magnetic,
+    // Everything a test charge feels: Electric + Magnetic
+    Combined,
+}
+
+// Softened Coulomb field of a source with strength q at displacement r (= target - source).
+// Inside the minimum interaction distance d the offset is still divided by d^3, so the field
+// is k*q*r/d^3 and falls off linearly to zero; this is the exact softening used by every
+// force calculation in the engine.
+pub(crate) fn coulomb_field(k: f64, q: f64, r: Vec2, min_dist: f64) -> Vec2 {
+    let dist_sq = r.x * r.x + r.y * r.y;
+    let min_dist_sq = min_dist * min_dist;
+    let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
+    let dist = f64::sqrt(safe_dist_sq);
+    if dist > 1e-8 {
+        let magnitude = (k * q) / safe_dist_sq;
+        Vec2::new(magnitude * (r.x / dist), magnitude * (r.y / dist))
+    } else {
+        Vec2::new(0.0, 0.0)
+    }
+}
+
+// Potential whose negative gradient is `coulomb_field`: k*q/r outside the softening radius and
+// the uniform-disc profile k*q*(3d^2 - r^2)/(2d^3) inside it, whose slope is the linear
+// inner field and which meets k*q/d at r = d.
+pub(crate) fn coulomb_potential(k: f64, q: f64, r: Vec2, min_dist: f64) -> f64 {
+    let dist = r.magnitude();
+    if dist >= min_dist {
+        if dist > 1e-8 { (k * q) / dist } else { 0.0 }
+    } else {
+        (k * q * (3.0 * min_dist * min_dist - dist * dist)) / (2.0 * min_dist * min_dist * min_dist)
+    }
+}
+
+impl Universe {
+    // Point sources (position, strength) contributing to the requested field
+    pub(crate) fn field_sources(&self, kind: FieldKind) -> Vec<(Vec2, f64)> {
+        let mut sources = Vec::new();
+        if kind != FieldKind::Magnetic {
+            for p in self.particles.iter().filter(|p| !p.tracer) {
+                sources.push((p.pos, p.charge));
+            }
+        }
+        if kind != FieldKind::Electric {
+            for m in &self.magnets {
+                sources.extend(m.poles());
+            }
+        }
+        sources
+    }
+
+    pub(crate) fn field_from_sources(&self, sources: &[(Vec2, f64)], at: Vec2) -> Vec2 {
+        let mut field = Vec2::new(0.0, 0.0);
+        for &(pos, strength) in sources {
+            field += coulomb_field(self.coulomb_constant, strength, at - pos, self.min_interaction_distance);
+        }
+        field
+    }
+
+    pub(crate) fn potential_from_sources(&self, sources: &[(Vec2, f64)], at: Vec2) -> f64 {
+        let mut potential = 0.0;
+        for &(pos, strength) in sources {
+            potential += coulomb_potential(
+                self.coulomb_constant,
+                strength,
+                at - pos,
+                self.min_interaction_distance
+            );
+        }
+        potential
+    }
+
+    // Node positions of an nx * ny grid spanning [min, max] inclusive, row-major (y outer)
+    fn grid_nodes(min_x: f64, min_y: f64, max_x: f64, max_y: f64, nx: usize, ny: usize) -> Vec<Vec2> {
+        let step_x = if nx > 1 { (max_x - min_x) / ((nx - 1) as f64) } else { 0.0 };
+        let step_y = if ny > 1 { (max_y - min_y) / ((ny - 1) as f64) } else { 0.0 };
+        let mut nodes = Vec::with_capacity(nx * ny);
+        for gy in 0..ny {
+            for gx in 0..nx {
+                nodes.push(Vec2::new(min_x + (gx as f64) * step_x, min_y + (gy as f64) * step_y));
+            }
+        }
+        nodes
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Field (force per unit charge) at a point
+    pub fn field_at(&self, kind: FieldKind, x: f64, y: f64) -> Vec2 {
+        let sources = self.field_sources(kind);
+        self.field_from_sources(&sources, Vec2::new(x, y))
+    }
+
+    // Scalar potential (potential energy per unit charge) at a point
+    pub fn potential_at(&self, kind: FieldKind, x: f64, y: f64) -> f64 {
+        let sources = self.field_sources(kind);
+        self.potential_from_sources(&sources, Vec2::new(x, y))
+    }
+
+    // Acceleration of every particle from the other particles' Coulomb forces alone (no
+    // magnets or applied fields), summed by the active backend exactly as in a step.
+    // Returns a Float64Array of interleaved (x, y) components, one pair per particle.
+    pub fn get_coulomb_accelerations(&self) -> Vec<f64> {
+        let positions: Vec<Vec2> = self.particles
+            .iter()
+            .map(|p| p.pos)
+            .collect();
+        let mut out = Vec::with_capacity(positions.len() * 2);
+        for acc in self.coulomb_accelerations(self.force_backend, &positions) {
+            out.push(acc.x);
+            out.push(acc.y);
+        }
+        out
+    }
+
+    // Field on an nx * ny grid of nodes spanning [min, max] inclusive; read off the mesh when
+    // the particle-mesh backend is active.
+    // Returns a Float64Array of interleaved (x, y) components, row-major with y as the outer index.
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_field_grid(
+        &self,
+        kind: FieldKind,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        nx: usize,
+        ny: usize
+    ) -> Vec<f64> {
+        let nodes = Self::grid_nodes(min_x, min_y, max_x, max_y, nx, ny);
+        let fields = self.mesh_field_samples(kind, &nodes).unwrap_or_else(|| {
+            let sources = self.field_sources(kind);
+            nodes
+                .iter()
+                .map(|&node| self.field_from_sources(&sources, node))
+                .collect()
+        });
+        let mut out = Vec::with_capacity(nx * ny * 2);
+        for field in fields {
+            out.push(field.x);
+            out.push(field.y);
+        }
+        out
+    }
+
+    // Potential on an nx * ny grid of nodes spanning [min, max] inclusive (mesh-based like
+    // sample_field_grid).
+    // Returns a Float64Array with one value per node, row-major with y as the outer index.
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_potential_grid(
+        &self,
+        kind: FieldKind,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        nx: usize,
+        ny: usize
+    ) -> Vec<f64> {
+        let nodes = Self::grid_nodes(min_x, min_y, max_x, max_y, nx, ny);
+        self.mesh_potential_samples(kind, &nodes).unwrap_or_else(|| {
+            let sources = self.field_sources(kind);
+            nodes
+                .iter()
+                .map(|&node| self.potential_from_sources(&sources, node))
+                .collect()
+        })
+    }
+}
+
+// A batch of polylines packed into flat typed arrays for cheap transfer to JS
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Polylines {
+    // Interleaved (x, y) vertex coordinates of every line, back to back
+    points: Vec<f64>,
+    // Vertex offset where each line starts, followed by the total vertex count
+    offsets: Vec<u32>,
+    // One scalar per line (contour level, or strength of the seeding source)
+    values: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl Polylines {
+    pub fn get_points(&self) -> Vec<f64> {
+        self.points.clone()
+    }
+
+    pub fn get_offsets(&self) -> Vec<u32> {
+        let mut offsets = self.offsets.clone();
+        offsets.push((self.points.len() / 2) as u32);
+        offsets
+    }
+
+    pub fn get_values(&self) -> Vec<f64> {
+        self.values.clone()
+    }
+
+    pub fn get_line_count(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn get_data(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self).unwrap()
+    }
+}
+
+impl Polylines {
+    pub(crate) fn push_line(&mut self, line: &[Vec2], value: f64) {
+        self.offsets.push((self.points.len() / 2) as u32);
+        self.values.push(value);
+        for p in line {
+            self.points.push(p.x);
+            self.points.push(p.y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ForceBackend;
+    use crate::spawn::{ SpawnOptions, SpawnShape, ValueDistribution };
+
+    // Central difference of the potential against the field, inside and outside the softening
+    // radius, so the overlays and the forces cannot disagree
+    #[test]
+    fn potential_gradient_matches_field() {
+        let (k, q, d, h) = (2.0, 3.0, 5.0, 1e-5);
+        let offsets = [
+            Vec2::new(1.0, 0.5),
+            Vec2::new(3.0, -2.0),
+            Vec2::new(4.9, 0.1),
+            Vec2::new(7.0, 3.0),
+            Vec2::new(-20.0, 11.0),
+        ];
+        for r in offsets {
+            let field = coulomb_field(k, q, r, d);
+            let slope = |step: Vec2| {
+                (coulomb_potential(k, q, r + step, d) - coulomb_potential(k, q, r - step, d)) /
+                    (2.0 * h)
+            };
+            let gradient = Vec2::new(slope(Vec2::new(h, 0.0)), slope(Vec2::new(0.0, h)));
+            let error = (field + gradient).magnitude();
+            assert!(error < 1e-8 * (1.0 + field.magnitude()), "at ({}, {})", r.x, r.y);
+        }
+    }
+
+    #[test]
+    fn potential_is_continuous_at_softening_radius() {
+        let (k, q, d) = (2.0, 3.0, 5.0);
+        let inside = coulomb_potential(k, q, Vec2::new(d - 1e-9, 0.0), d);
+        let outside = coulomb_potential(k, q, Vec2::new(d + 1e-9, 0.0), d);
+        assert!((inside - outside).abs() < 1e-9);
+    }
+
+    fn largest(values: &[f64]) -> f64 {
+        values.iter().fold(0.0, |max, v| f64::max(max, v.abs()))
+    }
+
+    fn largest_difference(a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b).fold(0.0, |max, (x, y)| f64::max(max, (x - y).abs()))
+    }
+
+    // With the particle-mesh backend the overlays are read off the mesh; with the short-range
+    // correction they must agree with the direct sum, magnet poles included
+    #[test]
+    fn mesh_overlays_match_the_direct_sum() {
+        let mut universe = Universe::new_empty();
+        let mut options = SpawnOptions::new(36, SpawnShape::SquareLattice);
+        options.spacing = 150.0;
+        options.charge = ValueDistribution::Alternating;
+        universe.spawn_particles(&options);
+        universe.add_magnet_simple(0.0, 500.0, 200.0);
+        universe.set_mesh_short_range(true);
+        let grid = (-400.0, -400.0, 400.0, 400.0, 17, 13);
+
+        for kind in [FieldKind::Electric, FieldKind::Combined] {
+            let sample = |universe: &Universe| {
+                let (min_x, min_y, max_x, max_y, nx, ny) = grid;
+                (
+                    universe.sample_field_grid(kind, min_x, min_y, max_x, max_y, nx, ny),
+                    universe.sample_potential_grid(kind, min_x, min_y, max_x, max_y, nx, ny),
+                )
+            };
+            universe.set_force_backend(ForceBackend::Direct);
+            let (direct_field, direct_potential) = sample(&universe);
+            universe.set_force_backend(ForceBackend::ParticleMesh);
+            let (mesh_field, mesh_potential) = sample(&universe);
+
+            let field_error = largest_difference(&mesh_field, &direct_field);
+            assert!(field_error < 1e-2 * largest(&direct_field), "{}", field_error);
+            let potential_error = largest_difference(&mesh_potential, &direct_potential);
+            assert!(potential_error < 2e-3 * largest(&direct_potential), "{}", potential_error);
+        }
+    }
+}
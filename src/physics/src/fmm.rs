@@ -0,0 +1,466 @@
+use nalgebra::Complex;
+
+use crate::{ parallel, Universe, Vec2 };
+use crate::fields::coulomb_field;
+
+type C = Complex<f64>;
+
+// Expansion orders accepted by the FMM backend
+pub(crate) const MIN_FMM_ORDER: usize = 2;
+pub(crate) const MAX_FMM_ORDER: usize = 12;
+pub(crate) fn default_fmm_order() -> usize {
+    6
+}
+
+// Average number of sources per leaf the level count aims for
+const LEAF_OCCUPANCY: usize = 16;
+const MAX_LEVELS: usize = 9;
+// Padding added around the particles when sizing the root square
+const ROOT_PADDING: f64 = 100.0;
+
+// Fast multipole method on a uniform quadtree of square cells, with expansions written in the
+// complex coordinate z = x + iy of the plane.
+//
+// The simulation uses the 3D 1/r Coulomb kernel restricted to the plane, not the 2D log kernel,
+// so the potential is not the real part of an analytic function and the classic Greengard-
+// Rokhlin series in z alone do not apply. Instead 1/|z - w| = (z - w)^-1/2 (z* - w*)^-1/2 is
+// expanded in both z and its conjugate:
+//
+//     multipole  M[a][b] = sum q v^a v*^b              (v = source - cell centre)
+//     local      phi(u)  = sum L[m][n] u^m u*^n        (u = target - cell centre)
+//
+// and every translation (M2M, M2L, L2L) factors as S X S^H for a matrix S that only depends on
+// the offset between cells, so it costs O(p^3) for order p. The truncation error at order p
+// shrinks roughly like (sqrt(2) / 2)^p in the worst case (well separated cells are one cell
+// apart) and much faster on average. Softening is only applied in the near field, which is why
+// leaves are never made narrower than the softening distance.
+#[derive(Clone, Default)]
+pub(crate) struct Fmm {
+    order: usize,
+    levels: usize,
+    // lower-left corner and side of the root square
+    origin: Vec2,
+    width: f64,
+    // sources ordered by leaf, leaf c holding sources[leaf_start[c]..leaf_start[c + 1]]
+    sources: Vec<(usize, Vec2, f64)>,
+    leaf_start: Vec<usize>,
+    // sources in particle order and the fill position of every leaf, while sorting
+    unsorted: Vec<(usize, Vec2, f64)>,
+    next: Vec<usize>,
+    // leaf of every target, and per-level source/target counts used to skip empty cells
+    target_leaf: Vec<usize>,
+    source_count: Vec<Vec<u32>>,
+    target_count: Vec<Vec<u32>>,
+    // (order + 1)^2 coefficients per cell, per level
+    multipoles: Vec<Vec<C>>,
+    locals: Vec<Vec<C>>,
+    // binom[n][k] up to n = 2 * order, and the coefficients of (1 + t)^-1/2
+    binom: Vec<Vec<f64>>,
+    half: Vec<f64>,
+    // M2L operators for the 7x7 block of offsets around a cell, with their 1/|R| scales
+    m2l: Vec<C>,
+    m2l_scale: Vec<f64>,
+    scratch: Vec<C>,
+}
+
+fn cell_center(origin: Vec2, cell_width: f64, ix: usize, iy: usize) -> Vec2 {
+    origin + Vec2::new((ix as f64 + 0.5) * cell_width, (iy as f64 + 0.5) * cell_width)
+}
+
+// Leaf containing `pos` in a grid of 2^levels x 2^levels cells (outside points are clamped)
+fn leaf_of(origin: Vec2, width: f64, levels: usize, pos: Vec2) -> usize {
+    let side = 1usize << levels;
+    let cell_width = width / side as f64;
+    let ix = (((pos.x - origin.x) / cell_width).floor().max(0.0) as usize).min(side - 1);
+    let iy = (((pos.y - origin.y) / cell_width).floor().max(0.0) as usize).min(side - 1);
+    iy * side + ix
+}
+
+// out += scale * S X S^H for n x n row-major matrices; tmp holds n * n values. Expansions of
+// real charges are Hermitian (X[b][a] = X[a][b]*), and so is the result, so only its upper
+// triangle is computed.
+fn add_sandwich(s: &[C], x: &[C], out: &mut [C], scale: f64, n: usize, tmp: &mut [C]) {
+    for m in 0..n {
+        for b in 0..n {
+            let mut sum = C::new(0.0, 0.0);
+            for a in 0..n {
+                sum += s[m * n + a] * x[a * n + b];
+            }
+            tmp[m * n + b] = sum;
+        }
+    }
+    for m in 0..n {
+        for m2 in m..n {
+            let mut sum = C::new(0.0, 0.0);
+            for b in 0..n {
+                sum += tmp[m * n + b] * s[m2 * n + b].conj();
+            }
+            out[m * n + m2] += sum * scale;
+            if m2 != m {
+                out[m2 * n + m] += sum.conj() * scale;
+            }
+        }
+    }
+}
+
+// d^0 .. d^(n-1), for n up to 2 * (MAX_FMM_ORDER + 1)
+fn powers(d: C, n: usize) -> [C; 2 * MAX_FMM_ORDER + 2] {
+    let mut result = [C::new(0.0, 0.0); 2 * MAX_FMM_ORDER + 2];
+    let mut power = C::new(1.0, 0.0);
+    for value in result.iter_mut().take(n) {
+        *value = power;
+        power *= d;
+    }
+    result
+}
+
+impl Fmm {
+    fn prepare_tables(&mut self, order: usize) {
+        if self.order == order && !self.binom.is_empty() {
+            return;
+        }
+        self.order = order;
+        let top = 2 * order + 1;
+        self.binom = (0..=top)
+            .map(|n| {
+                let mut row = vec![1.0; n + 1];
+                for k in 1..n {
+                    row[k] = row[k - 1] * ((n - k + 1) as f64) / (k as f64);
+                }
+                row
+            })
+            .collect();
+        self.half = vec![1.0; top + 1];
+        for k in 1..=top {
+            self.half[k] = (self.half[k - 1] * (-0.5 - (k - 1) as f64)) / (k as f64);
+        }
+    }
+
+    // M2M matrix P[j][a] = C(j, a) d^(j - a) moving a multipole by d (old centre - new centre);
+    // its transpose is the L2L matrix for d = new centre - old centre
+    fn shift_matrix(&self, d: C, transpose: bool) -> Vec<C> {
+        let n = self.order + 1;
+        let power = powers(d, n);
+        let mut matrix = vec![C::new(0.0, 0.0); n * n];
+        for j in 0..n {
+            for a in 0..=j {
+                let value = power[j - a] * self.binom[j][a];
+                if transpose {
+                    matrix[a * n + j] = value;
+                } else {
+                    matrix[j * n + a] = value;
+                }
+            }
+        }
+        matrix
+    }
+
+    // M2L matrices A[m][a] = c(m + a) C(m + a, m) (-1)^a R^-(m + a) for every offset R between
+    // well separated cells of the given width
+    fn prepare_m2l(&mut self, cell_width: f64) {
+        let n = self.order + 1;
+        self.m2l.clear();
+        self.m2l.resize(49 * n * n, C::new(0.0, 0.0));
+        self.m2l_scale.clear();
+        self.m2l_scale.resize(49, 0.0);
+        for dx in -3i32..=3 {
+            for dy in -3i32..=3 {
+                if dx.abs() <= 1 && dy.abs() <= 1 {
+                    continue;
+                }
+                let slot = ((dx + 3) * 7 + dy + 3) as usize;
+                let r = C::new(dx as f64 * cell_width, dy as f64 * cell_width);
+                let inverse = powers(r.inv(), 2 * n);
+                let matrix = &mut self.m2l[slot * n * n..(slot + 1) * n * n];
+                for m in 0..n {
+                    for a in 0..n {
+                        let sign = if a % 2 == 0 { 1.0 } else { -1.0 };
+                        let coefficient = self.half[m + a] * self.binom[m + a][m] * sign;
+                        matrix[m * n + a] = inverse[m + a] * coefficient;
+                    }
+                }
+                self.m2l_scale[slot] = 1.0 / r.norm();
+            }
+        }
+    }
+
+    fn leaf_of(&self, pos: Vec2) -> usize {
+        leaf_of(self.origin, self.width, self.levels, pos)
+    }
+
+    // Sorts the sources into leaves and sizes the tree for them
+    fn build(
+        &mut self,
+        order: usize,
+        min_dist: f64,
+        positions: &[Vec2],
+        sources: impl Iterator<Item = (usize, Vec2, f64)>
+    ) {
+        self.prepare_tables(order);
+        self.unsorted.clear();
+        self.unsorted.extend(sources);
+
+        // square root cell around every target and source
+        let mut min = Vec2::new(f64::MAX, f64::MAX);
+        let mut max = Vec2::new(f64::MIN, f64::MIN);
+        for p in positions.iter().chain(self.unsorted.iter().map(|s| &s.1)) {
+            min.x = min.x.min(p.x);
+            min.y = min.y.min(p.y);
+            max.x = max.x.max(p.x);
+            max.y = max.y.max(p.y);
+        }
+        if min.x > max.x {
+            min = Vec2::new(0.0, 0.0);
+            max = Vec2::new(0.0, 0.0);
+        }
+        self.width = f64::max(max.x - min.x, max.y - min.y) + 2.0 * ROOT_PADDING;
+        let center = (min + max) * 0.5;
+        self.origin = center - Vec2::new(self.width * 0.5, self.width * 0.5);
+
+        self.levels = 0;
+        while self.levels < MAX_LEVELS && (LEAF_OCCUPANCY << (2 * self.levels)) < self.unsorted.len() {
+            self.levels += 1;
+        }
+        // well separated leaves must stay outside each other's softening radius
+        while self.levels > 0 && self.width / ((1usize << self.levels) as f64) < min_dist {
+            self.levels -= 1;
+        }
+
+        // counting sort of the sources by leaf
+        let leaves = 1usize << (2 * self.levels);
+        self.leaf_start.clear();
+        self.leaf_start.resize(leaves + 1, 0);
+        for source in &self.unsorted {
+            let leaf = leaf_of(self.origin, self.width, self.levels, source.1);
+            self.leaf_start[leaf + 1] += 1;
+        }
+        for leaf in 0..leaves {
+            self.leaf_start[leaf + 1] += self.leaf_start[leaf];
+        }
+        self.next.clear();
+        self.next.extend_from_slice(&self.leaf_start);
+        self.sources.clear();
+        self.sources.resize(self.unsorted.len(), (0, Vec2::new(0.0, 0.0), 0.0));
+        for source in &self.unsorted {
+            let leaf = leaf_of(self.origin, self.width, self.levels, source.1);
+            self.sources[self.next[leaf]] = *source;
+            self.next[leaf] += 1;
+        }
+
+        self.target_leaf.clear();
+        for &pos in positions {
+            let leaf = self.leaf_of(pos);
+            self.target_leaf.push(leaf);
+        }
+
+        // occupancy of every cell on every level
+        self.source_count.resize(self.levels + 1, Vec::new());
+        self.target_count.resize(self.levels + 1, Vec::new());
+        for level in 0..=self.levels {
+            let cells = 1usize << (2 * level);
+            self.source_count[level].clear();
+            self.source_count[level].resize(cells, 0);
+            self.target_count[level].clear();
+            self.target_count[level].resize(cells, 0);
+        }
+        for leaf in 0..leaves {
+            let count = self.leaf_start[leaf + 1] - self.leaf_start[leaf];
+            self.source_count[self.levels][leaf] = count as u32;
+        }
+        for &leaf in &self.target_leaf {
+            self.target_count[self.levels][leaf] += 1;
+        }
+        for level in (0..self.levels).rev() {
+            let side = 1usize << level;
+            for cell in 0..1usize << (2 * (level + 1)) {
+                let (ix, iy) = (cell % (2 * side), cell / (2 * side));
+                let parent = (iy / 2) * side + ix / 2;
+                self.source_count[level][parent] += self.source_count[level + 1][cell];
+                self.target_count[level][parent] += self.target_count[level + 1][cell];
+            }
+        }
+    }
+
+    // Multipoles of the leaves (P2M), then of every coarser cell (M2M)
+    fn upward_pass(&mut self) {
+        let n = self.order + 1;
+        self.multipoles.resize(self.levels + 1, Vec::new());
+        for level in 0..=self.levels {
+            self.multipoles[level].clear();
+            self.multipoles[level].resize((1usize << (2 * level)) * n * n, C::new(0.0, 0.0));
+        }
+
+        let side = 1usize << self.levels;
+        let cell_width = self.width / side as f64;
+        for leaf in 0..side * side {
+            let center = cell_center(self.origin, cell_width, leaf % side, leaf / side);
+            let coefficients = &mut self.multipoles[self.levels][leaf * n * n..(leaf + 1) * n * n];
+            for &(_, pos, charge) in &self.sources[self.leaf_start[leaf]..self.leaf_start[leaf + 1]] {
+                let v = C::new(pos.x - center.x, pos.y - center.y);
+                let power = powers(v, n);
+                for a in 0..n {
+                    for b in 0..n {
+                        coefficients[a * n + b] += power[a] * power[b].conj() * charge;
+                    }
+                }
+            }
+        }
+
+        self.scratch.resize(n * n, C::new(0.0, 0.0));
+        for level in (0..self.levels).rev() {
+            let side = 1usize << level;
+            let child_width = self.width / (2 * side) as f64;
+            let shifts: Vec<Vec<C>> = (0..4)
+                .map(|q| {
+                    let (qx, qy) = ((q % 2) as f64 - 0.5, (q / 2) as f64 - 0.5);
+                    self.shift_matrix(C::new(qx * child_width, qy * child_width), false)
+                })
+                .collect();
+            let (coarse, fine) = self.multipoles.split_at_mut(level + 1);
+            for cell in 0..side * side {
+                if self.source_count[level][cell] == 0 {
+                    continue;
+                }
+                let (ix, iy) = (cell % side, cell / side);
+                for (q, shift) in shifts.iter().enumerate() {
+                    let child = (2 * iy + q / 2) * 2 * side + 2 * ix + (q % 2);
+                    if self.source_count[level + 1][child] == 0 {
+                        continue;
+                    }
+                    add_sandwich(
+                        shift,
+                        &fine[0][child * n * n..(child + 1) * n * n],
+                        &mut coarse[level][cell * n * n..(cell + 1) * n * n],
+                        1.0,
+                        n,
+                        &mut self.scratch
+                    );
+                }
+            }
+        }
+    }
+
+    // Local expansions from every well separated cell (M2L) and from the parent (L2L)
+    fn downward_pass(&mut self) {
+        let n = self.order + 1;
+        self.locals.resize(self.levels + 1, Vec::new());
+        for level in 0..=self.levels {
+            self.locals[level].clear();
+            self.locals[level].resize((1usize << (2 * level)) * n * n, C::new(0.0, 0.0));
+        }
+
+        for level in 2..=self.levels {
+            let side = 1usize << level;
+            let cell_width = self.width / side as f64;
+            self.prepare_m2l(cell_width);
+
+            // inherit the parent's expansion
+            let shifts: Vec<Vec<C>> = (0..4)
+                .map(|q| {
+                    let (qx, qy) = ((q % 2) as f64 - 0.5, (q / 2) as f64 - 0.5);
+                    self.shift_matrix(C::new(qx * cell_width, qy * cell_width), true)
+                })
+                .collect();
+            let (coarse, fine) = self.locals.split_at_mut(level);
+            for cell in 0..side * side {
+                if self.target_count[level][cell] == 0 {
+                    continue;
+                }
+                let (ix, iy) = (cell % side, cell / side);
+                let parent = (iy / 2) * (side / 2) + ix / 2;
+                let q = (iy % 2) * 2 + ix % 2;
+                add_sandwich(
+                    &shifts[q],
+                    &coarse[level - 1][parent * n * n..(parent + 1) * n * n],
+                    &mut fine[0][cell * n * n..(cell + 1) * n * n],
+                    1.0,
+                    n,
+                    &mut self.scratch
+                );
+
+                // children of the parent's neighbours that are not our own neighbours
+                let (px, py) = ((ix / 2) as i32, (iy / 2) as i32);
+                for sy in (2 * py - 2).max(0)..(2 * py + 4).min(side as i32) {
+                    for sx in (2 * px - 2).max(0)..(2 * px + 4).min(side as i32) {
+                        let (dx, dy) = (ix as i32 - sx, iy as i32 - sy);
+                        if dx.abs() <= 1 && dy.abs() <= 1 {
+                            continue;
+                        }
+                        let source = (sy as usize) * side + sx as usize;
+                        if self.source_count[level][source] == 0 {
+                            continue;
+                        }
+                        let slot = ((dx + 3) * 7 + dy + 3) as usize;
+                        add_sandwich(
+                            &self.m2l[slot * n * n..(slot + 1) * n * n],
+                            &self.multipoles[level][source * n * n..(source + 1) * n * n],
+                            &mut fine[0][cell * n * n..(cell + 1) * n * n],
+                            self.m2l_scale[slot],
+                            n,
+                            &mut self.scratch
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Field per unit charge at target i (at `pos`): local expansion of its leaf plus direct
+    // sums over the leaf and its neighbours
+    fn field_at(&self, i: usize, pos: Vec2, k: f64, min_dist: f64) -> Vec2 {
+        let n = self.order + 1;
+        let side = 1usize << self.levels;
+        let cell_width = self.width / side as f64;
+        let leaf = self.target_leaf[i];
+        let (ix, iy) = (leaf % side, leaf / side);
+
+        // gradient of phi = sum L[m][b] u^m u*^b, with g = d phi / du
+        let center = cell_center(self.origin, cell_width, ix, iy);
+        let u = C::new(pos.x - center.x, pos.y - center.y);
+        let power = powers(u, n);
+        let coefficients = &self.locals[self.levels][leaf * n * n..(leaf + 1) * n * n];
+        let mut g = C::new(0.0, 0.0);
+        for m in 1..n {
+            for b in 0..n {
+                g += coefficients[m * n + b] * power[m - 1] * power[b].conj() * (m as f64);
+            }
+        }
+        let mut field = Vec2::new(-2.0 * g.re, 2.0 * g.im) * k;
+
+        for sy in iy.saturating_sub(1)..(iy + 2).min(side) {
+            for sx in ix.saturating_sub(1)..(ix + 2).min(side) {
+                let neighbour = sy * side + sx;
+                let range = self.leaf_start[neighbour]..self.leaf_start[neighbour + 1];
+                for &(j, source_pos, charge) in &self.sources[range] {
+                    if j != i {
+                        field += coulomb_field(k, charge, pos - source_pos, min_dist);
+                    }
+                }
+            }
+        }
+        field
+    }
+}
+
+impl Universe {
+    // FMM Coulomb accelerations with particle i at positions[i]; tracers are targets only
+    pub(crate) fn fmm_accelerations(&self, positions: &[Vec2]) -> Vec<Vec2> {
+        let mut fmm = self.fmm.borrow_mut();
+        fmm.build(
+            self.fmm_order,
+            self.min_interaction_distance,
+            positions,
+            self.particles
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| !p.tracer)
+                .map(|(j, p)| (j, positions[j], p.charge))
+        );
+        fmm.upward_pass();
+        fmm.downward_pass();
+        let fmm = &*fmm;
+        let (k, min_dist) = (self.coulomb_constant, self.min_interaction_distance);
+        let fields = parallel::map(positions.len(), |i| fmm.field_at(i, positions[i], k, min_dist));
+        self.fields_to_accelerations(fields)
+    }
+}
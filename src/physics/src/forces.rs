@@ -0,0 +1,305 @@
+use std::rc::Rc;
+
+use crate::{ parallel, Magnet, Particle, Universe, Vec2 };
+use crate::fields::coulomb_field;
+
+// State the forces are evaluated at: particle i at positions[i] moving at velocities[i], magnet
+// m at magnet_positions[m] turned to magnet_angles[m]. Integrator stages pass predicted states,
+// so models must read positions, velocities and angles from here rather than from the universe.
+pub struct ForceState<'a> {
+    pub positions: &'a [Vec2],
+    pub velocities: &'a [Vec2],
+    pub magnet_positions: &'a [Vec2],
+    pub magnet_angles: &'a [f64],
+}
+
+// Accelerations being summed, one per particle and per magnet, plus the angular acceleration
+// of each magnet
+pub struct ForceBuffer {
+    pub particles: Vec<Vec2>,
+    pub magnets: Vec<Vec2>,
+    pub magnet_angular: Vec<f64>,
+}
+
+// One contribution to the accelerations of particles and magnets. The built-in models are
+// Coulomb forces between particles, magnet poles, and the applied fields with drag; more can
+// be registered with Universe::add_force_model and run after them in registration order.
+pub trait ForceModel {
+    // Identifies the model, e.g. for remove_force_model
+    fn name(&self) -> &str;
+
+    // Models that have nothing to add for this universe are skipped
+    fn enabled(&self, _universe: &Universe) -> bool {
+        true
+    }
+
+    fn accumulate(&self, universe: &Universe, state: &ForceState, accelerations: &mut ForceBuffer);
+}
+
+// Particle-particle Coulomb forces, summed by the selected backend
+struct Coulomb;
+
+impl ForceModel for Coulomb {
+    fn name(&self) -> &str {
+        "coulomb"
+    }
+
+    fn enabled(&self, universe: &Universe) -> bool {
+        !universe.particles.is_empty()
+    }
+
+    fn accumulate(&self, universe: &Universe, state: &ForceState, accelerations: &mut ForceBuffer) {
+        let coulomb = universe.coulomb_accelerations(universe.force_backend, state.positions);
+        for (acceleration, add) in accelerations.particles.iter_mut().zip(coulomb) {
+            *acceleration += add;
+        }
+    }
+}
+
+// Each magnet acts as two poles (+/- strength/2) on particles and on the other magnets'
+// poles, and feels the particles' field at its own poles, which both pushes and turns it
+struct MagnetPoles;
+
+impl ForceModel for MagnetPoles {
+    fn name(&self) -> &str {
+        "magnet_poles"
+    }
+
+    fn enabled(&self, universe: &Universe) -> bool {
+        !universe.magnets.is_empty()
+    }
+
+    fn accumulate(&self, universe: &Universe, state: &ForceState, accelerations: &mut ForceBuffer) {
+        let poles: Vec<[(Vec2, f64); 2]> = universe.magnets
+            .iter()
+            .zip(state.magnet_positions.iter().zip(state.magnet_angles))
+            .map(|(m, (&pos, &angle))| m.poles_at(pos, angle))
+            .collect();
+        let (k, min_dist) = (universe.coulomb_constant, universe.min_interaction_distance);
+
+        let flat: Vec<(Vec2, f64)> = poles.iter().flatten().copied().collect();
+        let fields = parallel::map(state.positions.len(), |i| {
+            let mut field = Vec2::new(0.0, 0.0);
+            for &(pole_pos, pole_strength) in &flat {
+                field += coulomb_field(k, pole_strength, state.positions[i] - pole_pos, min_dist);
+            }
+            field
+        });
+        let pole_accelerations = universe.fields_to_accelerations(fields);
+        for (acceleration, add) in accelerations.particles.iter_mut().zip(pole_accelerations) {
+            *acceleration += add;
+        }
+
+        for (i, magnet) in universe.magnets.iter().enumerate() {
+            let mut force = Vec2::new(0.0, 0.0);
+            let mut torque = 0.0;
+            for &(pole_pos, pole_strength) in &poles[i] {
+                let force_before = force;
+                // Contribution from particles (tracers exert no force)
+                for (p, &pos) in universe.particles.iter().zip(state.positions) {
+                    if !p.tracer {
+                        force += coulomb_field(k, p.charge, pole_pos - pos, min_dist) * pole_strength;
+                    }
+                }
+                // Contribution from other magnets (pole-pole interactions)
+                for (j, other) in poles.iter().enumerate() {
+                    if i == j {
+                        continue;
+                    }
+                    for &(other_pos, other_strength) in other {
+                        force +=
+                            coulomb_field(k, other_strength, pole_pos - other_pos, min_dist) *
+                            pole_strength;
+                    }
+                }
+                let pole_force = force - force_before;
+                let arm = pole_pos - state.magnet_positions[i];
+                torque += arm.x * pole_force.y - arm.y * pole_force.x;
+            }
+            accelerations.magnets[i] += force / magnet.mass;
+            // a thin bar of length `size` turning about its centre
+            let size = magnet.size as f64;
+            let inertia = (magnet.mass * size * size) / 12.0;
+            if inertia > 0.0 {
+                accelerations.magnet_angular[i] += torque / inertia;
+            }
+        }
+    }
+}
+
+// Uniform applied fields and linear drag, see external.rs
+struct External;
+
+impl ForceModel for External {
+    fn name(&self) -> &str {
+        "external"
+    }
+
+    fn enabled(&self, universe: &Universe) -> bool {
+        universe.has_external_forces()
+    }
+
+    fn accumulate(&self, universe: &Universe, state: &ForceState, accelerations: &mut ForceBuffer) {
+        universe.add_external_accelerations(state.velocities, &mut accelerations.particles);
+    }
+}
+
+const BUILT_IN: [&dyn ForceModel; 3] = [&Coulomb, &MagnetPoles, &External];
+
+impl Universe {
+    // Sums every enabled model at `state`
+    pub(crate) fn evaluate_forces(&self, state: &ForceState) -> ForceBuffer {
+        let zero = Vec2::new(0.0, 0.0);
+        let mut accelerations = ForceBuffer {
+            particles: vec![zero; state.positions.len()],
+            magnets: vec![zero; state.magnet_positions.len()],
+            magnet_angular: vec![0.0; state.magnet_positions.len()],
+        };
+        let models = BUILT_IN.into_iter().chain(self.force_models.iter().map(|m| m.as_ref()));
+        for model in models {
+            if model.enabled(self) {
+                model.accumulate(self, state, &mut accelerations);
+            }
+        }
+        accelerations
+    }
+}
+
+// Rust-side API for custom forces; not exported to JavaScript
+impl Universe {
+    // Adds a force model evaluated after the built-in ones. Models are code rather than scene
+    // data: they are not saved, and they stay registered when a scene is loaded or undone.
+    pub fn add_force_model(&mut self, model: impl ForceModel + 'static) {
+        self.force_models.push(Rc::new(model));
+    }
+
+    // Removes the registered models called `name`; returns whether any were removed
+    pub fn remove_force_model(&mut self, name: &str) -> bool {
+        let before = self.force_models.len();
+        self.force_models.retain(|m| m.name() != name);
+        self.force_models.len() != before
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn magnets(&self) -> &[Magnet] {
+        &self.magnets
+    }
+
+    // Charge over the mass used for particle i, so a force model can turn a field into an
+    // acceleration the same way the built-in ones do
+    pub fn charge_to_mass(&self, i: usize) -> f64 {
+        self.particles[i].charge / self.effective_mass(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unsoftened k q / r^2 field of a point source, written out independently of coulomb_field
+    fn point_field(k: f64, q: f64, source: Vec2, at: Vec2) -> Vec2 {
+        let r = at - source;
+        r * ((k * q) / r.magnitude().powi(3))
+    }
+
+    fn current_forces(universe: &Universe) -> ForceBuffer {
+        let positions: Vec<Vec2> = universe.particles
+            .iter()
+            .map(|p| p.pos)
+            .collect();
+        let velocities: Vec<Vec2> = universe.particles
+            .iter()
+            .map(|p| p.vel)
+            .collect();
+        let magnet_positions: Vec<Vec2> = universe.magnets
+            .iter()
+            .map(|m| m.pos)
+            .collect();
+        let magnet_angles: Vec<f64> = universe.magnets
+            .iter()
+            .map(|m| m.angle)
+            .collect();
+        universe.evaluate_forces(
+            &(ForceState {
+                positions: &positions,
+                velocities: &velocities,
+                magnet_positions: &magnet_positions,
+                magnet_angles: &magnet_angles,
+            })
+        )
+    }
+
+    // Constant push along +x on every particle
+    struct Wind;
+
+    impl ForceModel for Wind {
+        fn name(&self) -> &str {
+            "wind"
+        }
+
+        fn accumulate(&self, _: &Universe, _: &ForceState, accelerations: &mut ForceBuffer) {
+            for acceleration in accelerations.particles.iter_mut() {
+                acceleration.x += 50.0;
+            }
+        }
+    }
+
+    #[test]
+    fn registered_models_join_the_step_until_removed() {
+        let mut universe = Universe::new_empty();
+        universe.add_particle_simple(0.0, 0.0, 0.0, 0.0, 1.0);
+        universe.add_force_model(Wind);
+        assert_eq!(current_forces(&universe).particles[0].x, 50.0);
+        universe.time_step(0.1);
+        let pushed = universe.particles[0].vel;
+        assert!(pushed.x > 0.0 && pushed.y == 0.0);
+
+        assert!(universe.remove_force_model("wind"));
+        assert!(!universe.remove_force_model("wind"));
+        assert_eq!(current_forces(&universe).particles[0].x, 0.0);
+        universe.time_step(0.1);
+        assert_eq!(universe.particles[0].vel.x, pushed.x);
+    }
+
+    #[test]
+    fn coulomb_and_poles_match_hand_computed_forces() {
+        let mut universe = Universe::new_empty();
+        universe.add_particle(-40.0, 90.0, 0.0, 0.0, 5.0, 2.0, 0xff0000, 3.0);
+        universe.add_particle(70.0, -60.0, 0.0, 0.0, 5.0, 0.5, 0x0000ff, -1.5);
+        universe.add_magnet(10.0, 5.0, 0.6, 60.0, 20.0, 4.0, 0xff0000, 0x0000ff, 8.0, false);
+        let k = universe.coulomb_constant;
+        let (p, m) = (&universe.particles, &universe.magnets[0]);
+        let axis = Vec2::new(f64::cos(0.6), f64::sin(0.6));
+        let poles = [(m.pos + axis * 30.0, 4.0), (m.pos - axis * 30.0, -4.0)];
+
+        let forces = current_forces(&universe);
+        for i in 0..2 {
+            let other = &p[1 - i];
+            let mut field = point_field(k, other.charge, other.pos, p[i].pos);
+            for &(pos, strength) in &poles {
+                field += point_field(k, strength, pos, p[i].pos);
+            }
+            let expected = field * (p[i].charge / p[i].mass);
+            assert!((forces.particles[i] - expected).magnitude() < 1e-12 * expected.magnitude());
+        }
+
+        let mut force = Vec2::new(0.0, 0.0);
+        let mut torque = 0.0;
+        for &(pos, strength) in &poles {
+            let field = point_field(k, p[0].charge, p[0].pos, pos) +
+                point_field(k, p[1].charge, p[1].pos, pos);
+            let pole_force = field * strength;
+            let arm = pos - m.pos;
+            force += pole_force;
+            torque += arm.x * pole_force.y - arm.y * pole_force.x;
+        }
+        let expected = force / 4.0;
+        assert!((forces.magnets[0] - expected).magnitude() < 1e-12 * expected.magnitude());
+        let expected_angular = torque / ((4.0 * 60.0 * 60.0) / 12.0);
+        let angular_error = (forces.magnet_angular[0] - expected_angular).abs();
+        assert!(angular_error < 1e-12 * expected_angular.abs());
+    }
+}
@@ -0,0 +1,559 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    Boundary,
+    ForceBackend,
+    Implementation,
+    Magnet,
+    MeshAssignment,
+    Particle,
+    TrailColorMode,
+    Universe,
+};
+
+// Undo entries kept by default; the oldest are dropped beyond this
+const DEFAULT_UNDO_LIMIT: usize = 200;
+
+// Lists the editable fields of a type once and generates a function returning a bitmask of
+// the fields that differ between two values, plus one copying only the masked fields. Undo
+// then restores just what an edit touched and leaves simulated motion alone.
+macro_rules! masked_fields {
+    ($changed:ident, $copy:ident, $ty:ty, [$($field:ident),* $(,)?]) => {
+        fn $changed(a: &$ty, b: &$ty) -> u64 {
+            let mut mask = 0;
+            let mut bit = 1;
+            $(
+                if a.$field != b.$field {
+                    mask |= bit;
+                }
+                bit <<= 1;
+            )*
+            let _ = bit;
+            mask
+        }
+
+        fn $copy(target: &mut $ty, source: &$ty, mask: u64) {
+            let mut bit = 1;
+            $(
+                if mask & bit != 0 {
+                    target.$field = source.$field.clone();
+                }
+                bit <<= 1;
+            )*
+            let _ = bit;
+        }
+    };
+}
+
+masked_fields!(particle_changes, copy_particle_fields, Particle, [
+    pos,
+    vel,
+    radius,
+    mass,
+    color,
+    charge,
+    fixed,
+    tracer,
+    trail_length,
+    trail_interval,
+]);
+
+masked_fields!(magnet_changes, copy_magnet_fields, Magnet, [
+    pos,
+    vel,
+    angle,
+    spin,
+    size,
+    thickness,
+    mass,
+    color_north,
+    color_south,
+    strength,
+    fixed,
+]);
+
+// Declares the undoable universe settings once: the `Settings` copy plus capture/apply
+macro_rules! settings {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        #[derive(Clone, PartialEq)]
+        pub(crate) struct Settings {
+            $($field: $ty,)*
+        }
+
+        impl Settings {
+            fn capture(universe: &Universe) -> Settings {
+                Settings {
+                    $($field: universe.$field.clone(),)*
+                }
+            }
+        }
+
+        masked_fields!(settings_changes, copy_settings_fields, Settings, [$($field),*]);
+
+        fn apply_settings(universe: &mut Universe, settings: &Settings, mask: u64) {
+            let mut current = Settings::capture(universe);
+            copy_settings_fields(&mut current, settings, mask);
+            $(universe.$field = current.$field;)*
+        }
+    };
+}
+
+// Pause state is playback control, not part of the scene, so it is deliberately left out
+settings! {
+    coulomb_constant: f64,
+    air_density: f64,
+    show_trails: bool,
+    trail_length: usize,
+    trail_interval: u32,
+    trail_color_mode: TrailColorMode,
+    implementation: Implementation,
+    speed: f64,
+    default_mass: f64,
+    mass_calculation: bool,
+    default_charge: f64,
+    spawn_range: f64,
+    wind_x: f64,
+    wind_y: f64,
+    external_field_x: f64,
+    external_field_y: f64,
+    magnetic_field_z: f64,
+    damping: f64,
+    min_interaction_distance: f64,
+    force_backend: ForceBackend,
+    quadtree_theta: f64,
+    quadtree_threshold: usize,
+    fmm_threshold: usize,
+    auto_backend_timing: bool,
+    fmm_order: usize,
+    mesh_size: usize,
+    mesh_assignment: MeshAssignment,
+    mesh_short_range: bool,
+    collisions_enabled: bool,
+    restitution: f64,
+    boundary: Boundary,
+    boundary_width: f64,
+    boundary_height: f64,
+}
+
+// One reversible change. Entities are addressed by ID so that edits stay valid when other
+// entities are removed and indices shift.
+#[derive(Clone)]
+pub(crate) enum Edit {
+    Particle {
+        id: u32,
+        mask: u64,
+        before: Box<Particle>,
+        after: Box<Particle>,
+    },
+    Magnet {
+        id: u32,
+        mask: u64,
+        before: Box<Magnet>,
+        after: Box<Magnet>,
+    },
+    Settings {
+        mask: u64,
+        before: Box<Settings>,
+        after: Box<Settings>,
+    },
+    AddParticle {
+        index: usize,
+        particle: Box<Particle>,
+    },
+    RemoveParticle {
+        index: usize,
+        particle: Box<Particle>,
+    },
+    AddMagnet {
+        index: usize,
+        magnet: Box<Magnet>,
+    },
+    RemoveMagnet {
+        index: usize,
+        magnet: Box<Magnet>,
+    },
+    // Whole-state replacement (reset, loading a scene), stored as binary snapshots
+    Replace {
+        before: Vec<u8>,
+        after: Vec<u8>,
+    },
+}
+
+// Consecutive edits with the same key merge into one undo step (e.g. every position update
+// of a drag)
+#[derive(Clone, Copy, PartialEq)]
+enum CoalesceKey {
+    Particle(u32, u64),
+    Magnet(u32, u64),
+    Settings(u64),
+}
+
+impl Edit {
+    fn coalesce_key(&self) -> Option<CoalesceKey> {
+        match self {
+            Edit::Particle { id, mask, .. } => Some(CoalesceKey::Particle(*id, *mask)),
+            Edit::Magnet { id, mask, .. } => Some(CoalesceKey::Magnet(*id, *mask)),
+            Edit::Settings { mask, .. } => Some(CoalesceKey::Settings(*mask)),
+            _ => None,
+        }
+    }
+
+    // Folds a later edit with the same key into this one: keep our `before`, take its `after`
+    fn merge(&mut self, later: Edit) {
+        match (self, later) {
+            (Edit::Particle { after, .. }, Edit::Particle { after: later, .. }) => {
+                *after = later;
+            }
+            (Edit::Magnet { after, .. }, Edit::Magnet { after: later, .. }) => {
+                *after = later;
+            }
+            (Edit::Settings { after, .. }, Edit::Settings { after: later, .. }) => {
+                *after = later;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct History {
+    undo: Vec<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    limit: usize,
+    // open begin_edit_group() calls, and whether the group's entry has been pushed yet
+    group_depth: u32,
+    group_started: bool,
+    // key of the last recorded edit while it may still absorb the next one
+    coalesce: Option<CoalesceKey>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            undo: vec![],
+            redo: vec![],
+            limit: DEFAULT_UNDO_LIMIT,
+            group_depth: 0,
+            group_started: false,
+            coalesce: None,
+        }
+    }
+}
+
+impl History {
+    fn record(&mut self, edit: Edit) {
+        self.redo.clear();
+        let key = edit.coalesce_key();
+        if key.is_some() && key == self.coalesce {
+            if let Some(last) = self.undo.last_mut().and_then(|entry| entry.last_mut()) {
+                last.merge(edit);
+                return;
+            }
+        }
+        self.coalesce = key;
+
+        if self.group_depth > 0 && self.group_started {
+            if let Some(entry) = self.undo.last_mut() {
+                entry.push(edit);
+                return;
+            }
+        }
+        self.group_started = self.group_depth > 0;
+        self.undo.push(vec![edit]);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        if self.undo.len() > self.limit {
+            let excess = self.undo.len() - self.limit;
+            self.undo.drain(..excess);
+        }
+    }
+
+    fn seal(&mut self) {
+        self.coalesce = None;
+    }
+
+    fn close_groups(&mut self) {
+        self.group_depth = 0;
+        self.group_started = false;
+        self.seal();
+    }
+}
+
+impl Universe {
+    pub(crate) fn record(&mut self, edit: Edit) {
+        self.history.record(edit);
+        self.mark_timeline_dirty();
+    }
+
+    // Applies `edit` to one particle and records whatever it changed
+    pub(crate) fn edit_particle(&mut self, index: usize, edit: impl FnOnce(&mut Particle)) {
+        if let Some(particle) = self.particles.get_mut(index) {
+            let before = particle.clone();
+            edit(particle);
+            let mask = particle_changes(&before, particle);
+            if mask != 0 {
+                let edit = Edit::Particle {
+                    id: before.id,
+                    mask,
+                    before: Box::new(before),
+                    after: Box::new(particle.clone()),
+                };
+                self.record(edit);
+            }
+        }
+    }
+
+    pub(crate) fn edit_magnet(&mut self, index: usize, edit: impl FnOnce(&mut Magnet)) {
+        if let Some(magnet) = self.magnets.get_mut(index) {
+            let before = magnet.clone();
+            edit(magnet);
+            let mask = magnet_changes(&before, magnet);
+            if mask != 0 {
+                let edit = Edit::Magnet {
+                    id: before.id,
+                    mask,
+                    before: Box::new(before),
+                    after: Box::new(magnet.clone()),
+                };
+                self.record(edit);
+            }
+        }
+    }
+
+    pub(crate) fn edit_settings(&mut self, edit: impl FnOnce(&mut Universe)) {
+        let before = Settings::capture(self);
+        edit(self);
+        let after = Settings::capture(self);
+        let mask = settings_changes(&before, &after);
+        if mask != 0 {
+            self.record(Edit::Settings {
+                mask,
+                before: Box::new(before),
+                after: Box::new(after),
+            });
+        }
+    }
+
+    pub(crate) fn insert_particle_undoable(&mut self, particle: Particle) -> u32 {
+        let id = self.insert_particle(particle);
+        let index = self.particles.len() - 1;
+        let particle = Box::new(self.particles[index].clone());
+        self.record(Edit::AddParticle { index, particle });
+        id
+    }
+
+    pub(crate) fn insert_magnet_undoable(&mut self, magnet: Magnet) -> u32 {
+        let id = self.insert_magnet(magnet);
+        let index = self.magnets.len() - 1;
+        let magnet = Box::new(self.magnets[index].clone());
+        self.record(Edit::AddMagnet { index, magnet });
+        id
+    }
+
+    // Swaps in a whole new state as one undoable step; the history itself survives
+    pub(crate) fn replace_undoable(&mut self, next: Universe) {
+        let before = self.encode_snapshot(false);
+        self.replace_state(next);
+        let after = self.encode_snapshot(false);
+        self.record(Edit::Replace { before, after });
+        self.refresh_render_buffers();
+    }
+
+    fn apply_edit(&mut self, edit: &Edit, forward: bool) {
+        match edit {
+            Edit::Particle { id, mask, before, after } => {
+                if let Some(index) = self.get_particle_index(*id) {
+                    let source = if forward { after } else { before };
+                    copy_particle_fields(&mut self.particles[index], source, *mask);
+                }
+            }
+            Edit::Magnet { id, mask, before, after } => {
+                if let Some(index) = self.get_magnet_index(*id) {
+                    let source = if forward { after } else { before };
+                    copy_magnet_fields(&mut self.magnets[index], source, *mask);
+                }
+            }
+            Edit::Settings { mask, before, after } => {
+                apply_settings(self, if forward { after } else { before }, *mask);
+            }
+            Edit::AddParticle { index, particle } | Edit::RemoveParticle { index, particle } => {
+                let adding = matches!(edit, Edit::AddParticle { .. }) == forward;
+                if adding {
+                    let index = (*index).min(self.particles.len());
+                    self.insert_particle_at(index, (**particle).clone());
+                    self.trails.insert(index);
+                } else if let Some(index) = self.get_particle_index(particle.id) {
+                    self.take_particle(index);
+                    self.trails.remove(index);
+                }
+            }
+            Edit::AddMagnet { index, magnet } | Edit::RemoveMagnet { index, magnet } => {
+                let adding = matches!(edit, Edit::AddMagnet { .. }) == forward;
+                if adding {
+                    let index = (*index).min(self.magnets.len());
+                    self.insert_magnet_at(index, (**magnet).clone());
+                } else if let Some(index) = self.get_magnet_index(magnet.id) {
+                    self.take_magnet(index);
+                }
+            }
+            Edit::Replace { before, after } => {
+                // snapshots written by replace_undoable always decode
+                let _ = self.restore(if forward { after } else { before });
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Revert the most recent edit; returns false when there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        self.history.close_groups();
+        let entry = match self.history.undo.pop() {
+            Some(entry) => entry,
+            None => {
+                return false;
+            }
+        };
+        for edit in entry.iter().rev() {
+            self.apply_edit(edit, false);
+        }
+        self.history.redo.push(entry);
+        self.mark_timeline_dirty();
+        self.refresh_render_buffers();
+        true
+    }
+
+    // Re-apply the most recently undone edit; returns false when there is nothing to redo
+    pub fn redo(&mut self) -> bool {
+        self.history.close_groups();
+        let entry = match self.history.redo.pop() {
+            Some(entry) => entry,
+            None => {
+                return false;
+            }
+        };
+        for edit in entry.iter() {
+            self.apply_edit(edit, true);
+        }
+        self.history.undo.push(entry);
+        self.mark_timeline_dirty();
+        self.refresh_render_buffers();
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    pub fn get_undo_count(&self) -> usize {
+        self.history.undo.len()
+    }
+
+    pub fn get_redo_count(&self) -> usize {
+        self.history.redo.len()
+    }
+
+    // Everything edited until the matching end_edit_group() becomes a single undo step
+    // (groups nest; only the outermost one counts)
+    pub fn begin_edit_group(&mut self) {
+        if self.history.group_depth == 0 {
+            self.history.group_started = false;
+            self.history.seal();
+        }
+        self.history.group_depth += 1;
+    }
+
+    pub fn end_edit_group(&mut self) {
+        self.history.group_depth = self.history.group_depth.saturating_sub(1);
+        if self.history.group_depth == 0 {
+            self.history.close_groups();
+        }
+    }
+
+    // Repeated updates of the same property of the same entity merge into one undo step.
+    // Call this when a gesture ends (e.g. on pointer up) so the next drag starts a new step.
+    pub fn commit_edit(&mut self) {
+        self.history.seal();
+    }
+
+    pub fn set_undo_limit(&mut self, limit: usize) {
+        self.history.limit = limit;
+        self.history.trim();
+    }
+
+    pub fn get_undo_limit(&self) -> usize {
+        self.history.limit
+    }
+
+    pub fn clear_undo_history(&mut self) {
+        let limit = self.history.limit;
+        self.history = History {
+            limit,
+            ..History::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Universe;
+
+    fn dragged_scene() -> Universe {
+        let mut universe = Universe::new_empty();
+        universe.add_particle_simple(0.0, 0.0, 0.0, 0.0, 1.0);
+        universe.clear_undo_history();
+        universe
+    }
+
+    #[test]
+    fn updates_within_one_drag_coalesce() {
+        let mut universe = dragged_scene();
+        for x in [5.0, 10.0, 20.0] {
+            universe.update_particle_position(0, x, 0.0);
+        }
+        assert_eq!(universe.get_undo_count(), 1);
+        assert!(universe.undo());
+        assert_eq!(universe.particles[0].pos.x, 0.0);
+    }
+
+    #[test]
+    fn drags_separated_by_pointer_up_are_separate_steps() {
+        let mut universe = dragged_scene();
+        universe.update_particle_position(0, 10.0, 0.0);
+        universe.update_particle_position(0, 20.0, 0.0);
+        universe.commit_edit();
+        universe.update_particle_position(0, 30.0, 0.0);
+        universe.update_particle_position(0, 40.0, 0.0);
+        universe.commit_edit();
+
+        assert_eq!(universe.get_undo_count(), 2);
+        assert!(universe.undo());
+        assert_eq!(universe.particles[0].pos.x, 20.0);
+        assert!(universe.undo());
+        assert_eq!(universe.particles[0].pos.x, 0.0);
+    }
+
+    #[test]
+    fn drags_separated_by_a_time_step_are_separate_steps() {
+        let mut universe = dragged_scene();
+        universe.update_particle_position(0, 10.0, 0.0);
+        universe.update_particle_position(0, 20.0, 0.0);
+        universe.time_step(0.016);
+        let stepped = universe.particles[0].pos.x;
+        universe.update_particle_position(0, 30.0, 0.0);
+        universe.update_particle_position(0, 40.0, 0.0);
+
+        assert_eq!(universe.get_undo_count(), 2);
+        assert!(universe.undo());
+        assert_eq!(universe.particles[0].pos.x, stepped);
+        assert!(universe.undo());
+        assert_eq!(universe.particles[0].pos.x, 0.0);
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{ Magnet, Particle, Universe };
+
+// id -> index maps behind the by-ID API, kept up to date by the insert and remove helpers
+// below. A cached index is checked against the entity it points at before it is used, so a
+// state swapped in wholesale (restore, load) costs one rebuild on the next lookup.
+#[derive(Clone, Default)]
+pub(crate) struct IdIndex {
+    particles: HashMap<u32, usize>,
+    magnets: HashMap<u32, usize>,
+}
+
+fn cached_index(
+    map: &mut HashMap<u32, usize>,
+    len: usize,
+    id_at: impl Fn(usize) -> u32,
+    id: u32
+) -> Option<usize> {
+    match map.get(&id) {
+        Some(&index) if index < len && id_at(index) == id => {
+            return Some(index);
+        }
+        None if map.len() == len => {
+            return None;
+        }
+        _ => {}
+    }
+    map.clear();
+    map.extend((0..len).map(|i| (id_at(i), i)));
+    map.get(&id).copied()
+}
+
+fn index_inserted(map: &mut HashMap<u32, usize>, id: u32, index: usize) {
+    for i in map.values_mut() {
+        if *i >= index {
+            *i += 1;
+        }
+    }
+    map.insert(id, index);
+}
+
+fn index_removed(map: &mut HashMap<u32, usize>, id: u32, index: usize) {
+    map.remove(&id);
+    for i in map.values_mut() {
+        if *i > index {
+            *i -= 1;
+        }
+    }
+}
+
+// Stable entity IDs. Indices shift whenever something is removed, so anything that has to
+// keep referring to the same particle or magnet (UI selection, bonds, events) should hold
+// its ID instead. The index-based API remains as a compatibility layer.
+impl Universe {
+    pub(crate) fn allocate_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub(crate) fn insert_particle(&mut self, mut particle: Particle) -> u32 {
+        particle.id = self.allocate_id();
+        let id = particle.id;
+        self.insert_particle_at(self.particles.len(), particle);
+        id
+    }
+
+    pub(crate) fn insert_magnet(&mut self, mut magnet: Magnet) -> u32 {
+        magnet.id = self.allocate_id();
+        let id = magnet.id;
+        self.insert_magnet_at(self.magnets.len(), magnet);
+        id
+    }
+
+    // Every insertion into and removal from `particles` and `magnets` goes through these four,
+    // which keep the id index in step
+    pub(crate) fn insert_particle_at(&mut self, index: usize, particle: Particle) {
+        index_inserted(&mut self.ids.borrow_mut().particles, particle.id, index);
+        self.particles.insert(index, particle);
+    }
+
+    pub(crate) fn take_particle(&mut self, index: usize) -> Particle {
+        let particle = self.particles.remove(index);
+        index_removed(&mut self.ids.borrow_mut().particles, particle.id, index);
+        particle
+    }
+
+    pub(crate) fn insert_magnet_at(&mut self, index: usize, magnet: Magnet) {
+        index_inserted(&mut self.ids.borrow_mut().magnets, magnet.id, index);
+        self.magnets.insert(index, magnet);
+    }
+
+    pub(crate) fn take_magnet(&mut self, index: usize) -> Magnet {
+        let magnet = self.magnets.remove(index);
+        index_removed(&mut self.ids.borrow_mut().magnets, magnet.id, index);
+        magnet
+    }
+
+    // Gives fresh IDs to entities loaded without one (or with a duplicate) and moves
+    // `next_id` past every ID in use
+    pub(crate) fn repair_ids(&mut self) {
+        let mut seen = std::collections::HashSet::new();
+        let mut missing = Vec::new();
+        let mut missing_magnets = Vec::new();
+        for (i, p) in self.particles.iter().enumerate() {
+            if p.id == 0 || !seen.insert(p.id) {
+                missing.push(i);
+            }
+        }
+        for (i, m) in self.magnets.iter().enumerate() {
+            if m.id == 0 || !seen.insert(m.id) {
+                missing_magnets.push(i);
+            }
+        }
+        let highest = seen.iter().copied().max().unwrap_or(0);
+        self.next_id = self.next_id.max(highest + 1);
+        for i in missing {
+            self.particles[i].id = self.allocate_id();
+        }
+        for i in missing_magnets {
+            self.magnets[i].id = self.allocate_id();
+        }
+        *self.ids.borrow_mut() = IdIndex::default();
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    pub fn get_particle_index(&self, id: u32) -> Option<usize> {
+        let particles = &self.particles;
+        cached_index(&mut self.ids.borrow_mut().particles, particles.len(), |i| particles[i].id, id)
+    }
+
+    pub fn get_magnet_index(&self, id: u32) -> Option<usize> {
+        let magnets = &self.magnets;
+        cached_index(&mut self.ids.borrow_mut().magnets, magnets.len(), |i| magnets[i].id, id)
+    }
+
+    pub fn get_particle_id(&self, index: usize) -> Option<u32> {
+        self.particles.get(index).map(|p| p.id)
+    }
+
+    pub fn get_magnet_id(&self, index: usize) -> Option<u32> {
+        self.magnets.get(index).map(|m| m.id)
+    }
+
+    pub fn get_particle_by_id(&self, id: u32) -> Option<Particle> {
+        self.get_particle_index(id).and_then(|index| self.get_particle(index))
+    }
+
+    pub fn get_magnet_by_id(&self, id: u32) -> Option<Magnet> {
+        self.get_magnet_index(id).and_then(|index| self.get_magnet(index))
+    }
+
+    // Returns false when no particle has this id
+    pub fn remove_particle_by_id(&mut self, id: u32) -> bool {
+        match self.get_particle_index(id) {
+            Some(index) => {
+                self.remove_particle(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Returns false when no magnet has this id
+    pub fn remove_magnet_by_id(&mut self, id: u32) -> bool {
+        match self.get_magnet_index(id) {
+            Some(index) => {
+                self.remove_magnet(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn update_magnet_position_by_id(&mut self, id: u32, x: f64, y: f64) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_position(index, x, y);
+        }
+    }
+
+    pub fn update_magnet_velocity_by_id(&mut self, id: u32, vx: f64, vy: f64) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_velocity(index, vx, vy);
+        }
+    }
+
+    pub fn update_magnet_mass_by_id(&mut self, id: u32, mass: f64) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_mass(index, mass);
+        }
+    }
+
+    pub fn update_magnet_color_north_by_id(&mut self, id: u32, color: u32) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_color_north(index, color);
+        }
+    }
+
+    pub fn update_magnet_color_south_by_id(&mut self, id: u32, color: u32) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_color_south(index, color);
+        }
+    }
+
+    pub fn update_magnet_size_by_id(&mut self, id: u32, size: f32) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_size(index, size);
+        }
+    }
+
+    pub fn update_magnet_thickness_by_id(&mut self, id: u32, thickness: f32) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_thickness(index, thickness);
+        }
+    }
+
+    pub fn update_magnet_strength_by_id(&mut self, id: u32, strength: f64) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_strength(index, strength);
+        }
+    }
+
+    pub fn update_magnet_fixed_by_id(&mut self, id: u32, fixed: bool) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_fixed(index, fixed);
+        }
+    }
+
+    pub fn update_magnet_angle_by_id(&mut self, id: u32, angle: f64) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_angle(index, angle);
+        }
+    }
+
+    pub fn update_magnet_spin_by_id(&mut self, id: u32, spin: f64) {
+        if let Some(index) = self.get_magnet_index(id) {
+            self.update_magnet_spin(index, spin);
+        }
+    }
+
+    pub fn update_particle_position_by_id(&mut self, id: u32, x: f64, y: f64) {
+        if let Some(index) = self.get_particle_index(id) {
+            self.update_particle_position(index, x, y);
+        }
+    }
+
+    pub fn update_particle_velocity_by_id(&mut self, id: u32, vx: f64, vy: f64) {
+        if let Some(index) = self.get_particle_index(id) {
+            self.update_particle_velocity(index, vx, vy);
+        }
+    }
+
+    pub fn update_particle_mass_by_id(&mut self, id: u32, mass: f64) {
+        if let Some(index) = self.get_particle_index(id) {
+            self.update_particle_mass(index, mass);
+        }
+    }
+
+    pub fn update_particle_charge_by_id(&mut self, id: u32, charge: f64) {
+        if let Some(index) = self.get_particle_index(id) {
+            self.update_particle_charge(index, charge);
+        }
+    }
+
+    pub fn update_particle_radius_by_id(&mut self, id: u32, radius: f32) {
+        if let Some(index) = self.get_particle_index(id) {
+            self.update_particle_radius(index, radius);
+        }
+    }
+
+    pub fn update_particle_color_by_id(&mut self, id: u32, color: u32) {
+        if let Some(index) = self.get_particle_index(id) {
+            self.update_particle_color(index, color);
+        }
+    }
+
+    pub fn update_particle_fixed_by_id(&mut self, id: u32, fixed: bool) {
+        if let Some(index) = self.get_particle_index(id) {
+            self.update_particle_fixed(index, fixed);
+        }
+    }
+
+    pub fn update_particle_tracer_by_id(&mut self, id: u32, tracer: bool) {
+        if let Some(index) = self.get_particle_index(id) {
+            self.update_particle_tracer(index, tracer);
+        }
+    }
+
+    pub fn update_particle_trail_length_by_id(&mut self, id: u32, length: Option<u32>) {
+        if let Some(index) = self.get_particle_index(id) {
+            self.update_particle_trail_length(index, length);
+        }
+    }
+
+    pub fn update_particle_trail_interval_by_id(&mut self, id: u32, interval: Option<u32>) {
+        if let Some(index) = self.get_particle_index(id) {
+            self.update_particle_trail_interval(index, interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Universe;
+
+    fn three_particles() -> (Universe, [u32; 3]) {
+        let mut universe = Universe::new_empty();
+        let ids = [
+            universe.add_particle_simple(0.0, 0.0, 0.0, 0.0, 1.0),
+            universe.add_particle_simple(10.0, 0.0, 0.0, 0.0, 1.0),
+            universe.add_particle_simple(20.0, 0.0, 0.0, 0.0, 1.0),
+        ];
+        (universe, ids)
+    }
+
+    #[test]
+    fn ids_follow_particles_through_removal_and_undo() {
+        let (mut universe, [a, b, c]) = three_particles();
+        assert_eq!(universe.get_particle_index(c), Some(2));
+
+        assert!(universe.remove_particle_by_id(a));
+        assert_eq!(universe.get_particle_index(a), None);
+        assert_eq!(universe.get_particle_index(b), Some(0));
+        assert_eq!(universe.get_particle_index(c), Some(1));
+        universe.update_particle_position_by_id(c, 99.0, 0.0);
+        assert_eq!(universe.particles[1].pos.x, 99.0);
+
+        // removed ids are never handed out again
+        let d = universe.add_particle_simple(0.0, 0.0, 0.0, 0.0, 1.0);
+        assert!(d > c);
+
+        universe.undo(); // the add
+        universe.undo(); // the move
+        universe.undo(); // the removal
+        assert_eq!(universe.get_particle_index(a), Some(0));
+        assert_eq!(universe.get_particle_index(c), Some(2));
+        assert_eq!(universe.get_particle_index(d), None);
+    }
+
+    #[test]
+    fn lookups_survive_a_restored_state() {
+        let (mut universe, [a, _, c]) = three_particles();
+        let saved = universe.snapshot();
+        universe.remove_particle_by_id(a);
+        universe.add_particle_simple(0.0, 0.0, 0.0, 0.0, 1.0);
+        universe.restore(&saved).unwrap();
+        assert_eq!(universe.get_particle_index(a), Some(0));
+        assert_eq!(universe.get_particle_index(c), Some(2));
+    }
+
+    #[test]
+    fn repair_gives_missing_and_duplicate_ids_fresh_ones() {
+        let (mut universe, _) = three_particles();
+        universe.add_magnet_simple(0.0, 50.0, 1.0);
+        universe.particles[0].id = 7;
+        universe.particles[1].id = 0;
+        universe.particles[2].id = 7;
+        universe.magnets[0].id = 0;
+        universe.next_id = 1;
+        universe.repair_ids();
+
+        let mut ids: Vec<u32> = universe.particles
+            .iter()
+            .map(|p| p.id)
+            .chain(universe.magnets.iter().map(|m| m.id))
+            .collect();
+        assert_eq!(ids[0], 7);
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 4);
+        assert!(ids.iter().all(|&id| id != 0 && id < universe.next_id));
+        assert_eq!(universe.get_particle_index(universe.particles[2].id), Some(2));
+    }
+
+    #[test]
+    fn ids_continue_past_a_loaded_scene() {
+        let (universe, [_, _, c]) = three_particles();
+        let json = universe.to_json().unwrap();
+        let mut loaded = Universe::from_json(&json).unwrap();
+        assert_eq!(loaded.get_particle_index(c), Some(2));
+        assert!(loaded.add_particle_simple(0.0, 0.0, 0.0, 0.0, 1.0) > c);
+    }
+}
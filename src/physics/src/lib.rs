@@ -1,9 +1,59 @@
-use rand::Rng;
 use wasm_bindgen::prelude::*;
 use serde::{ Serialize, Deserialize };
 use core::ops;
+use std::cell::RefCell;
 use std::vec;
 
+mod fields;
+mod field_lines;
+mod contours;
+mod render;
+mod trails;
+mod ids;
+mod scene;
+mod snapshot;
+mod history;
+mod rewind;
+mod rng;
+mod spawn;
+mod external;
+mod presets;
+mod boundary;
+mod expr;
+mod scene_text;
+mod quadtree;
+mod fmm;
+mod fft;
+mod pm;
+mod backend;
+mod direct;
+mod parallel;
+mod forces;
+mod ode;
+
+pub use fields::{ FieldKind, Polylines };
+pub use field_lines::FieldLineOptions;
+pub use trails::TrailColorMode;
+pub use scene::SceneError;
+pub use boundary::Boundary;
+pub use backend::ForceBackend;
+pub use pm::MeshAssignment;
+pub use forces::{ ForceBuffer, ForceModel, ForceState };
+pub use spawn::{ SpawnOptions, SpawnShape, ValueDistribution, VelocityDistribution };
+use fields::coulomb_field;
+use render::RenderBuffers;
+use trails::TrailStore;
+use history::{ Edit, History };
+use rewind::Timeline;
+use rng::SimRng;
+use quadtree::QuadTree;
+use fmm::Fmm;
+use pm::ParticleMesh;
+use backend::AutoBackend;
+use direct::DirectSum;
+use ids::IdIndex;
+use std::rc::Rc;
+
 // Vec2 for 2D particle motion (y-axis is up)
 #[wasm_bindgen]
 #[derive(Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
@@ -87,20 +137,31 @@ pub struct Trail {
 #[wasm_bindgen]
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct Particle {
+    // Stable identifier assigned by the Universe (0 = not yet added)
+    #[serde(default)]
+    id: u32,
     pub pos: Vec2,
     pub vel: Vec2,
     pub acc: Vec2,
-    trail: Vec<Trail>,
     pub radius: f32,
     pub mass: f64,
     pub color: u32,
     // Charge in Coulombs
     pub charge: f64,
     pub fixed: bool,
+    // Tracers feel every force but are never used as a source (test charges)
+    #[serde(default)]
+    pub tracer: bool,
+    // Per-particle trail overrides (None follows the universe setting)
+    #[serde(default)]
+    trail_length: Option<u32>,
+    #[serde(default)]
+    trail_interval: Option<u32>,
 }
 #[wasm_bindgen]
 impl Particle {
     #[wasm_bindgen(constructor)]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         px: f64,
         py: f64,
@@ -112,6 +173,7 @@ impl Particle {
         charge: f64 // previously drag_coefficient
     ) -> Particle {
         Particle {
+            id: 0,
             pos: Vec2::new(px, py),
             vel: Vec2::new(vx, vy),
             acc: Vec2::new(0.0, 0.0),
@@ -119,13 +181,16 @@ impl Particle {
             mass,
             color,
             charge,
-            trail: vec![],
             fixed: false,
+            tracer: false,
+            trail_length: None,
+            trail_interval: None,
         }
     }
 
     pub fn new_simple(px: f64, py: f64, radius: f32, mass: f64, color: u32) -> Particle {
         Particle {
+            id: 0,
             pos: Vec2::new(px, py),
             vel: Vec2::new(0.0, 0.0),
             acc: Vec2::new(0.0, 0.0),
@@ -133,11 +198,17 @@ impl Particle {
             mass,
             color,
             charge: 0.0, // default charge set by Universe when added simply
-            trail: vec![],
             fixed: false,
+            tracer: false,
+            trail_length: None,
+            trail_interval: None,
         }
     }
 
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
     pub fn get_charge(&self) -> f64 {
         self.charge
     }
@@ -145,17 +216,6 @@ impl Particle {
         self.charge = charge;
     }
 
-    pub fn get_trail(&self) -> Vec<Trail> {
-        self.trail.clone()
-    }
-
-    pub fn add_trail_point(&mut self, pos: Vec2, color: u32, max: usize) {
-        self.trail.push(Trail { pos, color });
-        if self.trail.len() > max {
-            self.trail.remove(0);
-        }
-    }
-
     pub fn get_data(&self) -> JsValue {
         serde_wasm_bindgen::to_value(&self).unwrap()
     }
@@ -166,6 +226,13 @@ impl Particle {
     pub fn set_fixed(&mut self, f: bool) {
         self.fixed = f;
     }
+
+    pub fn is_tracer(&self) -> bool {
+        self.tracer
+    }
+    pub fn set_tracer(&mut self, t: bool) {
+        self.tracer = t;
+    }
 }
 
 #[wasm_bindgen]
@@ -177,126 +244,29 @@ pub enum Implementation {
     Leapfrog,
 }
 
-// Quadtree node used for Barnes-Hut approximation of Coulomb forces
-#[wasm_bindgen]
-#[derive(Serialize, Deserialize, Clone, Default)]
-pub struct QuadTreeNode {
-    charge: f64,
-    center_of_charge: Vec2,
-    dimensions: Vec2,
-    center: Vec2,
-    children: Option<[Box<QuadTreeNode>; 4]>,
-    particles: Vec<Particle>,
-}
-
-#[wasm_bindgen]
-impl QuadTreeNode {
-    fn new(dimensions: Vec2, center: Vec2) -> QuadTreeNode {
-        QuadTreeNode {
-            charge: 0.0,
-            center_of_charge: Vec2::new(0.0, 0.0),
-            dimensions,
-            center,
-            children: None,
-            particles: Vec::with_capacity(2),
-        }
-    }
-
-    fn add_particle(&mut self, particle: &Particle) {
-        if self.children.is_none() {
-            self.particles.push(particle.clone());
-            if self.particles.len() > 1 {
-                self.subdivide();
-            }
-        } else {
-            let quadrant: usize = self.get_quadrant(&Vec2::new(particle.pos.x, particle.pos.y));
-            if let Some(children) = &mut self.children {
-                children[quadrant].add_particle(particle);
-            }
-        }
-    }
-
-    fn subdivide(&mut self) {
-        // no particles -> nothing to subdivide
-        if self.particles.is_empty() {
-            return;
-        }
-        let new_dimensions: Vec2 = Vec2::new(self.dimensions.x / 2.0, self.dimensions.y / 2.0);
-        let child_centers: [Vec2; 4] = [
-            self.center + Vec2::new(new_dimensions.x / 2.0, new_dimensions.y / 2.0),
-            self.center + Vec2::new(-new_dimensions.x / 2.0, new_dimensions.y / 2.0),
-            self.center + Vec2::new(-new_dimensions.x / 2.0, -new_dimensions.y / 2.0),
-            self.center + Vec2::new(new_dimensions.x / 2.0, -new_dimensions.y / 2.0),
-        ];
-        let mut children: [Box<QuadTreeNode>; 4] = Default::default();
-        for i in 0..4 {
-            children[i] = Box::new(QuadTreeNode::new(new_dimensions, child_centers[i]));
-        }
-        for particle in &self.particles {
-            let quadrant: usize = self.get_quadrant(&Vec2::new(particle.pos.x, particle.pos.y));
-            children[quadrant].add_particle(particle);
-        }
-        self.children = Some(children);
-        self.particles.clear();
-    }
-
-    fn get_quadrant(&self, position: &Vec2) -> usize {
-        if position.x >= self.center.x && position.y >= self.center.y {
-            0
-        } else if position.x <= self.center.x && position.y >= self.center.y {
-            1
-        } else if position.x <= self.center.x && position.y <= self.center.y {
-            2
-        } else {
-            3
-        }
-    }
-
-    fn update_center_of_charge(&mut self) {
-        if self.particles.len() == 1 {
-            self.charge = self.particles[0].charge;
-            self.center_of_charge = Vec2::new(self.particles[0].pos.x, self.particles[0].pos.y);
-        } else if let Some(children) = &mut self.children {
-            self.charge = 0.0;
-            self.center_of_charge = Vec2::new(0.0, 0.0);
-            for child in children.iter_mut() {
-                child.update_center_of_charge();
-                self.charge += child.charge;
-                self.center_of_charge += child.center_of_charge * child.charge;
-            }
-            if self.charge != 0.0 {
-                self.center_of_charge /= self.charge;
-            } else {
-                self.center_of_charge = self.center;
-            }
-        } else {
-            self.charge = 0.0;
-            self.center_of_charge = Vec2::new(0.0, 0.0);
-        }
-    }
-
-    fn rebuild(&mut self, particles: &Vec<Particle>, dimensions: Vec2, center: Vec2) {
-        *self = QuadTreeNode::new(dimensions, center);
-        for particle in particles {
-            self.add_particle(particle);
-        }
-        self.update_center_of_charge();
-    }
-
-    pub fn get_data(&self) -> JsValue {
-        serde_wasm_bindgen::to_value(&self).unwrap()
-    }
-}
-
 #[wasm_bindgen]
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Universe {
     particles: Vec<Particle>,
     magnets: Vec<Magnet>,
+    // next stable id handed out to a particle or magnet (shared counter, never reused)
+    #[serde(default = "first_entity_id")]
+    next_id: u32,
+    // id -> index lookup behind the by-ID API, see ids.rs
+    #[serde(skip)]
+    ids: RefCell<IdIndex>,
     // Coulomb constant (k = 8.9875517923e9 N·m^2/C^2)
     coulomb_constant: f64,
     air_density: f64, // repurposed: default charge
     show_trails: bool,
+    #[serde(default = "default_trail_length")]
+    trail_length: usize,
+    #[serde(default = "default_trail_interval")]
+    trail_interval: u32,
+    #[serde(default)]
+    trail_color_mode: TrailColorMode,
+    #[serde(skip)]
+    trails: TrailStore,
     is_paused: bool,
     implementation: Implementation,
     speed: f64,
@@ -307,16 +277,96 @@ pub struct Universe {
     spawn_range: f64, // configurable spawn range for new particles (±units)
     wind_x: f64,
     wind_y: f64,
+    // uniform applied fields (E in the plane, B out of it) and linear drag
+    #[serde(default)]
+    external_field_x: f64,
+    #[serde(default)]
+    external_field_y: f64,
+    #[serde(default)]
+    magnetic_field_z: f64,
+    #[serde(default)]
+    damping: f64,
+    // edges of the simulation box
+    #[serde(default)]
+    boundary: Boundary,
+    #[serde(default = "boundary::default_boundary_size")]
+    boundary_width: f64,
+    #[serde(default = "boundary::default_boundary_size")]
+    boundary_height: f64,
     // Minimum interaction distance to avoid Coulomb singularities
     min_interaction_distance: f64,
+    // how Coulomb forces between particles are summed
+    #[serde(default)]
+    force_backend: ForceBackend,
+    // source buffers reused by every direct-sum evaluation
+    #[serde(skip)]
+    direct: RefCell<DirectSum>,
+    // force models registered from Rust, evaluated after the built-in ones
+    #[serde(skip)]
+    force_models: Vec<Rc<dyn ForceModel>>,
     // quadtree options
-    use_quadtree: bool,
     quadtree_theta: f64,
-    quadtree: QuadTreeNode,
+    // tree buffers reused by every Barnes-Hut evaluation, never saved
+    #[serde(skip)]
+    quadtree: RefCell<QuadTree>,
+    // particle counts where Auto moves to the quadtree and then to the FMM
     quadtree_threshold: usize,
+    #[serde(default = "backend::default_fmm_threshold")]
+    fmm_threshold: usize,
+    // let Auto choose by measured step cost instead of particle count
+    #[serde(default)]
+    auto_backend_timing: bool,
+    #[serde(skip)]
+    auto_backend: AutoBackend,
+    // FMM expansion order and the buffers reused by every evaluation
+    #[serde(default = "fmm::default_fmm_order")]
+    fmm_order: usize,
+    #[serde(skip)]
+    fmm: RefCell<Fmm>,
+    // particle-mesh options and the mesh reused by every evaluation
+    #[serde(default = "pm::default_mesh_size")]
+    mesh_size: usize,
+    #[serde(default)]
+    mesh_assignment: MeshAssignment,
+    #[serde(default)]
+    mesh_short_range: bool,
+    #[serde(skip)]
+    mesh: RefCell<ParticleMesh>,
     // collisions
     collisions_enabled: bool,
     restitution: f64, // coefficient of restitution (0..1)
+    // typed-array views for the renderer, refreshed every time_step
+    #[serde(skip)]
+    render: RenderBuffers,
+    // undo/redo stack of scene edits
+    #[serde(skip)]
+    history: History,
+    // simulation time and the rewind keyframes recorded along it
+    #[serde(default)]
+    time: f64,
+    #[serde(skip)]
+    timeline: Timeline,
+    // every random decision draws from this, so a scene plus a seed replays exactly
+    #[serde(default)]
+    rng: SimRng,
+}
+
+fn first_entity_id() -> u32 {
+    1
+}
+
+fn default_trail_length() -> usize {
+    250
+}
+
+fn default_trail_interval() -> u32 {
+    1
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Universe::new()
+    }
 }
 
 #[wasm_bindgen]
@@ -331,44 +381,76 @@ impl Universe {
     }
 
     fn new_with_defaults(with_defaults: bool) -> Universe {
-        let particles = if with_defaults {
-            // sample charge setup: positive/negative pair and a third neutral-ish
-            let p1 = Particle::new(-200.0, 0.0, 10.0, 1.0, 0xff0000, 0.0, 0.0, 20.0); // positive
-            let p2 = Particle::new(200.0, 0.0, 10.0, 1.0, 0xff0000, 0.0, 0.0, 20.0); // positive
-            let p3 = Particle::new(0.0, 200.0, 10.0, 0.8, 0x0000ff, 0.0, 0.0, -20.0); // negative
-            let p4 = Particle::new(0.0, -200.0, 10.0, 0.8, 0x0000ff, 0.0, 0.0, -20.0); // negative
-            vec![p1, p2, p3, p4]
-        } else {
-            vec![]
-        };
-
-        Universe {
-            particles,
+        let mut universe = Universe {
+            particles: vec![],
             magnets: vec![],
+            next_id: 1,
+            ids: RefCell::default(),
             coulomb_constant: 8.9875517923e3, // Coulomb constant (N·m²/C²)
             air_density: 1.0, // default charge
             wind_x: 0.0,
             wind_y: 0.0,
+            external_field_x: 0.0,
+            external_field_y: 0.0,
+            magnetic_field_z: 0.0,
+            damping: 0.0,
+            boundary: Boundary::Open,
+            boundary_width: boundary::default_boundary_size(),
+            boundary_height: boundary::default_boundary_size(),
             implementation: Implementation::Euler,
             speed: 1.0,
             show_trails: true,
+            trail_length: default_trail_length(),
+            trail_interval: default_trail_interval(),
+            trail_color_mode: TrailColorMode::Particle,
+            trails: TrailStore::default(),
             is_paused: false,
             default_mass: 1.0,
             mass_calculation: true,
             default_charge: 1.0, // default charge when adding simple
             spawn_range: 200.0, // default spawn spread in simulation units
             min_interaction_distance: 10.0, // default minimum distance (simulation units)
-            use_quadtree: false,
+            force_backend: ForceBackend::Auto,
+            direct: RefCell::default(),
+            force_models: Vec::new(),
             quadtree_theta: 0.5,
-            quadtree: QuadTreeNode::new(Vec2::new(2000.0, 2000.0), Vec2::new(0.0, 0.0)),
+            quadtree: RefCell::default(),
             quadtree_threshold: 150,
+            fmm_threshold: backend::default_fmm_threshold(),
+            auto_backend_timing: false,
+            auto_backend: AutoBackend::default(),
+            fmm_order: fmm::default_fmm_order(),
+            fmm: RefCell::default(),
+            mesh_size: pm::default_mesh_size(),
+            mesh_assignment: MeshAssignment::Tsc,
+            mesh_short_range: false,
+            mesh: RefCell::default(),
             collisions_enabled: false,
             restitution: 1.0,
+            render: RenderBuffers::default(),
+            history: History::default(),
+            time: 0.0,
+            timeline: Timeline::default(),
+            rng: SimRng::from_entropy(),
+        };
+
+        if with_defaults {
+            presets::add_quadrupole_charges(&mut universe);
         }
+
+        universe
     }
 
     pub fn time_step(&mut self, dt: f64) -> u8 {
-        if self.particles.is_empty() || self.is_paused {
+        let result = self.advance_recorded(dt);
+        self.refresh_render_buffers();
+        result
+    }
+
+    fn advance(&mut self, dt: f64) -> u8 {
+        // a step in between ends any gesture, so the next edit starts a new undo step
+        self.commit_edit();
+        if (self.particles.is_empty() && self.magnets.is_empty()) || self.is_paused {
             return 1;
         }
 
@@ -377,583 +459,71 @@ impl Universe {
         let sub_dt = (dt * speed_multiplier) / (steps as f64);
 
         for _ in 0..steps {
+            let timing = self.start_backend_timing();
             let result = self.single_physics_step(sub_dt);
+            self.finish_backend_timing(timing);
             if result != 0 {
                 return result;
             }
         }
+        self.time += dt * speed_multiplier;
 
         if self.show_trails {
-            for p in &mut self.particles {
-                p.add_trail_point(p.pos, p.color, 250);
-            }
+            self.record_trails();
         }
 
-        return 0;
+        0
     }
 
+    // Advances particles and magnets together with the selected integrator, see ode.rs
     fn single_physics_step(&mut self, dt: f64) -> u8 {
-        match self.implementation {
-            Implementation::Euler => {
-                let accelerations = if self.use_quadtree {
-                    self.calculate_electrostatic_accelerations_quadtree()
-                } else {
-                    self.calculate_electrostatic_accelerations()
-                };
-
-                if accelerations.iter().any(|acc| (acc.x.is_nan() || acc.y.is_nan())) {
-                    return 1;
-                }
-
-                for i in 0..self.particles.len() {
-                    if !self.particles[i].fixed {
-                        self.particles[i].vel.x += accelerations[i].x * dt;
-                        self.particles[i].vel.y += accelerations[i].y * dt;
-                        self.particles[i].pos.x += self.particles[i].vel.x * dt;
-                        self.particles[i].pos.y += self.particles[i].vel.y * dt;
-                    }
-                    self.particles[i].acc = accelerations[i];
-                }
-
-                // Update magnets (simple explicit integration per substep)
-                let magnet_accs = self.calculate_magnet_accelerations();
-                if magnet_accs.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) {
-                    return 1;
-                }
-                for mi in 0..self.magnets.len() {
-                    if !self.magnets[mi].fixed {
-                        self.magnets[mi].vel.x += magnet_accs[mi].x * dt;
-                        self.magnets[mi].vel.y += magnet_accs[mi].y * dt;
-                        self.magnets[mi].pos.x += self.magnets[mi].vel.x * dt;
-                        self.magnets[mi].pos.y += self.magnets[mi].vel.y * dt;
-                    }
-                    self.magnets[mi].acc = magnet_accs[mi];
-                }
-
-                if self.collisions_enabled {
-                    self.handle_collisions();
-                }
-            }
-            Implementation::RK4 => {
-                // 2D RK4 implementation
-                let initial_positions: Vec<Vec2> = self.particles
-                    .iter()
-                    .map(|p| p.pos)
-                    .collect();
-                let initial_velocities: Vec<Vec2> = self.particles
-                    .iter()
-                    .map(|p| p.vel)
-                    .collect();
-
-                let k1_acc = self.calculate_electrostatic_accelerations_for_state(
-                    &initial_positions,
-                    &initial_velocities
-                );
-                let k1_vel = initial_velocities.clone();
-
-                let mut k2_positions = Vec::with_capacity(self.particles.len());
-                let mut k2_velocities = Vec::with_capacity(self.particles.len());
-                for i in 0..self.particles.len() {
-                    k2_positions.push(initial_positions[i] + k1_vel[i] * (dt * 0.5));
-                    k2_velocities.push(initial_velocities[i] + k1_acc[i] * (dt * 0.5));
-                }
-                let k2_acc = self.calculate_electrostatic_accelerations_for_state(
-                    &k2_positions,
-                    &k2_velocities
-                );
-
-                let mut k3_positions = Vec::with_capacity(self.particles.len());
-                let mut k3_velocities = Vec::with_capacity(self.particles.len());
-                for i in 0..self.particles.len() {
-                    k3_positions.push(initial_positions[i] + k2_velocities[i] * (dt * 0.5));
-                    k3_velocities.push(initial_velocities[i] + k2_acc[i] * (dt * 0.5));
-                }
-                let k3_acc = self.calculate_electrostatic_accelerations_for_state(
-                    &k3_positions,
-                    &k3_velocities
-                );
-
-                let mut k4_positions = Vec::with_capacity(self.particles.len());
-                let mut k4_velocities = Vec::with_capacity(self.particles.len());
-                for i in 0..self.particles.len() {
-                    k4_positions.push(initial_positions[i] + k3_velocities[i] * dt);
-                    k4_velocities.push(initial_velocities[i] + k3_acc[i] * dt);
-                }
-                let k4_acc = self.calculate_electrostatic_accelerations_for_state(
-                    &k4_positions,
-                    &k4_velocities
-                );
-
-                // NaN check
-                if
-                    k1_acc.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) ||
-                    k2_acc.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) ||
-                    k3_acc.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) ||
-                    k4_acc.iter().any(|a| (a.x.is_nan() || a.y.is_nan()))
-                {
-                    return 1;
-                }
-
-                for i in 0..self.particles.len() {
-                    self.particles[i].pos.x +=
-                        ((k1_vel[i].x +
-                            2.0 * k2_velocities[i].x +
-                            2.0 * k3_velocities[i].x +
-                            k4_velocities[i].x) *
-                            dt) /
-                        6.0;
-                    self.particles[i].pos.y +=
-                        ((k1_vel[i].y +
-                            2.0 * k2_velocities[i].y +
-                            2.0 * k3_velocities[i].y +
-                            k4_velocities[i].y) *
-                            dt) /
-                        6.0;
-
-                    if !self.particles[i].fixed {
-                        self.particles[i].vel.x +=
-                            ((k1_acc[i].x + 2.0 * k2_acc[i].x + 2.0 * k3_acc[i].x + k4_acc[i].x) *
-                                dt) /
-                            6.0;
-                        self.particles[i].vel.y +=
-                            ((k1_acc[i].y + 2.0 * k2_acc[i].y + 2.0 * k3_acc[i].y + k4_acc[i].y) *
-                                dt) /
-                            6.0;
-                    }
-
-                    self.particles[i].acc = k1_acc[i];
-                }
-
-                // Integrate magnets (after RK4 particles update)
-                let magnet_accs = self.calculate_magnet_accelerations();
-                if magnet_accs.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) {
-                    return 1;
-                }
-                for mi in 0..self.magnets.len() {
-                    if !self.magnets[mi].fixed {
-                        self.magnets[mi].vel.x += magnet_accs[mi].x * dt;
-                        self.magnets[mi].vel.y += magnet_accs[mi].y * dt;
-                        self.magnets[mi].pos.x += self.magnets[mi].vel.x * dt;
-                        self.magnets[mi].pos.y += self.magnets[mi].vel.y * dt;
-                    }
-                    self.magnets[mi].acc = magnet_accs[mi];
-                }
-
-                if self.collisions_enabled {
-                    self.handle_collisions();
-                }
-            }
-            Implementation::Verlet => {
-                let accelerations = self.calculate_electrostatic_accelerations();
-                if accelerations.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) {
-                    return 1;
-                }
-
-                let mut new_positions = Vec::with_capacity(self.particles.len());
-                for i in 0..self.particles.len() {
-                    new_positions.push(
-                        Vec2::new(
-                            self.particles[i].pos.x +
-                                self.particles[i].vel.x * dt +
-                                0.5 * accelerations[i].x * dt * dt,
-                            self.particles[i].pos.y +
-                                self.particles[i].vel.y * dt +
-                                0.5 * accelerations[i].y * dt * dt
-                        )
-                    );
-                }
-
-                let new_acc = self.calculate_electrostatic_accelerations_for_state(
-                    &new_positions,
-                    &self.particles
-                        .iter()
-                        .map(|p| p.vel)
-                        .collect::<Vec<_>>()
-                );
-                if new_acc.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) {
-                    return 1;
-                }
-
-                for i in 0..self.particles.len() {
-                    if !self.particles[i].fixed {
-                        self.particles[i].vel.x += 0.5 * (accelerations[i].x + new_acc[i].x) * dt;
-                        self.particles[i].vel.y += 0.5 * (accelerations[i].y + new_acc[i].y) * dt;
-                        self.particles[i].pos = new_positions[i];
-                    }
-                    self.particles[i].acc = new_acc[i];
-                }
-
-                // Update magnets (Verlet integration: explicit update per substep)
-                let magnet_accs = self.calculate_magnet_accelerations();
-                if magnet_accs.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) {
-                    return 1;
-                }
-                for mi in 0..self.magnets.len() {
-                    if !self.magnets[mi].fixed {
-                        self.magnets[mi].vel.x += magnet_accs[mi].x * dt;
-                        self.magnets[mi].vel.y += magnet_accs[mi].y * dt;
-                        self.magnets[mi].pos.x += self.magnets[mi].vel.x * dt;
-                        self.magnets[mi].pos.y += self.magnets[mi].vel.y * dt;
-                    }
-                    self.magnets[mi].acc = magnet_accs[mi];
-                }
-
-                if self.collisions_enabled {
-                    self.handle_collisions();
-                }
-            }
-            Implementation::Leapfrog => {
-                let accelerations = self.calculate_electrostatic_accelerations();
-                if accelerations.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) {
-                    return 1;
-                }
-
-                let mut velocities_half = Vec::with_capacity(self.particles.len());
-                for i in 0..self.particles.len() {
-                    velocities_half.push(
-                        Vec2::new(
-                            self.particles[i].vel.x + accelerations[i].x * (dt / 2.0),
-                            self.particles[i].vel.y + accelerations[i].y * (dt / 2.0)
-                        )
-                    );
-                }
-
-                let mut new_positions = Vec::with_capacity(self.particles.len());
-                for i in 0..self.particles.len() {
-                    new_positions.push(
-                        Vec2::new(
-                            self.particles[i].pos.x + velocities_half[i].x * dt,
-                            self.particles[i].pos.y + velocities_half[i].y * dt
-                        )
-                    );
-                }
-
-                let new_acc = self.calculate_electrostatic_accelerations_for_state(
-                    &new_positions,
-                    &velocities_half
-                );
-                if new_acc.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) {
-                    return 1;
-                }
-
-                for i in 0..self.particles.len() {
-                    if !self.particles[i].fixed {
-                        self.particles[i].pos = new_positions[i];
-                        self.particles[i].vel.x = velocities_half[i].x + new_acc[i].x * (dt / 2.0);
-                        self.particles[i].vel.y = velocities_half[i].y + new_acc[i].y * (dt / 2.0);
-                    }
-                    self.particles[i].acc = new_acc[i];
-                }
-
-                // Update magnets (Leapfrog - explicit per substep)
-                let magnet_accs = self.calculate_magnet_accelerations();
-                if magnet_accs.iter().any(|a| (a.x.is_nan() || a.y.is_nan())) {
-                    return 1;
-                }
-                for mi in 0..self.magnets.len() {
-                    if !self.magnets[mi].fixed {
-                        self.magnets[mi].vel.x += magnet_accs[mi].x * dt;
-                        self.magnets[mi].vel.y += magnet_accs[mi].y * dt;
-                        self.magnets[mi].pos.x += self.magnets[mi].vel.x * dt;
-                        self.magnets[mi].pos.y += self.magnets[mi].vel.y * dt;
-                    }
-                    self.magnets[mi].acc = magnet_accs[mi];
-                }
+        let mut state = self.system_state();
+        let start = state.clone();
+        let shown = ode::integrate(self.implementation, &mut state, dt, |q, v| {
+            self.system_accelerations(&start, q, v)
+        });
+        let Some(shown) = shown else {
+            return 1;
+        };
+        self.set_system_state(&state, &shown);
 
-                if self.collisions_enabled {
-                    self.handle_collisions();
-                }
-            }
+        if self.collisions_enabled {
+            self.handle_collisions();
         }
-
+        self.apply_boundary();
         0
     }
 
-    // Naive O(n^2) Coulomb acceleration calculation (2D)
-    fn calculate_electrostatic_accelerations(&self) -> Vec<Vec2> {
-        let n = self.particles.len();
-        let mut accelerations = vec![Vec2::new(0.0, 0.0); n];
-
-        for i in 0..n {
-            let qi = self.particles[i].charge; // charge
-            let mi = if self.mass_calculation { self.particles[i].mass } else { self.default_mass };
-            for j in 0..n {
-                if i == j {
-                    continue;
-                }
-                let qj = self.particles[j].charge;
-
-                let rx = self.particles[i].pos.x - self.particles[j].pos.x; // r = r_i - r_j
-                let ry = self.particles[i].pos.y - self.particles[j].pos.y;
-                let dist_sq = rx * rx + ry * ry;
-                // Softening: enforce a minimum interaction distance to avoid singularities
-                let min_dist = self.min_interaction_distance; // use configured softening distance
-                let min_dist_sq = min_dist * min_dist;
-                let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                let dist = f64::sqrt(safe_dist_sq);
-
-                if dist > 1e-8 {
-                    // Coulomb: F = k * qi * qj / r^2 * r_hat (where r_hat points from j->i)
-                    let force_magnitude = (self.coulomb_constant * qi * qj) / safe_dist_sq;
-                    let acc_magnitude = force_magnitude / mi;
-                    accelerations[i].x += acc_magnitude * (rx / dist);
-                    accelerations[i].y += acc_magnitude * (ry / dist);
-                }
-            }
-
-            // Magnet dipole contributions: treat each magnet as two poles (+/- strength/2)
-            for m in &self.magnets {
-                let half = (m.size as f64) * 0.5;
-                let ux = f64::cos(m.angle);
-                let uy = f64::sin(m.angle);
-                let north = Vec2::new(m.pos.x + ux * half, m.pos.y + uy * half);
-                let south = Vec2::new(m.pos.x - ux * half, m.pos.y - uy * half);
-                let pole_n = m.strength * 0.5; // north pole pseudo-charge
-                let pole_s = -m.strength * 0.5; // south pole pseudo-charge
-
-                // north pole
-                {
-                    let rx = self.particles[i].pos.x - north.x;
-                    let ry = self.particles[i].pos.y - north.y;
-                    let dist_sq = rx * rx + ry * ry;
-                    let min_dist = self.min_interaction_distance;
-                    let min_dist_sq = min_dist * min_dist;
-                    let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                    let dist = f64::sqrt(safe_dist_sq);
-                    if dist > 1e-8 {
-                        let force_magnitude = (self.coulomb_constant * qi * pole_n) / safe_dist_sq;
-                        let acc_magnitude = force_magnitude / mi;
-                        accelerations[i].x += acc_magnitude * (rx / dist);
-                        accelerations[i].y += acc_magnitude * (ry / dist);
-                    }
-                }
-
-                // south pole
-                {
-                    let rx = self.particles[i].pos.x - south.x;
-                    let ry = self.particles[i].pos.y - south.y;
-                    let dist_sq = rx * rx + ry * ry;
-                    let min_dist = self.min_interaction_distance;
-                    let min_dist_sq = min_dist * min_dist;
-                    let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                    let dist = f64::sqrt(safe_dist_sq);
-                    if dist > 1e-8 {
-                        let force_magnitude = (self.coulomb_constant * qi * pole_s) / safe_dist_sq;
-                        let acc_magnitude = force_magnitude / mi;
-                        accelerations[i].x += acc_magnitude * (rx / dist);
-                        accelerations[i].y += acc_magnitude * (ry / dist);
-                    }
-                }
-            }
-        }
-
-        accelerations
-    }
-
-    fn calculate_electrostatic_accelerations_for_state(
-        &self,
-        positions: &[Vec2],
-        _velocities: &[Vec2]
-    ) -> Vec<Vec2> {
-        let n = positions.len();
-        let mut accelerations = vec![Vec2::new(0.0, 0.0); n];
-
-        for i in 0..n {
-            let qi = self.particles[i].charge;
-            let mi = if self.mass_calculation { self.particles[i].mass } else { self.default_mass };
-            for j in 0..n {
-                if i == j {
-                    continue;
-                }
-                let qj = self.particles[j].charge;
-                let rx = positions[i].x - positions[j].x;
-                let ry = positions[i].y - positions[j].y;
-                let dist_sq = rx * rx + ry * ry;
-                // Softening to avoid singularities in intermediate state calculations
-                let min_dist = self.min_interaction_distance; // use configured softening distance
-                let min_dist_sq = min_dist * min_dist;
-                let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                let dist = f64::sqrt(safe_dist_sq);
-                if dist > 1e-8 {
-                    let force_magnitude = (self.coulomb_constant * qi * qj) / safe_dist_sq;
-                    let acc_magnitude = force_magnitude / mi;
-                    accelerations[i].x += acc_magnitude * (rx / dist);
-                    accelerations[i].y += acc_magnitude * (ry / dist);
-                }
-            }
-
-            // Include magnet dipole contributions using magnet positions from self
-            for m in &self.magnets {
-                let half = (m.size as f64) * 0.5;
-                let ux = f64::cos(m.angle);
-                let uy = f64::sin(m.angle);
-                let north = Vec2::new(m.pos.x + ux * half, m.pos.y + uy * half);
-                let south = Vec2::new(m.pos.x - ux * half, m.pos.y - uy * half);
-                let pole_n = m.strength * 0.5;
-                let pole_s = -m.strength * 0.5;
-
-                // North pole contribution
-                {
-                    let rx = positions[i].x - north.x;
-                    let ry = positions[i].y - north.y;
-                    let dist_sq = rx * rx + ry * ry;
-                    let min_dist = self.min_interaction_distance;
-                    let min_dist_sq = min_dist * min_dist;
-                    let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                    let dist = f64::sqrt(safe_dist_sq);
-                    if dist > 1e-8 {
-                        let force_magnitude = (self.coulomb_constant * qi * pole_n) / safe_dist_sq;
-                        let acc_magnitude = force_magnitude / mi;
-                        accelerations[i].x += acc_magnitude * (rx / dist);
-                        accelerations[i].y += acc_magnitude * (ry / dist);
-                    }
-                }
-
-                // South pole contribution
-                {
-                    let rx = positions[i].x - south.x;
-                    let ry = positions[i].y - south.y;
-                    let dist_sq = rx * rx + ry * ry;
-                    let min_dist = self.min_interaction_distance;
-                    let min_dist_sq = min_dist * min_dist;
-                    let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                    let dist = f64::sqrt(safe_dist_sq);
-                    if dist > 1e-8 {
-                        let force_magnitude = (self.coulomb_constant * qi * pole_s) / safe_dist_sq;
-                        let acc_magnitude = force_magnitude / mi;
-                        accelerations[i].x += acc_magnitude * (rx / dist);
-                        accelerations[i].y += acc_magnitude * (ry / dist);
-                    }
-                }
+    // Exact Coulomb acceleration of particle i from the given source particles, summed on its
+    // own; the direct backend evaluates all particles at once in direct.rs
+    fn direct_acceleration(&self, i: usize, positions: &[Vec2], sources: &[usize]) -> Vec2 {
+        let mut field = Vec2::new(0.0, 0.0);
+        for &j in sources {
+            if i == j {
+                continue;
             }
+            // Coulomb: F = k * qi * qj / r^2 * r_hat (where r_hat points from j->i)
+            field += coulomb_field(
+                self.coulomb_constant,
+                self.particles[j].charge,
+                positions[i] - positions[j],
+                self.min_interaction_distance
+            );
         }
-
-        accelerations
+        field * (self.particles[i].charge / self.effective_mass(i))
     }
 
-    // Compute accelerations on magnets due to particles and other magnets (dipole approximation)
-    fn calculate_magnet_accelerations(&self) -> Vec<Vec2> {
-        let mcount = self.magnets.len();
-        let mut accs = vec![Vec2::new(0.0, 0.0); mcount];
-        if mcount == 0 {
-            return accs;
-        }
-
-        // Precompute particle charges/positions
-        for i in 0..mcount {
-            let m = &self.magnets[i];
-            let half = (m.size as f64) * 0.5;
-            let ux = f64::cos(m.angle);
-            let uy = f64::sin(m.angle);
-            let north = Vec2::new(m.pos.x + ux * half, m.pos.y + uy * half);
-            let south = Vec2::new(m.pos.x - ux * half, m.pos.y - uy * half);
-            let pole_i_n = m.strength * 0.5;
-            let pole_i_s = -m.strength * 0.5;
-
-            // Contribution from particles
-            for p in &self.particles {
-                let qi = p.charge;
-                // force on north pole due to particle
-                let rxn = north.x - p.pos.x;
-                let ryn = north.y - p.pos.y;
-                let dist_sq_n = rxn * rxn + ryn * ryn;
-                let min_dist = self.min_interaction_distance;
-                let min_dist_sq = min_dist * min_dist;
-                let safe_dist_sq_n = if dist_sq_n < min_dist_sq { min_dist_sq } else { dist_sq_n };
-                let dist_n = f64::sqrt(safe_dist_sq_n);
-                if dist_n > 1e-8 {
-                    let force_mag_n = (self.coulomb_constant * qi * pole_i_n) / safe_dist_sq_n;
-                    accs[i].x += (force_mag_n * (rxn / dist_n)) / m.mass;
-                    accs[i].y += (force_mag_n * (ryn / dist_n)) / m.mass;
-                }
-
-                // force on south pole due to particle
-                let rxs = south.x - p.pos.x;
-                let rys = south.y - p.pos.y;
-                let dist_sq_s = rxs * rxs + rys * rys;
-                let safe_dist_sq_s = if dist_sq_s < min_dist_sq { min_dist_sq } else { dist_sq_s };
-                let dist_s = f64::sqrt(safe_dist_sq_s);
-                if dist_s > 1e-8 {
-                    let force_mag_s = (self.coulomb_constant * qi * pole_i_s) / safe_dist_sq_s;
-                    accs[i].x += (force_mag_s * (rxs / dist_s)) / m.mass;
-                    accs[i].y += (force_mag_s * (rys / dist_s)) / m.mass;
-                }
-            }
-
-            // Contribution from other magnets (pole-pole interactions)
-            for j in 0..mcount {
-                if i == j {
-                    continue;
-                }
-                let other = &self.magnets[j];
-                let half_o = (other.size as f64) * 0.5;
-                let ux_o = f64::cos(other.angle);
-                let uy_o = f64::sin(other.angle);
-                let north_o = Vec2::new(other.pos.x + ux_o * half_o, other.pos.y + uy_o * half_o);
-                let south_o = Vec2::new(other.pos.x - ux_o * half_o, other.pos.y - uy_o * half_o);
-                let pole_j_n = other.strength * 0.5;
-                let pole_j_s = -other.strength * 0.5;
-
-                // i.north with j.north
-                let rx = north.x - north_o.x;
-                let ry = north.y - north_o.y;
-                let dist_sq = rx * rx + ry * ry;
-                let min_dist = self.min_interaction_distance;
-                let min_dist_sq = min_dist * min_dist;
-                let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                let dist = f64::sqrt(safe_dist_sq);
-                if dist > 1e-8 {
-                    let force_mag = (self.coulomb_constant * pole_i_n * pole_j_n) / safe_dist_sq;
-                    accs[i].x += (force_mag * (rx / dist)) / m.mass;
-                    accs[i].y += (force_mag * (ry / dist)) / m.mass;
-                }
-
-                // i.north with j.south
-                let rx = north.x - south_o.x;
-                let ry = north.y - south_o.y;
-                let dist_sq = rx * rx + ry * ry;
-                let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                let dist = f64::sqrt(safe_dist_sq);
-                if dist > 1e-8 {
-                    let force_mag = (self.coulomb_constant * pole_i_n * pole_j_s) / safe_dist_sq;
-                    accs[i].x += (force_mag * (rx / dist)) / m.mass;
-                    accs[i].y += (force_mag * (ry / dist)) / m.mass;
-                }
-
-                // i.south with j.north
-                let rx = south.x - north_o.x;
-                let ry = south.y - north_o.y;
-                let dist_sq = rx * rx + ry * ry;
-                let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                let dist = f64::sqrt(safe_dist_sq);
-                if dist > 1e-8 {
-                    let force_mag = (self.coulomb_constant * pole_i_s * pole_j_n) / safe_dist_sq;
-                    accs[i].x += (force_mag * (rx / dist)) / m.mass;
-                    accs[i].y += (force_mag * (ry / dist)) / m.mass;
-                }
-
-                // i.south with j.south
-                let rx = south.x - south_o.x;
-                let ry = south.y - south_o.y;
-                let dist_sq = rx * rx + ry * ry;
-                let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                let dist = f64::sqrt(safe_dist_sq);
-                if dist > 1e-8 {
-                    let force_mag = (self.coulomb_constant * pole_i_s * pole_j_s) / safe_dist_sq;
-                    accs[i].x += (force_mag * (rx / dist)) / m.mass;
-                    accs[i].y += (force_mag * (ry / dist)) / m.mass;
-                }
-            }
-        }
-
-        accs
-    }
-
-    // Collision handling (elastic collisions)
+    // Collision handling (elastic collisions); tracers pass through everything
     fn handle_collisions(&mut self) {
         let n = self.particles.len();
         for i in 0..n {
+            if self.particles[i].tracer {
+                continue;
+            }
             for j in i + 1..n {
+                if self.particles[j].tracer {
+                    continue;
+                }
                 let dx = self.particles[i].pos.x - self.particles[j].pos.x;
                 let dy = self.particles[i].pos.y - self.particles[j].pos.y;
                 let dist = f64::sqrt(dx * dx + dy * dy);
@@ -1014,11 +584,13 @@ impl Universe {
         }
     }
 
+    // Undoable: the previous state comes back with undo()
     pub fn reset(&mut self) {
-        *self = Universe::new();
+        self.replace_undoable(Universe::new());
     }
 
     // Magnet addition/removal and accessors
+    #[allow(clippy::too_many_arguments)]
     pub fn add_magnet(
         &mut self,
         px: f64,
@@ -1031,7 +603,7 @@ impl Universe {
         color_south: u32,
         strength: f64,
         fixed: bool
-    ) {
+    ) -> u32 {
         let m = Magnet::new(
             px,
             py,
@@ -1044,22 +616,25 @@ impl Universe {
             strength,
             fixed
         );
-        self.magnets.push(m);
+        self.insert_magnet_undoable(m)
     }
 
-    pub fn add_magnet_simple(&mut self, px: f64, py: f64, strength: f64) {
+    pub fn add_magnet_simple(&mut self, px: f64, py: f64, strength: f64) -> u32 {
         let m = Magnet::new_simple(px, py, strength);
-        self.magnets.push(m);
+        self.insert_magnet_undoable(m)
     }
 
     // Magnet helpers (reintroduced)
     pub fn pop_magnet(&mut self) {
-        self.magnets.pop();
+        if !self.magnets.is_empty() {
+            self.remove_magnet(self.magnets.len() - 1);
+        }
     }
 
     pub fn remove_magnet(&mut self, index: usize) {
         if index < self.magnets.len() {
-            self.magnets.remove(index);
+            let magnet = Box::new(self.take_magnet(index));
+            self.record(Edit::RemoveMagnet { index, magnet });
         }
     }
 
@@ -1076,66 +651,73 @@ impl Universe {
     }
 
     pub fn update_magnet_position(&mut self, index: usize, x: f64, y: f64) {
-        if index < self.magnets.len() {
-            self.magnets[index].pos = Vec2::new(x, y);
-        }
+        self.edit_magnet(index, |m| {
+            m.pos = Vec2::new(x, y);
+        });
     }
 
     pub fn update_magnet_velocity(&mut self, index: usize, vx: f64, vy: f64) {
-        if index < self.magnets.len() {
-            self.magnets[index].vel = Vec2::new(vx, vy);
-        }
+        self.edit_magnet(index, |m| {
+            m.vel = Vec2::new(vx, vy);
+        });
     }
 
     pub fn update_magnet_mass(&mut self, index: usize, mass: f64) {
-        if index < self.magnets.len() {
-            self.magnets[index].mass = mass;
-        }
+        self.edit_magnet(index, |m| {
+            m.mass = mass;
+        });
     }
 
     pub fn update_magnet_color_north(&mut self, index: usize, color: u32) {
-        if index < self.magnets.len() {
-            self.magnets[index].color_north = color;
-        }
+        self.edit_magnet(index, |m| {
+            m.color_north = color;
+        });
     }
 
     pub fn update_magnet_color_south(&mut self, index: usize, color: u32) {
-        if index < self.magnets.len() {
-            self.magnets[index].color_south = color;
-        }
+        self.edit_magnet(index, |m| {
+            m.color_south = color;
+        });
     }
 
     pub fn update_magnet_size(&mut self, index: usize, size: f32) {
-        if index < self.magnets.len() {
-            self.magnets[index].size = size;
-        }
+        self.edit_magnet(index, |m| {
+            m.size = size;
+        });
     }
 
     pub fn update_magnet_thickness(&mut self, index: usize, thickness: f32) {
-        if index < self.magnets.len() {
-            self.magnets[index].thickness = thickness;
-        }
+        self.edit_magnet(index, |m| {
+            m.thickness = thickness;
+        });
     }
 
     pub fn update_magnet_strength(&mut self, index: usize, strength: f64) {
-        if index < self.magnets.len() {
-            self.magnets[index].strength = strength;
-        }
+        self.edit_magnet(index, |m| {
+            m.strength = strength;
+        });
     }
 
     pub fn update_magnet_fixed(&mut self, index: usize, fixed: bool) {
-        if index < self.magnets.len() {
-            self.magnets[index].fixed = fixed;
-        }
+        self.edit_magnet(index, |m| {
+            m.fixed = fixed;
+        });
     }
 
     pub fn update_magnet_angle(&mut self, index: usize, angle: f64) {
-        if index < self.magnets.len() {
-            self.magnets[index].angle = angle;
-        }
+        self.edit_magnet(index, |m| {
+            m.angle = angle;
+        });
+    }
+
+    pub fn update_magnet_spin(&mut self, index: usize, spin: f64) {
+        self.edit_magnet(index, |m| {
+            m.spin = spin;
+        });
     }
 
     // Particle addition/removal and accessors
+    #[allow(clippy::too_many_arguments)]
     pub fn add_particle(
         &mut self,
         px: f64,
@@ -1146,7 +728,7 @@ impl Universe {
         mass: f64,
         color: u32,
         charge: f64
-    ) {
+    ) -> u32 {
         let mut particle = Particle::new(px, py, radius, mass, color, vx, vy, charge);
         // If default charge was set in default_charge, use it when the provided charge is 0
         if particle.charge == 0.0 {
@@ -1160,41 +742,65 @@ impl Universe {
             } else if particle.charge > 0.0 {
                 0xff0000
             } else {
-                Self::random_color()
+                self.random_color()
             };
         }
 
-        self.particles.push(particle);
+        self.insert_particle_undoable(particle)
     }
 
-    pub fn random_color() -> u32 {
+    pub fn random_color(&mut self) -> u32 {
         let colors = [0xff0000, 0x0000ff, 0x00ff00, 0xf0f000, 0x00f0f0, 0xf000f0];
-        let idx = rand::thread_rng().gen_range(0..colors.len());
-        colors[idx]
+        colors[self.random_index(colors.len())]
     }
 
-    pub fn add_particle_simple(&mut self, px: f64, py: f64, vx: f64, vy: f64, c: f64) {
+    pub fn add_particle_simple(&mut self, px: f64, py: f64, vx: f64, vy: f64, c: f64) -> u32 {
         // Color by charge sign: red = positive, blue = negative, random for neutral
         let default_color = if c < 0.0 {
             0x0000ff
         } else if c > 0.0 {
             0xff0000
         } else {
-            Self::random_color()
+            self.random_color()
         };
         let default_radius = 10.0;
 
         let p = Particle::new(px, py, default_radius, self.default_mass, default_color, vx, vy, c);
-        self.particles.push(p);
+        self.insert_particle_undoable(p)
+    }
+
+    // Add a tracer (test charge): moves under all forces but exerts none
+    pub fn add_tracer(&mut self, px: f64, py: f64, vx: f64, vy: f64, c: f64) -> u32 {
+        let color = if c < 0.0 {
+            0x0000ff
+        } else if c > 0.0 {
+            0xff0000
+        } else {
+            self.random_color()
+        };
+        let mut p = Particle::new(px, py, 3.0, self.default_mass, color, vx, vy, c);
+        p.tracer = true;
+        self.insert_particle_undoable(p)
+    }
+
+    pub fn get_tracer_count(&self) -> i32 {
+        self.particles
+            .iter()
+            .filter(|p| p.tracer)
+            .count() as i32
     }
 
     pub fn pop_particle(&mut self) {
-        self.particles.pop();
+        if !self.particles.is_empty() {
+            self.remove_particle(self.particles.len() - 1);
+        }
     }
 
     pub fn remove_particle(&mut self, index: usize) {
         if index < self.particles.len() {
-            self.particles.remove(index);
+            let particle = Box::new(self.take_particle(index));
+            self.trails.remove(index);
+            self.record(Edit::RemoveParticle { index, particle });
         }
     }
 
@@ -1212,9 +818,8 @@ impl Universe {
 
     pub fn get_trails(&self) -> JsValue {
         if self.show_trails {
-            let trails: Vec<Vec<Trail>> = self.particles
-                .iter()
-                .map(|p| p.get_trail())
+            let trails: Vec<Vec<Trail>> = (0..self.particles.len())
+                .map(|i| self.trails.get(i))
                 .collect();
             serde_wasm_bindgen::to_value(&trails).unwrap()
         } else {
@@ -1228,18 +833,22 @@ impl Universe {
     }
 
     pub fn set_coulomb_constant(&mut self, k: f64) {
-        self.coulomb_constant = k;
+        self.edit_settings(|u| {
+            u.coulomb_constant = k;
+        });
     }
 
     pub fn get_coulomb_constant(&self) -> f64 {
-        return self.coulomb_constant;
+        self.coulomb_constant
     }
     pub fn set_speed(&mut self, speed: f64) {
-        self.speed = speed;
+        self.edit_settings(|u| {
+            u.speed = speed;
+        });
     }
 
     pub fn get_speed(&self) -> f64 {
-        return self.speed;
+        self.speed
     }
 
     pub fn set_is_paused(&mut self, is_paused: bool) {
@@ -1247,28 +856,34 @@ impl Universe {
     }
 
     pub fn get_is_paused(&self) -> bool {
-        return self.is_paused;
+        self.is_paused
     }
 
     pub fn set_implementation(&mut self, implementation: Implementation) {
-        self.implementation = implementation;
+        self.edit_settings(|u| {
+            u.implementation = implementation;
+        });
     }
 
     pub fn get_implementation(&self) -> Implementation {
-        return self.implementation;
+        self.implementation
     }
 
     pub fn set_show_trails(&mut self, show_trails: bool) {
-        self.show_trails = show_trails;
+        self.edit_settings(|u| {
+            u.show_trails = show_trails;
+        });
     }
 
     pub fn get_show_trails(&self) -> bool {
-        return self.show_trails;
+        self.show_trails
     }
 
     // New: minimum interaction distance (softening) to avoid singular Coulomb forces
     pub fn set_min_interaction_distance(&mut self, d: f64) {
-        self.min_interaction_distance = d;
+        self.edit_settings(|u| {
+            u.min_interaction_distance = d;
+        });
     }
 
     pub fn get_min_interaction_distance(&self) -> f64 {
@@ -1276,7 +891,9 @@ impl Universe {
     }
 
     pub fn set_spawn_range(&mut self, r: f64) {
-        self.spawn_range = r;
+        self.edit_settings(|u| {
+            u.spawn_range = r;
+        });
     }
 
     pub fn get_spawn_range(&self) -> f64 {
@@ -1284,146 +901,137 @@ impl Universe {
     }
 
     pub fn set_default_charge(&mut self, charge: f64) {
-        self.default_charge = charge;
+        self.edit_settings(|u| {
+            u.default_charge = charge;
+        });
     }
 
     pub fn get_default_charge(&self) -> f64 {
-        return self.default_charge;
+        self.default_charge
     }
 
     pub fn set_default_mass(&mut self, mass: f64) {
-        self.default_mass = mass;
+        self.edit_settings(|u| {
+            u.default_mass = mass;
+        });
     }
 
     pub fn get_default_mass(&self) -> f64 {
-        return self.default_mass;
+        self.default_mass
     }
 
     // Toggle whether individual particle mass is used in acceleration calculations
     pub fn set_mass_calculation(&mut self, mass_calculation: bool) {
-        self.mass_calculation = mass_calculation;
+        self.edit_settings(|u| {
+            u.mass_calculation = mass_calculation;
+        });
     }
 
     pub fn get_mass_calculation(&self) -> bool {
-        return self.mass_calculation;
+        self.mass_calculation
     }
 
-    // Quadtree controls (reintroduced)
+    // Quadtree controls (reintroduced); these force the quadtree or direct backend, and report
+    // whether the quadtree is in use even when Auto picked it
     pub fn set_use_quadtree(&mut self, use_quadtree: bool) {
-        self.use_quadtree = use_quadtree;
+        let backend = if use_quadtree { ForceBackend::Quadtree } else { ForceBackend::Direct };
+        self.set_force_backend(backend);
     }
 
     pub fn get_use_quadtree(&self) -> bool {
-        return self.use_quadtree;
+        self.active_backend() == ForceBackend::Quadtree
     }
 
     pub fn toggle_use_quadtree(&mut self) {
-        self.use_quadtree = !self.use_quadtree;
+        self.set_use_quadtree(!self.get_use_quadtree());
     }
 
     pub fn set_quadtree_theta(&mut self, theta: f64) {
-        self.quadtree_theta = theta;
+        self.edit_settings(|u| {
+            u.quadtree_theta = theta;
+        });
     }
 
     pub fn get_quadtree_theta(&self) -> f64 {
-        return self.quadtree_theta;
+        self.quadtree_theta
     }
 
-    pub fn get_quadtree(&mut self) -> JsValue {
-        if !self.particles.is_empty() {
-            let mut min = Vec2::new(f64::MAX, f64::MAX);
-            let mut max = Vec2::new(f64::MIN, f64::MIN);
-
-            for p in &self.particles {
-                if p.pos.x < min.x {
-                    min.x = p.pos.x;
-                }
-                if p.pos.y < min.y {
-                    min.y = p.pos.y;
-                }
-                if p.pos.x > max.x {
-                    max.x = p.pos.x;
-                }
-                if p.pos.y > max.y {
-                    max.y = p.pos.y;
-                }
-            }
-
-            // Add small padding
-            let padding = 100.0;
-            min = min - Vec2::new(padding, padding);
-            max = max + Vec2::new(padding, padding);
-            let dimensions = max - min;
-            let center = min + dimensions * 0.5;
-            self.quadtree.rebuild(&self.particles, dimensions, center);
-        }
-        serde_wasm_bindgen::to_value(&self.quadtree).unwrap()
-    }
     pub fn set_use_mass_in_calculation(&mut self, _use_mass: bool) {
         // Mass is always used for converting force->acceleration in this simulation
     }
 
     pub fn get_use_mass_in_calculation(&self) -> bool {
-        return true;
+        true
     }
 
     // Collisions controls
     pub fn set_collisions_enabled(&mut self, enabled: bool) {
-        self.collisions_enabled = enabled;
+        self.edit_settings(|u| {
+            u.collisions_enabled = enabled;
+        });
     }
 
     pub fn get_collisions_enabled(&self) -> bool {
-        return self.collisions_enabled;
+        self.collisions_enabled
     }
 
     pub fn set_restitution(&mut self, r: f64) {
-        self.restitution = r;
+        self.edit_settings(|u| {
+            u.restitution = r;
+        });
     }
 
     pub fn get_restitution(&self) -> f64 {
-        return self.restitution;
+        self.restitution
     }
     pub fn update_particle_position(&mut self, index: usize, x: f64, y: f64) {
-        if index < self.particles.len() {
-            self.particles[index].pos = Vec2::new(x, y);
-        }
+        self.edit_particle(index, |p| {
+            p.pos = Vec2::new(x, y);
+        });
     }
 
     pub fn update_particle_velocity(&mut self, index: usize, vx: f64, vy: f64) {
-        if index < self.particles.len() {
-            self.particles[index].vel = Vec2::new(vx, vy);
-        }
+        self.edit_particle(index, |p| {
+            p.vel = Vec2::new(vx, vy);
+        });
     }
 
     pub fn update_particle_mass(&mut self, index: usize, mass: f64) {
-        if index < self.particles.len() {
-            self.particles[index].mass = mass;
-        }
+        self.edit_particle(index, |p| {
+            p.mass = mass;
+        });
     }
 
     pub fn update_particle_charge(&mut self, index: usize, charge: f64) {
-        if index < self.particles.len() {
-            self.particles[index].charge = charge;
-        }
+        self.edit_particle(index, |p| {
+            p.charge = charge;
+        });
     }
 
     pub fn update_particle_radius(&mut self, index: usize, radius: f32) {
-        if index < self.particles.len() {
-            self.particles[index].radius = radius;
-        }
+        self.edit_particle(index, |p| {
+            p.radius = radius;
+        });
     }
 
     pub fn update_particle_color(&mut self, index: usize, color: u32) {
-        if index < self.particles.len() {
-            self.particles[index].color = color;
-        }
+        self.edit_particle(index, |p| {
+            p.color = color;
+        });
     }
 
     // Update particle fixed state
     pub fn update_particle_fixed(&mut self, index: usize, fixed: bool) {
-        if index < self.particles.len() {
-            self.particles[index].fixed = fixed;
-        }
+        self.edit_particle(index, |p| {
+            p.fixed = fixed;
+        });
+    }
+
+    // Update particle tracer state
+    pub fn update_particle_tracer(&mut self, index: usize, tracer: bool) {
+        self.edit_particle(index, |p| {
+            p.tracer = tracer;
+        });
     }
 
     // NOTE: Particle magnet strength removed; magnets carry strength instead
@@ -1432,10 +1040,15 @@ impl Universe {
 #[wasm_bindgen]
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Magnet {
+    // Stable identifier assigned by the Universe (0 = not yet added)
+    #[serde(default)]
+    id: u32,
     pub pos: Vec2,
     pub vel: Vec2,
     pub acc: Vec2,
     pub angle: f64, // orientation in radians; +x direction indicates North
+    #[serde(default)]
+    pub spin: f64, // angular velocity in radians per second
     pub size: f32, // overall length of magnet (distance between pole centers)
     pub thickness: f32, // visual thickness
     pub mass: f64,
@@ -1448,6 +1061,7 @@ pub struct Magnet {
 #[wasm_bindgen]
 impl Magnet {
     #[wasm_bindgen(constructor)]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         px: f64,
         py: f64,
@@ -1461,10 +1075,12 @@ impl Magnet {
         fixed: bool
     ) -> Magnet {
         Magnet {
+            id: 0,
             pos: Vec2::new(px, py),
             vel: Vec2::new(0.0, 0.0),
             acc: Vec2::new(0.0, 0.0),
             angle,
+            spin: 0.0,
             size,
             thickness,
             mass,
@@ -1477,10 +1093,12 @@ impl Magnet {
 
     pub fn new_simple(px: f64, py: f64, strength: f64) -> Magnet {
         Magnet {
+            id: 0,
             pos: Vec2::new(px, py),
             vel: Vec2::new(0.0, 0.0),
             acc: Vec2::new(0.0, 0.0),
             angle: 0.0,
+            spin: 0.0,
             size: 60.0,
             thickness: 20.0,
             mass: 1.0,
@@ -1491,6 +1109,10 @@ impl Magnet {
         }
     }
 
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
     pub fn get_data(&self) -> JsValue {
         serde_wasm_bindgen::to_value(&self).unwrap()
     }
@@ -1503,170 +1125,85 @@ impl Magnet {
     }
 }
 
-// Add quadtree acceleration method within an impl Universe block
-impl Universe {
-    fn calculate_electrostatic_accelerations_quadtree(&mut self) -> Vec<Vec2> {
-        let n = self.particles.len();
-        let mut accelerations = vec![Vec2::new(0.0, 0.0); n];
-
-        if n == 0 {
-            return accelerations;
-        }
-
-        // Rebuild quadtree
-        let mut min = Vec2::new(f64::MAX, f64::MAX);
-        let mut max = Vec2::new(f64::MIN, f64::MIN);
-
-        for p in &self.particles {
-            if p.pos.x < min.x {
-                min.x = p.pos.x;
-            }
-            if p.pos.y < min.y {
-                min.y = p.pos.y;
-            }
-            if p.pos.x > max.x {
-                max.x = p.pos.x;
-            }
-            if p.pos.y > max.y {
-                max.y = p.pos.y;
-            }
-        }
-
-        // Add small padding
-        let padding = 100.0;
-        min = min - Vec2::new(padding, padding);
-        max = max + Vec2::new(padding, padding);
-        let dimensions = max - min;
-        let center = min + dimensions * 0.5;
+impl Magnet {
+    // North and south pseudo-poles as (position, strength), +/- strength/2 at the magnet ends
+    pub(crate) fn poles(&self) -> [(Vec2, f64); 2] {
+        self.poles_at(self.pos, self.angle)
+    }
 
-        self.quadtree.rebuild(&self.particles, dimensions, center);
+    // Poles the magnet would have with its center at `pos`, turned to `angle`
+    pub(crate) fn poles_at(&self, pos: Vec2, angle: f64) -> [(Vec2, f64); 2] {
+        let half = (self.size as f64) * 0.5;
+        let axis = Vec2::new(f64::cos(angle), f64::sin(angle));
+        [
+            (pos + axis * half, self.strength * 0.5),
+            (pos - axis * half, -self.strength * 0.5),
+        ]
+    }
+}
 
-        for i in 0..n {
-            let pos2d = Vec2::new(self.particles[i].pos.x, self.particles[i].pos.y);
-            let particle_mass = if self.mass_calculation {
-                self.particles[i].mass
-            } else {
-                self.default_mass
-            };
-            // Inline recursive quadtree evaluation to compute acceleration
-            fn rec(
-                quad: &QuadTreeNode,
-                particle_pos: Vec2,
-                particle_charge: f64,
-                particle_mass: f64,
-                theta: f64,
-                coulomb_constant: f64,
-                min_interaction_distance: f64
-            ) -> Vec2 {
-                if quad.charge == 0.0 {
-                    return Vec2::new(0.0, 0.0);
-                }
-                let dx = quad.center_of_charge.x - particle_pos.x;
-                let dy = quad.center_of_charge.y - particle_pos.y;
-                let distance = f64::sqrt(dx * dx + dy * dy);
-                if distance < 1e-10 {
-                    return Vec2::new(0.0, 0.0);
-                }
-                let size = f64::max(quad.dimensions.x, quad.dimensions.y);
-                let ratio = size / distance;
-
-                if ratio < theta || quad.children.is_none() {
-                    let distance_squared = distance * distance;
-                    let min_dist = min_interaction_distance;
-                    let min_dist_sq = min_dist * min_dist;
-                    let safe_distance_squared = if distance_squared < min_dist_sq {
-                        min_dist_sq
-                    } else {
-                        distance_squared
-                    };
-                    let safe_distance = f64::sqrt(safe_distance_squared);
-                    let force_magnitude =
-                        (coulomb_constant * quad.charge * particle_charge) / safe_distance_squared;
-                    let acc_magnitude = force_magnitude / particle_mass;
-                    let unit_x = (particle_pos.x - quad.center_of_charge.x) / safe_distance;
-                    let unit_y = (particle_pos.y - quad.center_of_charge.y) / safe_distance;
-                    Vec2::new(acc_magnitude * unit_x, acc_magnitude * unit_y)
-                } else {
-                    let mut acc = Vec2::new(0.0, 0.0);
-                    if let Some(children) = &quad.children {
-                        for child in children.iter() {
-                            acc += rec(
-                                child,
-                                particle_pos,
-                                particle_charge,
-                                particle_mass,
-                                theta,
-                                coulomb_constant,
-                                min_interaction_distance
-                            );
-                        }
-                    }
-                    acc
-                }
-            }
+// Add quadtree acceleration method within an impl Universe block
+impl Universe {
+    // Mass used to turn a force on particle i into an acceleration
+    fn effective_mass(&self, i: usize) -> f64 {
+        if self.mass_calculation { self.particles[i].mass } else { self.default_mass }
+    }
 
-            let acc2d = rec(
-                &self.quadtree,
-                pos2d,
-                self.particles[i].charge,
-                particle_mass,
-                self.quadtree_theta,
-                self.coulomb_constant,
-                self.min_interaction_distance
-            );
-            accelerations[i].x = acc2d.x;
-            accelerations[i].y = acc2d.y;
+    // Accelerations from the Coulomb fields at each particle
+    pub(crate) fn fields_to_accelerations(&self, mut fields: Vec<Vec2>) -> Vec<Vec2> {
+        for (i, field) in fields.iter_mut().enumerate() {
+            *field = *field * (self.particles[i].charge / self.effective_mass(i));
         }
+        fields
+    }
 
-        // Include magnet dipole contributions
-        for i in 0..n {
-            let qi = self.particles[i].charge;
-            let mi = if self.mass_calculation { self.particles[i].mass } else { self.default_mass };
-            for m in &self.magnets {
-                let half = (m.size as f64) * 0.5;
-                let ux = f64::cos(m.angle);
-                let uy = f64::sin(m.angle);
-                let north = Vec2::new(m.pos.x + ux * half, m.pos.y + uy * half);
-                let south = Vec2::new(m.pos.x - ux * half, m.pos.y - uy * half);
-                let pole_n = m.strength * 0.5;
-                let pole_s = -m.strength * 0.5;
-
-                // North pole
-                {
-                    let rx = self.particles[i].pos.x - north.x;
-                    let ry = self.particles[i].pos.y - north.y;
-                    let dist_sq = rx * rx + ry * ry;
-                    let min_dist = self.min_interaction_distance;
-                    let min_dist_sq = min_dist * min_dist;
-                    let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                    let dist = f64::sqrt(safe_dist_sq);
-                    if dist > 1e-8 {
-                        let force_magnitude = (self.coulomb_constant * qi * pole_n) / safe_dist_sq;
-                        let acc_magnitude = force_magnitude / mi;
-                        accelerations[i].x += acc_magnitude * (rx / dist);
-                        accelerations[i].y += acc_magnitude * (ry / dist);
-                    }
-                }
+    // Indices of particles that act as field sources (everything except tracers)
+    fn source_indices(&self) -> Vec<usize> {
+        self.particles
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| !p.tracer)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
 
-                // South pole
-                {
-                    let rx = self.particles[i].pos.x - south.x;
-                    let ry = self.particles[i].pos.y - south.y;
-                    let dist_sq = rx * rx + ry * ry;
-                    let min_dist = self.min_interaction_distance;
-                    let min_dist_sq = min_dist * min_dist;
-                    let safe_dist_sq = if dist_sq < min_dist_sq { min_dist_sq } else { dist_sq };
-                    let dist = f64::sqrt(safe_dist_sq);
-                    if dist > 1e-8 {
-                        let force_magnitude = (self.coulomb_constant * qi * pole_s) / safe_dist_sq;
-                        let acc_magnitude = force_magnitude / mi;
-                        accelerations[i].x += acc_magnitude * (rx / dist);
-                        accelerations[i].y += acc_magnitude * (ry / dist);
-                    }
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A tracer next to a source: every backend pushes the tracer, and the source's acceleration
+    // does not depend on the tracer's charge at all
+    #[test]
+    fn tracers_feel_forces_but_exert_none() {
+        let scene = |tracer_charge: f64| {
+            let mut universe = Universe::new_empty();
+            universe.add_particle_simple(0.0, 0.0, 0.0, 0.0, 1.0);
+            universe.add_tracer(50.0, 0.0, 0.0, 0.0, tracer_charge);
+            universe
+        };
+        let (mut universe, heavy) = (scene(1.0), scene(-1000.0));
+        let positions: Vec<Vec2> = universe.particles
+            .iter()
+            .map(|p| p.pos)
+            .collect();
+
+        for backend in [
+            ForceBackend::Direct,
+            ForceBackend::Quadtree,
+            ForceBackend::Fmm,
+            ForceBackend::ParticleMesh,
+        ] {
+            let acc = universe.coulomb_accelerations(backend, &positions);
+            let other = heavy.coulomb_accelerations(backend, &positions);
+            assert_eq!((acc[0].x, acc[0].y), (other[0].x, other[0].y), "{:?}", backend);
+            assert!(acc[1].x > 0.0, "tracer not repelled under {:?}", backend);
         }
+        let direct = universe.coulomb_accelerations(ForceBackend::Direct, &positions);
+        assert_eq!((direct[0].x, direct[0].y), (0.0, 0.0));
 
-        accelerations
+        universe.time_step(0.016);
+        assert_eq!((universe.particles[0].pos.x, universe.particles[0].pos.y), (0.0, 0.0));
+        assert!(universe.particles[1].pos.x > 50.0);
     }
 }
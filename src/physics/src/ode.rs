@@ -0,0 +1,323 @@
+use crate::{ Implementation, Universe, Vec2 };
+use crate::forces::ForceState;
+
+// The whole system as a second-order ODE q'' = a(q, q'). q holds particle positions (x, y per
+// particle), then magnet positions, then magnet angles; v holds their rates in the same layout.
+// Components of fixed bodies are frozen: integrators never change them, not even in
+// intermediate stages.
+#[derive(Clone)]
+pub(crate) struct SystemState {
+    q: Vec<f64>,
+    v: Vec<f64>,
+    frozen: Vec<bool>,
+    particles: usize,
+    magnets: usize,
+}
+
+impl SystemState {
+    fn magnet_offset(&self) -> usize {
+        2 * self.particles
+    }
+
+    fn angle_offset(&self) -> usize {
+        2 * (self.particles + self.magnets)
+    }
+}
+
+// x + rate * h on every unfrozen component
+fn advanced(x: &[f64], rate: &[f64], h: f64, frozen: &[bool]) -> Vec<f64> {
+    x.iter()
+        .zip(rate)
+        .zip(frozen)
+        .map(|((&x, &r), &frozen)| if frozen { x } else { x + r * h })
+        .collect()
+}
+
+fn pairs(values: &[f64]) -> Vec<Vec2> {
+    values
+        .chunks_exact(2)
+        .map(|c| Vec2::new(c[0], c[1]))
+        .collect()
+}
+
+// Advances `state` by dt with `method`, where `accel` gives the accelerations for given q and v
+// (None if they are not finite). Returns the accelerations to show for the new state: those
+// at its start for Euler and RK4, and at its end for Verlet and leapfrog.
+pub(crate) fn integrate(
+    method: Implementation,
+    state: &mut SystemState,
+    dt: f64,
+    accel: impl Fn(&[f64], &[f64]) -> Option<Vec<f64>>
+) -> Option<Vec<f64>> {
+    let frozen = &state.frozen;
+    let (q, v) = (&state.q, &state.v);
+    let (next_q, next_v, shown) = match method {
+        // semi-implicit: the position update uses the new velocity
+        Implementation::Euler => {
+            let a = accel(q, v)?;
+            let next_v = advanced(v, &a, dt, frozen);
+            let next_q = advanced(q, &next_v, dt, frozen);
+            (next_q, next_v, a)
+        }
+        Implementation::RK4 => {
+            let half = dt * 0.5;
+            let a1 = accel(q, v)?;
+            let (q2, v2) = (advanced(q, v, half, frozen), advanced(v, &a1, half, frozen));
+            let a2 = accel(&q2, &v2)?;
+            let (q3, v3) = (advanced(q, &v2, half, frozen), advanced(v, &a2, half, frozen));
+            let a3 = accel(&q3, &v3)?;
+            let (q4, v4) = (advanced(q, &v3, dt, frozen), advanced(v, &a3, dt, frozen));
+            let a4 = accel(&q4, &v4)?;
+            let combine = |x: &[f64], k1: &[f64], k2: &[f64], k3: &[f64], k4: &[f64]| {
+                (0..x.len())
+                    .map(|i| {
+                        if frozen[i] {
+                            x[i]
+                        } else {
+                            x[i] + ((k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]) * dt) / 6.0
+                        }
+                    })
+                    .collect::<Vec<f64>>()
+            };
+            let next_q = combine(q, v, &v2, &v3, &v4);
+            let next_v = combine(v, &a1, &a2, &a3, &a4);
+            (next_q, next_v, a1)
+        }
+        // velocity Verlet; velocity-dependent forces (drag, magnetic field) at the end of the
+        // step see the predicted velocity v + a0 dt, since the new one is not known yet
+        Implementation::Verlet => {
+            let a0 = accel(q, v)?;
+            let next_q: Vec<f64> = (0..q.len())
+                .map(|i| if frozen[i] { q[i] } else { q[i] + v[i] * dt + 0.5 * a0[i] * dt * dt })
+                .collect();
+            let predicted_v = advanced(v, &a0, dt, frozen);
+            let a1 = accel(&next_q, &predicted_v)?;
+            let next_v: Vec<f64> = (0..v.len())
+                .map(|i| if frozen[i] { v[i] } else { v[i] + 0.5 * (a0[i] + a1[i]) * dt })
+                .collect();
+            (next_q, next_v, a1)
+        }
+        // kick-drift-kick
+        Implementation::Leapfrog => {
+            let a0 = accel(q, v)?;
+            let half_v = advanced(v, &a0, dt / 2.0, frozen);
+            let next_q = advanced(q, &half_v, dt, frozen);
+            let a1 = accel(&next_q, &half_v)?;
+            let next_v = advanced(&half_v, &a1, dt / 2.0, frozen);
+            (next_q, next_v, a1)
+        }
+    };
+    state.q = next_q;
+    state.v = next_v;
+    Some(shown)
+}
+
+impl Universe {
+    pub(crate) fn system_state(&self) -> SystemState {
+        let (n, m) = (self.particles.len(), self.magnets.len());
+        let mut q = Vec::with_capacity(2 * n + 3 * m);
+        let mut v = Vec::with_capacity(2 * n + 3 * m);
+        let mut frozen = Vec::with_capacity(2 * n + 3 * m);
+        for p in &self.particles {
+            q.extend([p.pos.x, p.pos.y]);
+            v.extend([p.vel.x, p.vel.y]);
+            frozen.extend([p.fixed; 2]);
+        }
+        for magnet in &self.magnets {
+            q.extend([magnet.pos.x, magnet.pos.y]);
+            v.extend([magnet.vel.x, magnet.vel.y]);
+            frozen.extend([magnet.fixed; 2]);
+        }
+        for magnet in &self.magnets {
+            q.push(magnet.angle);
+            v.push(magnet.spin);
+            frozen.push(magnet.fixed);
+        }
+        SystemState { q, v, frozen, particles: n, magnets: m }
+    }
+
+    // Writes an integrated state back, with `shown` as the accelerations to display
+    pub(crate) fn set_system_state(&mut self, state: &SystemState, shown: &[f64]) {
+        let (q, v) = (&state.q, &state.v);
+        for (i, p) in self.particles.iter_mut().enumerate() {
+            p.pos = Vec2::new(q[2 * i], q[2 * i + 1]);
+            p.vel = Vec2::new(v[2 * i], v[2 * i + 1]);
+            p.acc = Vec2::new(shown[2 * i], shown[2 * i + 1]);
+        }
+        let (offset, angles) = (state.magnet_offset(), state.angle_offset());
+        for (i, magnet) in self.magnets.iter_mut().enumerate() {
+            let j = offset + 2 * i;
+            magnet.pos = Vec2::new(q[j], q[j + 1]);
+            magnet.vel = Vec2::new(v[j], v[j + 1]);
+            magnet.acc = Vec2::new(shown[j], shown[j + 1]);
+            // keep the angle within [-pi, pi] so a spinning magnet does not wind up
+            let angle = q[angles + i];
+            magnet.angle = if angle.abs() > std::f64::consts::PI {
+                (angle + std::f64::consts::PI).rem_euclid(std::f64::consts::TAU) -
+                    std::f64::consts::PI
+            } else {
+                angle
+            };
+            magnet.spin = v[angles + i];
+        }
+    }
+
+    // Accelerations of every component of a state shaped like `like`, from all enabled force
+    // models; None if any is not finite
+    pub(crate) fn system_accelerations(
+        &self,
+        like: &SystemState,
+        q: &[f64],
+        v: &[f64]
+    ) -> Option<Vec<f64>> {
+        let (offset, angles) = (like.magnet_offset(), like.angle_offset());
+        let positions = pairs(&q[..offset]);
+        let velocities = pairs(&v[..offset]);
+        let magnet_positions = pairs(&q[offset..angles]);
+        let state = ForceState {
+            positions: &positions,
+            velocities: &velocities,
+            magnet_positions: &magnet_positions,
+            magnet_angles: &q[angles..],
+        };
+        let forces = self.evaluate_forces(&state);
+
+        let mut a = Vec::with_capacity(q.len());
+        for acc in forces.particles.iter().chain(&forces.magnets) {
+            a.extend([acc.x, acc.y]);
+        }
+        a.extend(forces.magnet_angular);
+        if a.iter().any(|x| !x.is_finite()) {
+            return None;
+        }
+        Some(a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::coulomb_potential;
+
+    const METHODS: [(&str, Implementation); 4] = [
+        ("Euler", Implementation::Euler),
+        ("RK4", Implementation::RK4),
+        ("Verlet", Implementation::Verlet),
+        ("Leapfrog", Implementation::Leapfrog),
+    ];
+
+    // A free magnet swinging and turning past a fixed charge
+    fn magnet_and_charge(implementation: Implementation) -> Universe {
+        let mut universe = Universe::new_empty();
+        universe.set_implementation(implementation);
+        universe.add_particle(0.0, 0.0, 0.0, 0.0, 5.0, 1.0, 0xff0000, 5.0);
+        universe.particles[0].fixed = true;
+        universe.add_magnet(120.0, 0.0, 1.0, 60.0, 20.0, 2.0, 0xff0000, 0x0000ff, 10.0, false);
+        universe.magnets[0].vel = Vec2::new(0.0, 3.0);
+        universe.magnets[0].spin = 0.2;
+        universe
+    }
+
+    // Kinetic and rotational energy of the magnet plus its poles' energy in the charge's field
+    fn energy(universe: &Universe) -> f64 {
+        let (p, m) = (&universe.particles[0], &universe.magnets[0]);
+        let (k, min_dist) = (universe.coulomb_constant, universe.min_interaction_distance);
+        let size = m.size as f64;
+        let inertia = (m.mass * size * size) / 12.0;
+        let speed_sq = m.vel.x * m.vel.x + m.vel.y * m.vel.y;
+        let mut energy = 0.5 * m.mass * speed_sq + 0.5 * inertia * m.spin * m.spin;
+        for (pos, strength) in m.poles() {
+            energy += strength * coulomb_potential(k, p.charge, pos - p.pos, min_dist);
+        }
+        energy
+    }
+
+    #[test]
+    fn single_magnet_conserves_energy() {
+        let bounds = [0.15, 2e-4, 5e-4, 5e-4];
+        for ((name, implementation), bound) in METHODS.into_iter().zip(bounds) {
+            let mut universe = magnet_and_charge(implementation);
+            let start = energy(&universe);
+            let mut worst: f64 = 0.0;
+            for _ in 0..1000 {
+                universe.time_step(0.01);
+                worst = worst.max((energy(&universe) - start).abs());
+            }
+            let magnet = &universe.magnets[0];
+            assert!(magnet.pos.distance_from(Vec2::new(120.0, 0.0)) > 10.0, "{}", name);
+            assert!((magnet.angle - 1.0).abs() > 0.1, "{}", name);
+            assert!(worst < bound * start.abs(), "{}: {}", name, worst / start.abs());
+        }
+    }
+
+    #[test]
+    fn fixed_bodies_stay_frozen() {
+        for (name, implementation) in METHODS {
+            let mut universe = magnet_and_charge(implementation);
+            universe.add_particle(-60.0, 40.0, 2.0, -1.0, 5.0, 1.0, 0x0000ff, -3.0);
+            universe.add_magnet(-100.0, -80.0, 2.5, 40.0, 10.0, 1.0, 0xff0000, 0x0000ff, 6.0, true);
+            let (particle, magnet) = (universe.particles[0].clone(), universe.magnets[1].clone());
+            for _ in 0..200 {
+                universe.time_step(0.01);
+            }
+            let p = &universe.particles[0];
+            let m = &universe.magnets[1];
+            let bits = |v: Vec2| (v.x.to_bits(), v.y.to_bits());
+            assert_eq!(bits(p.pos), bits(particle.pos), "{}", name);
+            assert_eq!(bits(p.vel), bits(particle.vel), "{}", name);
+            assert_eq!(bits(m.pos), bits(magnet.pos), "{}", name);
+            assert_eq!(bits(m.vel), bits(magnet.vel), "{}", name);
+            assert_eq!(m.angle.to_bits(), magnet.angle.to_bits(), "{}", name);
+            assert_eq!(m.spin.to_bits(), magnet.spin.to_bits(), "{}", name);
+            // while the free ones moved
+            assert!(universe.particles[1].pos.distance_from(Vec2::new(-60.0, 40.0)) > 1.0);
+        }
+    }
+
+    // Drag is the simplest velocity-dependent force: v(t) = v0 exp(-gamma t)
+    #[test]
+    fn verlet_stays_second_order_under_drag() {
+        let exact = 100.0 * f64::exp(-2.0);
+        for (name, implementation, bound) in [
+            ("RK4", Implementation::RK4, 1e-8),
+            ("Verlet", Implementation::Verlet, 5e-4),
+        ] {
+            let mut state = SystemState {
+                q: vec![0.0],
+                v: vec![100.0],
+                frozen: vec![false],
+                particles: 0,
+                magnets: 0,
+            };
+            for _ in 0..100 {
+                integrate(implementation, &mut state, 0.01, |_, v| Some(vec![-2.0 * v[0]]));
+            }
+            let error = (state.v[0] - exact).abs() / exact;
+            assert!(error < bound, "{}: {}", name, error);
+        }
+    }
+
+    #[test]
+    fn magnet_angles_are_wrapped() {
+        let mut universe = Universe::new_empty();
+        universe.add_magnet_simple(0.0, 0.0, 1.0);
+        let mut state = universe.system_state();
+        let angle = state.angle_offset();
+        let pi = std::f64::consts::PI;
+        for (set, expected) in [
+            (3.0, 3.0),
+            (pi, pi),
+            (-pi, -pi),
+            (7.0, 7.0 - 2.0 * pi),
+            (-7.0, 2.0 * pi - 7.0),
+            (3.0 + 10.0 * pi, 3.0),
+            (3.0 * pi, -pi),
+        ] {
+            state.q[angle] = set;
+            universe.set_system_state(&state, &[0.0; 3]);
+            let wrapped = universe.magnets[0].angle;
+            assert!((wrapped - expected).abs() < 1e-9, "{} -> {}", set, wrapped);
+            assert!(wrapped.abs() <= pi);
+        }
+    }
+}
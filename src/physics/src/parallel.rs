@@ -0,0 +1,46 @@
+// Work splitting for the force loops. With the `parallel` feature (and threads available on the
+// target) these run on rayon's pool; otherwise they run in order on the calling thread. Each
+// piece of work writes only its own output and outputs are combined in index order, so results
+// are the same for any thread count.
+
+#[cfg(all(feature = "parallel", any(not(target_arch = "wasm32"), target_feature = "atomics")))]
+mod imp {
+    use rayon::prelude::*;
+
+    pub(crate) fn threads() -> usize {
+        rayon::current_num_threads()
+    }
+
+    pub(crate) fn map<T, F>(n: usize, f: F) -> Vec<T> where T: Send, F: Fn(usize) -> T + Sync + Send {
+        if threads() <= 1 {
+            return (0..n).map(f).collect();
+        }
+        (0..n).into_par_iter().map(f).collect()
+    }
+
+    pub(crate) fn for_each_mut<T, F>(items: &mut [T], f: F) where T: Send, F: Fn(&mut T) + Sync + Send {
+        if threads() <= 1 {
+            items.iter_mut().for_each(f);
+            return;
+        }
+        items.par_iter_mut().for_each(f);
+    }
+}
+
+#[cfg(not(all(feature = "parallel", any(not(target_arch = "wasm32"), target_feature = "atomics"))))]
+mod imp {
+    pub(crate) fn threads() -> usize {
+        1
+    }
+
+    // Same bounds as the threaded version so code builds identically with or without it
+    pub(crate) fn map<T, F>(n: usize, f: F) -> Vec<T> where T: Send, F: Fn(usize) -> T + Sync + Send {
+        (0..n).map(f).collect()
+    }
+
+    pub(crate) fn for_each_mut<T, F>(items: &mut [T], f: F) where T: Send, F: Fn(&mut T) + Sync + Send {
+        items.iter_mut().for_each(f);
+    }
+}
+
+pub(crate) use imp::{ for_each_mut, map, threads };
@@ -0,0 +1,711 @@
+use wasm_bindgen::prelude::*;
+use serde::{ Serialize, Deserialize };
+use nalgebra::Complex;
+
+use std::cell::RefMut;
+
+use crate::{ parallel, Boundary, FieldKind, ForceBackend, Universe, Vec2 };
+use crate::fields::{ coulomb_field, coulomb_potential };
+use crate::fft::fft2;
+
+type C = Complex<f64>;
+
+// Mesh sizes accepted by the particle-mesh backend (cells per side, rounded to a power of two)
+pub(crate) const MIN_MESH_SIZE: usize = 16;
+pub(crate) const MAX_MESH_SIZE: usize = 512;
+pub(crate) fn default_mesh_size() -> usize {
+    128
+}
+
+// Width of the Gaussian that splits the kernel into mesh and short-range parts, in cells
+const SPLIT_CELLS: f64 = 1.5;
+// Short-range (P3M) correction cutoff, in split widths
+const CUTOFF_SIGMAS: f64 = 4.0;
+// Empty cells kept around the particles of an isolated mesh so assignment stencils fit
+const MARGIN_CELLS: usize = 2;
+
+// How charge is spread onto the mesh and forces are read back from it
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum MeshAssignment {
+    // Cloud-in-cell: bilinear over the 2x2 nearest cells
+    Cic,
+    // Triangular-shaped cloud: quadratic over 3x3 cells, smoother and more accurate
+    #[default]
+    Tsc,
+}
+
+// Particle-mesh solver for the in-plane 1/r Coulomb kernel.
+//
+// Poisson's equation in two dimensions has the log kernel, which is not what the engine
+// simulates, so instead of solving it the mesh convolves the charge density with the 1/r
+// Green's function by FFT. The kernel is split Ewald-style into a smooth long-range part,
+// erf(r / (sqrt(2) sigma)) / r (the potential of a Gaussian cloud of width sigma), handled on
+// the mesh, and the short-range remainder. The mesh alone gives forces that are softened over
+// about one cell; with P3M the remainder is summed directly for pairs within a few sigma, which
+// restores the engine's usual softened Coulomb force.
+//
+// With periodic boundaries the mesh covers the boundary box and the kernel is applied in Fourier
+// space (2 pi / k) erfc(k sigma / sqrt(2)); the k = 0 term is dropped, which is the same as a
+// neutralising background. Otherwise the mesh is fitted around the particles and zero padded to
+// twice its size (Hockney's method), so no periodic images are felt. Depositing and interpolating
+// with the same weights and an antisymmetric field kernel keeps self-forces at zero.
+#[derive(Clone, Default)]
+pub(crate) struct ParticleMesh {
+    // cells per side, and the transform size (n, or 2n when zero padded)
+    n: usize,
+    size: usize,
+    periodic: bool,
+    assignment: MeshAssignment,
+    origin: Vec2,
+    cell: Vec2,
+    sigma: f64,
+    // transformed kernels of the potential and of the field (x + iy), and their geometry
+    kernels: [Vec<C>; 2],
+    kernel_key: Option<(bool, usize, MeshAssignment, u64, u64)>,
+    density: Vec<C>,
+    work: Vec<C>,
+    column: Vec<C>,
+    // long-range potential and field per unit charge (without the Coulomb constant) per cell
+    potential: Vec<f64>,
+    field_x: Vec<f64>,
+    field_y: Vec<f64>,
+    // sources binned for the short-range correction; bin b holds binned[bin_start[b]..]
+    sources: Vec<(usize, Vec2, f64)>,
+    bins: (usize, usize),
+    bin_size: Vec2,
+    bin_start: Vec<usize>,
+    binned: Vec<(usize, Vec2, f64)>,
+}
+
+// Complementary error function (Numerical Recipes erfcc, relative error below 1.2e-7)
+const ERFC_COEFFICIENTS: [f64; 10] = [
+    -1.26551223, 1.00002368, 0.37409196, 0.09678418, -0.18628806, 0.27886807, -1.13520398,
+    1.48851587, -0.82215223, 0.17087277,
+];
+
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = ERFC_COEFFICIENTS.iter().rev().fold(0.0, |acc, c| acc * t + c);
+    let result = t * (-z * z + poly).exp();
+    if x >= 0.0 { result } else { 2.0 - result }
+}
+
+// Long-range potential erf(r / (sqrt(2) sigma)) / r of a unit charge
+fn long_potential(r: Vec2, sigma: f64) -> f64 {
+    let dist = r.magnitude();
+    let a = std::f64::consts::FRAC_1_SQRT_2 / sigma;
+    if dist < 1e-4 * sigma {
+        return a * std::f64::consts::FRAC_2_SQRT_PI;
+    }
+    (1.0 - erfc(a * dist)) / dist
+}
+
+// Minus the gradient of long_potential
+fn long_field(r: Vec2, sigma: f64) -> Vec2 {
+    let dist = r.magnitude();
+    let a = std::f64::consts::FRAC_1_SQRT_2 / sigma;
+    let gaussian = a * std::f64::consts::FRAC_2_SQRT_PI;
+    if dist < 1e-4 * sigma {
+        // series limit, linear in r
+        return r * ((2.0 * gaussian * a * a) / 3.0);
+    }
+    let magnitude =
+        (1.0 - erfc(a * dist)) / (dist * dist) - (gaussian * (-a * a * dist * dist).exp()) / dist;
+    r * (magnitude / dist)
+}
+
+// First cell and weights of the assignment stencil along one axis, for g in cell units
+fn axis_weights(g: f64, assignment: MeshAssignment) -> (i64, [f64; 3], usize) {
+    match assignment {
+        MeshAssignment::Cic => {
+            let s = g - 0.5;
+            let first = s.floor();
+            let f = s - first;
+            (first as i64, [1.0 - f, f, 0.0], 2)
+        }
+        MeshAssignment::Tsc => {
+            let nearest = (g - 0.5).round();
+            let d = g - 0.5 - nearest;
+            let weights = [0.5 * (0.5 - d) * (0.5 - d), 0.75 - d * d, 0.5 * (0.5 + d) * (0.5 + d)];
+            (nearest as i64 - 1, weights, 3)
+        }
+    }
+}
+
+// Grid coordinate `i` mapped into 0..n, wrapping or clamping
+fn mesh_index(i: i64, n: usize, periodic: bool) -> usize {
+    if periodic { i.rem_euclid(n as i64) as usize } else { i.clamp(0, n as i64 - 1) as usize }
+}
+
+impl ParticleMesh {
+    // Fits the mesh: the boundary box when periodic, otherwise a square around `points`
+    fn layout(
+        &mut self,
+        n: usize,
+        assignment: MeshAssignment,
+        periodic: Option<(f64, f64)>,
+        points: &[Vec2]
+    ) {
+        self.n = n;
+        self.assignment = assignment;
+        self.periodic = periodic.is_some();
+        if let Some((width, height)) = periodic {
+            self.size = n;
+            self.cell = Vec2::new(width / (n as f64), height / (n as f64));
+            self.origin = Vec2::new(-width * 0.5, -height * 0.5);
+        } else {
+            self.size = 2 * n;
+            let mut min = Vec2::new(f64::MAX, f64::MAX);
+            let mut max = Vec2::new(f64::MIN, f64::MIN);
+            for p in points {
+                min.x = min.x.min(p.x);
+                min.y = min.y.min(p.y);
+                max.x = max.x.max(p.x);
+                max.y = max.y.max(p.y);
+            }
+            if min.x > max.x {
+                min = Vec2::new(0.0, 0.0);
+                max = Vec2::new(0.0, 0.0);
+            }
+            let extent = f64::max(f64::max(max.x - min.x, max.y - min.y), 1.0);
+            // cell sizes snap to powers of 2^(1/4) so the kernels survive small changes in extent
+            let raw = extent / ((n - 2 * MARGIN_CELLS) as f64);
+            let h = (2f64).powf((raw.log2() * 4.0).ceil() / 4.0);
+            self.cell = Vec2::new(h, h);
+            let center = (min + max) * 0.5;
+            self.origin = center - Vec2::new((n as f64) * h * 0.5, (n as f64) * h * 0.5);
+        }
+        self.sigma = SPLIT_CELLS * f64::max(self.cell.x, self.cell.y);
+        self.prepare_kernels();
+    }
+
+    fn prepare_kernels(&mut self) {
+        let key = (self.periodic, self.n, self.assignment, self.cell.x.to_bits(), self.cell.y.to_bits());
+        if self.kernel_key == Some(key) {
+            return;
+        }
+        self.kernel_key = Some(key);
+        let size = self.size;
+        for kernel in self.kernels.iter_mut() {
+            kernel.clear();
+            kernel.resize(size * size, C::new(0.0, 0.0));
+        }
+        let wrapped = |i: usize| if i < size / 2 { i as f64 } else { i as f64 - size as f64 };
+
+        if self.periodic {
+            let (width, height) = (self.cell.x * (size as f64), self.cell.y * (size as f64));
+            let scale = 1.0 / (self.cell.x * self.cell.y);
+            for iy in 0..size {
+                for ix in 0..size {
+                    let kx = (2.0 * std::f64::consts::PI * wrapped(ix)) / width;
+                    let ky = (2.0 * std::f64::consts::PI * wrapped(iy)) / height;
+                    let k = (kx * kx + ky * ky).sqrt();
+                    if k == 0.0 {
+                        continue;
+                    }
+                    let potential = ((2.0 * std::f64::consts::PI) / k) *
+                        erfc((k * self.sigma) * std::f64::consts::FRAC_1_SQRT_2) *
+                        scale;
+                    // the Nyquist derivative is dropped so the field kernel stays real and odd
+                    let dx = if ix == size / 2 { 0.0 } else { kx };
+                    let dy = if iy == size / 2 { 0.0 } else { ky };
+                    let at = iy * size + ix;
+                    self.kernels[0][at] = C::new(potential, 0.0);
+                    // -i kx phi + i (-i ky phi)
+                    self.kernels[1][at] = C::new(dy * potential, -dx * potential);
+                }
+            }
+        } else {
+            for iy in 0..size {
+                for ix in 0..size {
+                    let r = Vec2::new(wrapped(ix) * self.cell.x, wrapped(iy) * self.cell.y);
+                    // the Nyquist row and column have no mirror image and lie beyond any pair
+                    // separation, so the field there is dropped to keep the kernel odd
+                    let nyquist = ix == size / 2 || iy == size / 2;
+                    let field = if nyquist { Vec2::new(0.0, 0.0) } else { long_field(r, self.sigma) };
+                    let at = iy * size + ix;
+                    self.kernels[0][at] = C::new(long_potential(r, self.sigma), 0.0);
+                    self.kernels[1][at] = C::new(field.x, field.y);
+                }
+            }
+            for kernel in self.kernels.iter_mut() {
+                fft2(kernel, size, size, false, &mut self.column);
+            }
+        }
+
+        // undo the smoothing of depositing and interpolating with the assignment window
+        let order = match self.assignment {
+            MeshAssignment::Cic => 4,
+            MeshAssignment::Tsc => 6,
+        };
+        let window = |i: usize| {
+            let x = (std::f64::consts::PI * wrapped(i)) / (size as f64);
+            if x == 0.0 { 1.0 } else { (x.sin() / x).powi(order) }
+        };
+        for iy in 0..size {
+            for ix in 0..size {
+                let scale = 1.0 / (window(ix) * window(iy));
+                for kernel in self.kernels.iter_mut() {
+                    kernel[iy * size + ix] *= scale;
+                }
+            }
+        }
+    }
+
+    // Cells and weights of the assignment stencil around `pos`: (flat cell, weight) pairs
+    fn stencil(&self, pos: Vec2) -> ([(usize, f64); 9], usize) {
+        let (x0, wx, count) = axis_weights((pos.x - self.origin.x) / self.cell.x, self.assignment);
+        let (y0, wy, _) = axis_weights((pos.y - self.origin.y) / self.cell.y, self.assignment);
+        let mut cells = [(0, 0.0); 9];
+        let mut used = 0;
+        for (j, wy) in wy.iter().enumerate().take(count) {
+            let y = mesh_index(y0 + j as i64, self.n, self.periodic);
+            for (i, wx) in wx.iter().enumerate().take(count) {
+                let x = mesh_index(x0 + i as i64, self.n, self.periodic);
+                cells[used] = (y * self.n + x, wx * wy);
+                used += 1;
+            }
+        }
+        (cells, used)
+    }
+
+    // Deposits the sources and computes the long-range field (and potential, for overlays) on
+    // every cell
+    fn solve(&mut self, sources: impl Iterator<Item = (usize, Vec2, f64)>, with_potential: bool) {
+        self.sources.clear();
+        self.sources.extend(sources);
+        let (n, size) = (self.n, self.size);
+        self.density.clear();
+        self.density.resize(size * size, C::new(0.0, 0.0));
+        for &(_, pos, charge) in &self.sources {
+            let (cells, count) = self.stencil(pos);
+            for &(cell, weight) in &cells[..count] {
+                self.density[(cell / n) * size + (cell % n)].re += charge * weight;
+            }
+        }
+        fft2(&mut self.density, size, size, false, &mut self.column);
+
+        // both field components come back from one transform, as its real and imaginary parts
+        self.convolve(1);
+        self.field_x.clear();
+        self.field_y.clear();
+        for y in 0..n {
+            let row = &self.work[y * size..y * size + n];
+            self.field_x.extend(row.iter().map(|c| c.re));
+            self.field_y.extend(row.iter().map(|c| c.im));
+        }
+        if with_potential {
+            self.convolve(0);
+            self.potential.clear();
+            for y in 0..n {
+                self.potential.extend(self.work[y * size..y * size + n].iter().map(|c| c.re));
+            }
+        }
+    }
+
+    // work = inverse transform of density * kernels[kernel]
+    fn convolve(&mut self, kernel: usize) {
+        self.work.clear();
+        self.work.extend(self.density.iter().zip(&self.kernels[kernel]).map(|(a, b)| a * b));
+        fft2(&mut self.work, self.size, self.size, true, &mut self.column);
+    }
+
+    // Bins the sources into cells at least as wide as the short-range cutoff
+    fn bin_sources(&mut self) {
+        let span = Vec2::new(self.cell.x * (self.n as f64), self.cell.y * (self.n as f64));
+        let cutoff = CUTOFF_SIGMAS * self.sigma;
+        self.bins = (
+            ((span.x / cutoff).floor() as usize).max(1),
+            ((span.y / cutoff).floor() as usize).max(1),
+        );
+        self.bin_size = Vec2::new(span.x / (self.bins.0 as f64), span.y / (self.bins.1 as f64));
+        let count = self.bins.0 * self.bins.1;
+        self.bin_start.clear();
+        self.bin_start.resize(count + 1, 0);
+        for s in 0..self.sources.len() {
+            let bin = self.bin_of(self.sources[s].1);
+            self.bin_start[bin + 1] += 1;
+        }
+        for b in 0..count {
+            self.bin_start[b + 1] += self.bin_start[b];
+        }
+        self.binned.clear();
+        self.binned.resize(self.sources.len(), (0, Vec2::new(0.0, 0.0), 0.0));
+        let mut next = self.bin_start.clone();
+        for s in 0..self.sources.len() {
+            let bin = self.bin_of(self.sources[s].1);
+            self.binned[next[bin]] = self.sources[s];
+            next[bin] += 1;
+        }
+    }
+
+    fn bin_coords(&self, pos: Vec2) -> (i64, i64) {
+        (
+            ((pos.x - self.origin.x) / self.bin_size.x).floor() as i64,
+            ((pos.y - self.origin.y) / self.bin_size.y).floor() as i64,
+        )
+    }
+
+    fn bin_index(&self, bx: i64, by: i64) -> usize {
+        let x = mesh_index(bx, self.bins.0, self.periodic);
+        let y = mesh_index(by, self.bins.1, self.periodic);
+        y * self.bins.0 + x
+    }
+
+    fn bin_of(&self, pos: Vec2) -> usize {
+        let (bx, by) = self.bin_coords(pos);
+        self.bin_index(bx, by)
+    }
+
+    // Displacement from `from` to `to`, using the nearest periodic image when periodic
+    fn displacement(&self, to: Vec2, from: Vec2) -> Vec2 {
+        let mut d = to - from;
+        if self.periodic {
+            let span = Vec2::new(self.cell.x * (self.n as f64), self.cell.y * (self.n as f64));
+            d.x -= span.x * (d.x / span.x).round();
+            d.y -= span.y * (d.y / span.y).round();
+        }
+        d
+    }
+
+    // Calls `visit(index, displacement, charge)` for every source within the cutoff of `pos`
+    fn for_each_near(&self, pos: Vec2, mut visit: impl FnMut(usize, Vec2, f64)) {
+        let cutoff = CUTOFF_SIGMAS * self.sigma;
+        let (bx, by) = self.bin_coords(pos);
+        // neighbouring bins, each visited once even when a small periodic mesh wraps onto itself
+        let mut seen = [usize::MAX; 9];
+        let mut used = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (x, y) = (bx + dx, by + dy);
+                let outside = x < 0 || y < 0 || x >= self.bins.0 as i64 || y >= self.bins.1 as i64;
+                if outside && !self.periodic {
+                    continue;
+                }
+                let bin = self.bin_index(x, y);
+                if seen[..used].contains(&bin) {
+                    continue;
+                }
+                seen[used] = bin;
+                used += 1;
+                let range = self.bin_start[bin]..self.bin_start[bin + 1];
+                for &(index, source_pos, charge) in &self.binned[range] {
+                    let d = self.displacement(pos, source_pos);
+                    if d.x * d.x + d.y * d.y < cutoff * cutoff {
+                        visit(index, d, charge);
+                    }
+                }
+            }
+        }
+    }
+
+    // Field per unit charge at `pos` from every source except particle `exclude`
+    fn field_at(
+        &self,
+        pos: Vec2,
+        exclude: Option<usize>,
+        k: f64,
+        min_dist: f64,
+        short_range: bool
+    ) -> Vec2 {
+        let (cells, count) = self.stencil(pos);
+        let mut field = Vec2::new(0.0, 0.0);
+        for &(cell, weight) in &cells[..count] {
+            field += Vec2::new(self.field_x[cell], self.field_y[cell]) * weight;
+        }
+        field = field * k;
+        if short_range {
+            self.for_each_near(pos, |index, d, charge| {
+                if Some(index) != exclude {
+                    let long = long_field(d, self.sigma) * (k * charge);
+                    field += coulomb_field(k, charge, d, min_dist) - long;
+                }
+            });
+        }
+        field
+    }
+
+    fn potential_at(&self, pos: Vec2, k: f64, min_dist: f64, short_range: bool) -> f64 {
+        let (cells, count) = self.stencil(pos);
+        let mut potential = 0.0;
+        for &(cell, weight) in &cells[..count] {
+            potential += self.potential[cell] * weight;
+        }
+        potential *= k;
+        if short_range {
+            self.for_each_near(pos, |_, d, charge| {
+                let long = long_potential(d, self.sigma) * k * charge;
+                potential += coulomb_potential(k, charge, d, min_dist) - long;
+            });
+        }
+        potential
+    }
+}
+
+impl Universe {
+    // Solves the mesh for the particles at `positions`; in isolated mode the mesh also covers
+    // `extra` points (overlay corners)
+    fn solve_mesh(
+        &self,
+        mesh: &mut ParticleMesh,
+        positions: &[Vec2],
+        extra: &[Vec2],
+        with_potential: bool
+    ) {
+        let periodic = if self.boundary == Boundary::Periodic {
+            Some((self.boundary_width, self.boundary_height))
+        } else {
+            None
+        };
+        let points: Vec<Vec2> = positions.iter().chain(extra).copied().collect();
+        mesh.layout(self.mesh_size, self.mesh_assignment, periodic, &points);
+        mesh.solve(
+            self.particles
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| !p.tracer)
+                .map(|(j, p)| (j, positions[j], p.charge)),
+            with_potential
+        );
+        if self.mesh_short_range {
+            mesh.bin_sources();
+        }
+    }
+
+    // Particle-mesh Coulomb accelerations with particle i at positions[i]
+    pub(crate) fn mesh_accelerations(&self, positions: &[Vec2]) -> Vec<Vec2> {
+        let mut mesh = self.mesh.borrow_mut();
+        self.solve_mesh(&mut mesh, positions, &[], false);
+        let mesh = &*mesh;
+        let (k, min_dist, short_range) = (
+            self.coulomb_constant,
+            self.min_interaction_distance,
+            self.mesh_short_range,
+        );
+        let fields = parallel::map(positions.len(), |i| {
+            mesh.field_at(positions[i], Some(i), k, min_dist, short_range)
+        });
+        self.fields_to_accelerations(fields)
+    }
+
+    // Mesh solved for the current particles and covering `nodes`, when overlays of `kind` should
+    // be read off it; None means the caller sums the sources directly
+    fn overlay_mesh(&self, kind: FieldKind, nodes: &[Vec2]) -> Option<RefMut<'_, ParticleMesh>> {
+        if self.active_backend() != ForceBackend::ParticleMesh || kind == FieldKind::Magnetic {
+            return None;
+        }
+        let positions: Vec<Vec2> = self.particles
+            .iter()
+            .map(|p| p.pos)
+            .collect();
+        let mut mesh = self.mesh.borrow_mut();
+        self.solve_mesh(&mut mesh, &positions, nodes, true);
+        Some(mesh)
+    }
+
+    // Magnet poles that overlays of `kind` add on top of the mesh
+    fn overlay_poles(&self, kind: FieldKind) -> Vec<(Vec2, f64)> {
+        if kind == FieldKind::Combined { self.field_sources(FieldKind::Magnetic) } else { Vec::new() }
+    }
+
+    pub(crate) fn mesh_field_samples(&self, kind: FieldKind, nodes: &[Vec2]) -> Option<Vec<Vec2>> {
+        let mesh = self.overlay_mesh(kind, nodes)?;
+        let poles = self.overlay_poles(kind);
+        Some(
+            nodes
+                .iter()
+                .map(|&node| {
+                    let field = mesh.field_at(
+                        node,
+                        None,
+                        self.coulomb_constant,
+                        self.min_interaction_distance,
+                        self.mesh_short_range
+                    );
+                    field + self.field_from_sources(&poles, node)
+                })
+                .collect()
+        )
+    }
+
+    pub(crate) fn mesh_potential_samples(&self, kind: FieldKind, nodes: &[Vec2]) -> Option<Vec<f64>> {
+        let mesh = self.overlay_mesh(kind, nodes)?;
+        let poles = self.overlay_poles(kind);
+        Some(
+            nodes
+                .iter()
+                .map(|&node| {
+                    let potential = mesh.potential_at(
+                        node,
+                        self.coulomb_constant,
+                        self.min_interaction_distance,
+                        self.mesh_short_range
+                    );
+                    potential + self.potential_from_sources(&poles, node)
+                })
+                .collect()
+        )
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Mesh cells per side, rounded up to a power of two within 16..=512
+    pub fn set_mesh_size(&mut self, size: usize) {
+        self.edit_settings(|u| {
+            u.mesh_size = size.clamp(MIN_MESH_SIZE, MAX_MESH_SIZE).next_power_of_two();
+        });
+    }
+
+    pub fn get_mesh_size(&self) -> usize {
+        self.mesh_size
+    }
+
+    pub fn set_mesh_assignment(&mut self, assignment: MeshAssignment) {
+        self.edit_settings(|u| {
+            u.mesh_assignment = assignment;
+        });
+    }
+
+    pub fn get_mesh_assignment(&self) -> MeshAssignment {
+        self.mesh_assignment
+    }
+
+    // P3M: add the exact short-range remainder for pairs within a few mesh cells
+    pub fn set_mesh_short_range(&mut self, enabled: bool) {
+        self.edit_settings(|u| {
+            u.mesh_short_range = enabled;
+        });
+    }
+
+    pub fn get_mesh_short_range(&self) -> bool {
+        self.mesh_short_range
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn::{ SpawnOptions, SpawnShape, ValueDistribution };
+
+    // Checkerboard of well separated charges: every pair is many cells apart
+    fn lattice() -> Universe {
+        let mut universe = Universe::new_empty();
+        let mut options = SpawnOptions::new(36, SpawnShape::SquareLattice);
+        options.spacing = 150.0;
+        options.charge = ValueDistribution::Alternating;
+        universe.spawn_particles(&options);
+        universe.set_force_backend(ForceBackend::ParticleMesh);
+        universe
+    }
+
+    // Dense blob whose forces are dominated by pairs closer than a cell
+    fn blob() -> Universe {
+        let mut universe = Universe::new_empty();
+        universe.set_seed(7);
+        let mut options = SpawnOptions::new(300, SpawnShape::Gaussian);
+        options.size = 200.0;
+        options.charge = ValueDistribution::Alternating;
+        options.radius = 2.0;
+        universe.spawn_particles(&options);
+        universe.set_force_backend(ForceBackend::ParticleMesh);
+        universe
+    }
+
+    #[test]
+    fn mesh_index_wraps_or_clamps() {
+        assert_eq!(mesh_index(3, 8, true), 3);
+        assert_eq!(mesh_index(-1, 8, true), 7);
+        assert_eq!(mesh_index(8, 8, true), 0);
+        assert_eq!(mesh_index(-9, 8, true), 7);
+        assert_eq!(mesh_index(3, 8, false), 3);
+        assert_eq!(mesh_index(-1, 8, false), 0);
+        assert_eq!(mesh_index(8, 8, false), 7);
+    }
+
+    #[test]
+    fn layout_fits_the_particles_or_the_box() {
+        let points = [Vec2::new(-130.0, 40.0), Vec2::new(250.0, -75.0), Vec2::new(10.0, 90.0)];
+        let mut mesh = ParticleMesh::default();
+        mesh.layout(64, MeshAssignment::Tsc, None, &points);
+        assert_eq!((mesh.n, mesh.size), (64, 128));
+        // cell sizes are powers of 2^(1/4)
+        let quarter_steps = mesh.cell.x.log2() * 4.0;
+        assert!((quarter_steps - quarter_steps.round()).abs() < 1e-9);
+        assert!(mesh.cell.x * ((64 - 2 * MARGIN_CELLS) as f64) >= 380.0);
+        let margin = (MARGIN_CELLS as f64) * mesh.cell.x;
+        let end = mesh.origin + Vec2::new(64.0 * mesh.cell.x, 64.0 * mesh.cell.y);
+        for p in points {
+            assert!(p.x >= mesh.origin.x + margin && p.x <= end.x - margin);
+            assert!(p.y >= mesh.origin.y + margin && p.y <= end.y - margin);
+        }
+
+        mesh.layout(64, MeshAssignment::Tsc, Some((1000.0, 800.0)), &points);
+        assert_eq!((mesh.n, mesh.size), (64, 64));
+        assert_eq!((mesh.origin.x, mesh.origin.y), (-500.0, -400.0));
+        assert_eq!((mesh.cell.x, mesh.cell.y), (1000.0 / 64.0, 800.0 / 64.0));
+    }
+
+    #[test]
+    fn lone_particle_feels_no_self_force() {
+        for periodic in [false, true] {
+            for assignment in [MeshAssignment::Cic, MeshAssignment::Tsc] {
+                let mut universe = Universe::new_empty();
+                if periodic {
+                    universe.set_boundary(Boundary::Periodic, 1000.0, 800.0);
+                }
+                universe.set_mesh_assignment(assignment);
+                universe.add_particle_simple(13.7, -21.3, 0.0, 0.0, 1.0);
+                // a neutral companion so the isolated mesh is not centred on the charge
+                universe.add_particle_simple(-200.0, 150.0, 0.0, 0.0, 0.0);
+                let positions = [universe.particles[0].pos, universe.particles[1].pos];
+                let acceleration = universe.mesh_accelerations(&positions)[0];
+                assert!(acceleration.magnitude() < 1e-10, "{} {:?}", periodic, assignment);
+            }
+        }
+    }
+
+    #[test]
+    fn isolated_mesh_error_falls_with_size() {
+        let mut universe = lattice();
+        let mut previous = f64::INFINITY;
+        for (size, bound) in [(64, 5e-4), (128, 3e-4), (256, 1e-4), (512, 2e-5)] {
+            universe.set_mesh_size(size);
+            let report = universe.accuracy_report(ForceBackend::ParticleMesh, 36);
+            assert!(report.mean_error < bound, "size {}: {}", size, report.mean_error);
+            assert!(report.mean_error < previous, "size {}: {}", size, report.mean_error);
+            previous = report.mean_error;
+        }
+    }
+
+    // The direct sum has no periodic images; a neutral lattice in a wide box barely feels them
+    #[test]
+    fn periodic_mesh_matches_a_sparse_lattice() {
+        let mut universe = lattice();
+        universe.set_boundary(Boundary::Periodic, 3000.0, 3000.0);
+        universe.set_mesh_size(256);
+        for short_range in [false, true] {
+            universe.set_mesh_short_range(short_range);
+            let report = universe.accuracy_report(ForceBackend::ParticleMesh, 36);
+            assert!(report.mean_error < 2e-3, "{}: {}", short_range, report.mean_error);
+        }
+    }
+
+    #[test]
+    fn short_range_correction_resolves_close_pairs() {
+        for periodic in [false, true] {
+            let mut universe = blob();
+            if periodic {
+                universe.set_boundary(Boundary::Periodic, 3000.0, 3000.0);
+            }
+            universe.set_mesh_short_range(false);
+            let mesh_only = universe.accuracy_report(ForceBackend::ParticleMesh, 300);
+            assert!(mesh_only.mean_error > 0.1, "{}: {}", periodic, mesh_only.mean_error);
+            universe.set_mesh_short_range(true);
+            let p3m = universe.accuracy_report(ForceBackend::ParticleMesh, 300);
+            assert!(p3m.mean_error < 3e-3, "{}: {}", periodic, p3m.mean_error);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+
+use crate::{ ForceBackend, Implementation, Magnet, Particle, SceneError, Universe };
+use crate::spawn::{ SpawnOptions, SpawnShape, ValueDistribution, VelocityDistribution };
+
+const RED: u32 = 0xff0000;
+const BLUE: u32 = 0x0000ff;
+const GOLD: u32 = 0xf0c000;
+
+#[derive(Serialize)]
+pub struct PresetInfo {
+    name: &'static str,
+    title: &'static str,
+    description: &'static str,
+}
+
+struct Preset {
+    info: PresetInfo,
+    // Populates an empty universe and adjusts its integrator/softening settings
+    build: fn(&mut Universe),
+}
+
+const PRESETS: &[Preset] = &[
+    Preset {
+        info: PresetInfo {
+            name: "dipole",
+            title: "Dipole",
+            description: "A fixed +/- charge pair with a ring of test charges falling along its field",
+        },
+        build: build_dipole,
+    },
+    Preset {
+        info: PresetInfo {
+            name: "quadrupole",
+            title: "Quadrupole",
+            description: "Two positive and two negative charges on a cross (the default scene)",
+        },
+        build: build_quadrupole,
+    },
+    Preset {
+        info: PresetInfo {
+            name: "ionic_lattice",
+            title: "Ionic lattice",
+            description: "A warm 8x8 rock-salt style lattice of alternating charges with collisions",
+        },
+        build: build_ionic_lattice,
+    },
+    Preset {
+        info: PresetInfo {
+            name: "thomson",
+            title: "Thomson problem",
+            description: "Like charges confined by a charged ring relax into a minimum-energy shell pattern",
+        },
+        build: build_thomson,
+    },
+    Preset {
+        info: PresetInfo {
+            name: "cyclotron",
+            title: "Cyclotron",
+            description: "Test charges launched into a uniform magnetic field circle with radius mv/qB",
+        },
+        build: build_cyclotron,
+    },
+    Preset {
+        info: PresetInfo {
+            name: "rutherford",
+            title: "Rutherford scattering",
+            description: "A beam of positive test charges scattered by a heavy fixed nucleus",
+        },
+        build: build_rutherford,
+    },
+    Preset {
+        info: PresetInfo {
+            name: "magnet_chain",
+            title: "Magnet chain",
+            description: "A row of free bar magnets pulling together pole to pole",
+        },
+        build: build_magnet_chain,
+    },
+    Preset {
+        info: PresetInfo {
+            name: "penning_trap",
+            title: "Penning-style trap",
+            description: "Charges held radially by a ring electrode while a magnetic field curls their paths",
+        },
+        build: build_penning_trap,
+    },
+    Preset {
+        info: PresetInfo {
+            name: "plasma_blob",
+            title: "Plasma blob",
+            description: "A hot, neutral Gaussian cloud of 200 charges expanding under Barnes-Hut forces",
+        },
+        build: build_plasma_blob,
+    },
+];
+
+fn charge_color(charge: f64) -> u32 {
+    if charge < 0.0 { BLUE } else { RED }
+}
+
+fn add_fixed(universe: &mut Universe, x: f64, y: f64, radius: f32, charge: f64) {
+    let mut p = Particle::new(x, y, radius, 1.0, charge_color(charge), 0.0, 0.0, charge);
+    p.fixed = true;
+    universe.insert_particle(p);
+}
+
+fn add_tracer(universe: &mut Universe, x: f64, y: f64, vx: f64, vy: f64, charge: f64, color: u32) {
+    let mut p = Particle::new(x, y, 3.0, 1.0, color, vx, vy, charge);
+    p.tracer = true;
+    universe.insert_particle(p);
+}
+
+// Ring of fixed charges used as an electrode
+fn add_ring(universe: &mut Universe, count: usize, radius: f64, charge: f64) {
+    for i in 0..count {
+        let angle = (2.0 * std::f64::consts::PI * (i as f64)) / (count as f64);
+        add_fixed(universe, radius * f64::cos(angle), radius * f64::sin(angle), 4.0, charge);
+    }
+}
+
+// The four charges the engine has always started with
+pub(crate) fn add_quadrupole_charges(universe: &mut Universe) {
+    let p1 = Particle::new(-200.0, 0.0, 10.0, 1.0, RED, 0.0, 0.0, 20.0); // positive
+    let p2 = Particle::new(200.0, 0.0, 10.0, 1.0, RED, 0.0, 0.0, 20.0); // positive
+    let p3 = Particle::new(0.0, 200.0, 10.0, 0.8, BLUE, 0.0, 0.0, -20.0); // negative
+    let p4 = Particle::new(0.0, -200.0, 10.0, 0.8, BLUE, 0.0, 0.0, -20.0); // negative
+    for p in [p1, p2, p3, p4] {
+        universe.insert_particle(p);
+    }
+}
+
+fn build_dipole(universe: &mut Universe) {
+    universe.implementation = Implementation::RK4;
+    universe.min_interaction_distance = 10.0;
+    add_fixed(universe, -120.0, 0.0, 12.0, 30.0);
+    add_fixed(universe, 120.0, 0.0, 12.0, -30.0);
+    for i in 0..24 {
+        let angle = (2.0 * std::f64::consts::PI * (i as f64)) / 24.0;
+        add_tracer(universe, 320.0 * f64::cos(angle), 320.0 * f64::sin(angle), 0.0, 0.0, 1.0, GOLD);
+    }
+}
+
+fn build_quadrupole(universe: &mut Universe) {
+    universe.implementation = Implementation::Verlet;
+    universe.min_interaction_distance = 10.0;
+    add_quadrupole_charges(universe);
+}
+
+fn build_ionic_lattice(universe: &mut Universe) {
+    universe.implementation = Implementation::Verlet;
+    universe.min_interaction_distance = 12.0;
+    universe.collisions_enabled = true;
+    universe.restitution = 0.8;
+    let mut options = SpawnOptions::new(64, SpawnShape::SquareLattice);
+    options.spacing = 40.0;
+    options.radius = 8.0;
+    options.charge = ValueDistribution::Alternating;
+    options.charge_value = 8.0;
+    options.velocity = VelocityDistribution::MaxwellBoltzmann;
+    options.temperature = 20.0;
+    universe.spawn_particles(&options);
+}
+
+fn build_thomson(universe: &mut Universe) {
+    universe.implementation = Implementation::Verlet;
+    universe.min_interaction_distance = 8.0;
+    universe.damping = 0.8;
+    add_ring(universe, 60, 320.0, -6.0);
+    let mut options = SpawnOptions::new(12, SpawnShape::Disc);
+    options.size = 150.0;
+    options.radius = 8.0;
+    options.charge_value = -10.0;
+    universe.spawn_particles(&options);
+}
+
+fn build_cyclotron(universe: &mut Universe) {
+    universe.implementation = Implementation::RK4;
+    universe.magnetic_field_z = 1.0;
+    // unit charge and mass: orbit radius = speed / Bz, period = 2 pi / Bz
+    for (i, speed) in [40.0, 70.0, 100.0, 130.0, 160.0].iter().enumerate() {
+        let charge = if i % 2 == 0 { 1.0 } else { -1.0 };
+        add_tracer(universe, 0.0, 0.0, 0.0, *speed, charge, charge_color(charge));
+    }
+}
+
+fn build_rutherford(universe: &mut Universe) {
+    universe.implementation = Implementation::RK4;
+    universe.min_interaction_distance = 2.0;
+    add_fixed(universe, 0.0, 0.0, 15.0, 50.0);
+    // closest head-on approach: k q Q / (m v^2 / 2), about 80 units
+    for i in 0..21 {
+        let impact = -200.0 + 20.0 * (i as f64);
+        add_tracer(universe, -600.0, impact, 150.0, 0.0, 2.0, GOLD);
+    }
+}
+
+fn build_magnet_chain(universe: &mut Universe) {
+    universe.implementation = Implementation::Verlet;
+    universe.min_interaction_distance = 15.0;
+    for i in 0..6 {
+        let x = -250.0 + 100.0 * (i as f64);
+        // slight alternating misalignment so the chain visibly snaps together
+        let y = if i % 2 == 0 { 8.0 } else { -8.0 };
+        let magnet = Magnet::new(x, y, 0.0, 60.0, 20.0, 5.0, RED, BLUE, 2.0, false);
+        universe.insert_magnet(magnet);
+    }
+}
+
+fn build_penning_trap(universe: &mut Universe) {
+    universe.implementation = Implementation::RK4;
+    universe.min_interaction_distance = 8.0;
+    universe.magnetic_field_z = 2.0;
+    // like-charged ring: its centre is a potential minimum within the plane
+    add_ring(universe, 48, 300.0, -12.0);
+    let mut options = SpawnOptions::new(6, SpawnShape::Disc);
+    options.size = 80.0;
+    options.radius = 5.0;
+    options.charge_value = -3.0;
+    options.velocity = VelocityDistribution::Uniform;
+    options.speed = 40.0;
+    universe.spawn_particles(&options);
+}
+
+fn build_plasma_blob(universe: &mut Universe) {
+    universe.implementation = Implementation::Leapfrog;
+    universe.min_interaction_distance = 8.0;
+    universe.force_backend = ForceBackend::Quadtree;
+    let mut options = SpawnOptions::new(200, SpawnShape::Gaussian);
+    options.size = 80.0;
+    options.radius = 4.0;
+    options.charge = ValueDistribution::Alternating;
+    options.charge_value = 4.0;
+    options.velocity = VelocityDistribution::MaxwellBoltzmann;
+    options.temperature = 400.0;
+    universe.spawn_particles(&options);
+}
+
+impl Universe {
+    pub(crate) fn build_preset(&self, name: &str) -> Result<Universe, SceneError> {
+        let preset = PRESETS.iter()
+            .find(|p| p.info.name == name)
+            .ok_or_else(|| SceneError::Invalid(format!("unknown preset \"{}\"", name)))?;
+        let mut universe = Universe::new_empty();
+        // continue this universe's random sequence so presets are reproducible under a seed
+        universe.rng = self.rng.clone();
+        (preset.build)(&mut universe);
+        // building is not an edit of its own; loading the preset is one undo step
+        universe.clear_undo_history();
+        universe.refresh_render_buffers();
+        Ok(universe)
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Catalog of built-in presets as [{ name, title, description }]
+    pub fn list_presets() -> JsValue {
+        let infos: Vec<&PresetInfo> = PRESETS.iter()
+            .map(|p| &p.info)
+            .collect();
+        serde_wasm_bindgen::to_value(&infos).unwrap()
+    }
+
+    pub fn get_preset_names() -> Vec<String> {
+        PRESETS.iter()
+            .map(|p| p.info.name.to_string())
+            .collect()
+    }
+
+    // Replace the scene with a named preset (undoable)
+    pub fn load_preset(&mut self, name: &str) -> Result<(), SceneError> {
+        let preset = self.build_preset(name)?;
+        self.replace_undoable(preset);
+        Ok(())
+    }
+}
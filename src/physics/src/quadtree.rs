@@ -0,0 +1,411 @@
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+
+use crate::{ parallel, Universe, Vec2 };
+use crate::fields::coulomb_field;
+
+// Padding added around the particles' bounding box when sizing the root node
+const ROOT_PADDING: f64 = 100.0;
+// Sources a node may hold before it is split
+const LEAF_CAPACITY: usize = 8;
+// Nodes this deep are never split, so coincident particles cannot subdivide forever
+const MAX_DEPTH: u32 = 32;
+// first_child of a leaf (the root is node 0 and never anyone's child)
+const LEAF: u32 = 0;
+
+// Point charge held by the tree; `index` is the particle it came from
+#[derive(Clone, Copy, Default)]
+struct QuadSource {
+    index: usize,
+    pos: Vec2,
+    charge: f64,
+}
+
+// One node of the flat tree. Its children are the four consecutive nodes starting at
+// first_child and its sources are QuadTree::sources[start..end]. Multipole moments are kept
+// about the geometric centre, up to the quadrupole, so neutral clusters (dipoles, plasmas)
+// still produce their far field.
+#[derive(Clone, Copy, Default)]
+struct Node {
+    center: Vec2,
+    dimensions: Vec2,
+    first_child: u32,
+    start: u32,
+    end: u32,
+    depth: u32,
+    // net charge (monopole moment)
+    charge: f64,
+    // total |q|: a node is skipped only when it holds no charge at all
+    abs_charge: f64,
+    // sum of q * d, with d measured from `center`
+    dipole: Vec2,
+    // in-plane part of sum of q * (3 d d^T - |d|^2 I) as [xx, xy, yy]
+    quadrupole: [f64; 3],
+}
+
+impl Node {
+    fn new(center: Vec2, dimensions: Vec2, start: usize, end: usize, depth: u32) -> Node {
+        Node {
+            center,
+            dimensions,
+            start: start as u32,
+            end: end as u32,
+            depth,
+            ..Node::default()
+        }
+    }
+
+    fn len(&self) -> usize {
+        (self.end - self.start) as usize
+    }
+
+    fn get_quadrant(&self, position: Vec2) -> usize {
+        match (position.x >= self.center.x, position.y >= self.center.y) {
+            (true, true) => 0,
+            (false, true) => 1,
+            (false, false) => 2,
+            (true, false) => 3,
+        }
+    }
+
+    // Adds a charge q at offset d from the centre to the moments
+    fn add_point_moments(&mut self, q: f64, d: Vec2) {
+        let d_sq = d.x * d.x + d.y * d.y;
+        self.charge += q;
+        self.abs_charge += q.abs();
+        self.dipole += d * q;
+        self.quadrupole[0] += q * (3.0 * d.x * d.x - d_sq);
+        self.quadrupole[1] += q * 3.0 * d.x * d.y;
+        self.quadrupole[2] += q * (3.0 * d.y * d.y - d_sq);
+    }
+
+    // Adds a child's moments, shifted from the child's centre (offset s from ours)
+    fn add_child_moments(&mut self, child: &Node) {
+        let s = child.center - self.center;
+        let (q, p) = (child.charge, child.dipole);
+        let p_dot_s = p.x * s.x + p.y * s.y;
+        let s_sq = s.x * s.x + s.y * s.y;
+        self.charge += q;
+        self.abs_charge += child.abs_charge;
+        self.dipole += p + s * q;
+        self.quadrupole[0] +=
+            child.quadrupole[0] + 6.0 * p.x * s.x - 2.0 * p_dot_s + q * (3.0 * s.x * s.x - s_sq);
+        self.quadrupole[1] += child.quadrupole[1] + 3.0 * (p.x * s.y + p.y * s.x) + q * 3.0 * s.x * s.y;
+        self.quadrupole[2] +=
+            child.quadrupole[2] + 6.0 * p.y * s.y - 2.0 * p_dot_s + q * (3.0 * s.y * s.y - s_sq);
+    }
+
+    // Field per unit charge at displacement r (= target - centre) from the node's multipole
+    // expansion: monopole, dipole and quadrupole terms of the 1/r potential
+    fn multipole_field(&self, r: Vec2, k: f64) -> Vec2 {
+        let r_sq = r.x * r.x + r.y * r.y;
+        let r_len = r_sq.sqrt();
+        let inv_r3 = 1.0 / (r_sq * r_len);
+        let inv_r5 = inv_r3 / r_sq;
+        let inv_r7 = inv_r5 / r_sq;
+        let p = self.dipole;
+        let [qxx, qxy, qyy] = self.quadrupole;
+        let p_dot_r = p.x * r.x + p.y * r.y;
+        let q_r = Vec2::new(qxx * r.x + qxy * r.y, qxy * r.x + qyy * r.y);
+        let r_q_r = r.x * q_r.x + r.y * q_r.y;
+
+        let monopole = r * (self.charge * inv_r3);
+        let dipole = r * (3.0 * p_dot_r * inv_r5) - p * inv_r3;
+        let quadrupole = r * (2.5 * r_q_r * inv_r7) - q_r * inv_r5;
+        (monopole + dipole + quadrupole) * k
+    }
+}
+
+// Barnes-Hut quadtree stored as a flat arena of nodes. It is rebuilt in place for every
+// force evaluation so its buffers are reused across substeps, and both building and walking
+// it are iterative.
+#[derive(Clone, Default)]
+pub(crate) struct QuadTree {
+    nodes: Vec<Node>,
+    // ordered so that every node's sources are contiguous
+    sources: Vec<QuadSource>,
+    // partition buffer for split()
+    scratch: Vec<QuadSource>,
+}
+
+// Rectangle of one node, for the quadtree overlay
+#[derive(Serialize)]
+struct NodeRect {
+    center: Vec2,
+    dimensions: Vec2,
+}
+
+impl QuadTree {
+    fn rebuild(&mut self, sources: impl Iterator<Item = QuadSource>) {
+        self.sources.clear();
+        self.sources.extend(sources);
+        self.nodes.clear();
+        let (dimensions, center) = bounding_box(self.sources.iter().map(|s| s.pos));
+        self.nodes.push(Node::new(center, dimensions, 0, self.sources.len(), 0));
+
+        // children are appended after their parent, so one forward pass splits everything
+        let mut next = 0;
+        while next < self.nodes.len() {
+            let node = self.nodes[next];
+            if node.len() > LEAF_CAPACITY && node.depth < MAX_DEPTH {
+                self.split(next);
+            }
+            next += 1;
+        }
+
+        // and one backward pass reaches every child before its parent
+        for i in (0..self.nodes.len()).rev() {
+            let mut node = self.nodes[i];
+            if node.first_child == LEAF {
+                for source in &self.sources[node.start as usize..node.end as usize] {
+                    node.add_point_moments(source.charge, source.pos - node.center);
+                }
+            } else {
+                let first = node.first_child as usize;
+                for child in &self.nodes[first..first + 4] {
+                    node.add_child_moments(child);
+                }
+            }
+            self.nodes[i] = node;
+        }
+    }
+
+    // Groups a node's sources by quadrant (counting sort) and appends its four children
+    fn split(&mut self, index: usize) {
+        let node = self.nodes[index];
+        let (start, end) = (node.start as usize, node.end as usize);
+
+        let mut counts = [0usize; 4];
+        for source in &self.sources[start..end] {
+            counts[node.get_quadrant(source.pos)] += 1;
+        }
+        let mut offsets = [0usize; 4];
+        for q in 1..4 {
+            offsets[q] = offsets[q - 1] + counts[q - 1];
+        }
+        let child_starts = offsets;
+        self.scratch.clear();
+        self.scratch.resize(end - start, QuadSource::default());
+        for source in &self.sources[start..end] {
+            let q = node.get_quadrant(source.pos);
+            self.scratch[offsets[q]] = *source;
+            offsets[q] += 1;
+        }
+        self.sources[start..end].copy_from_slice(&self.scratch);
+
+        let half = node.dimensions * 0.5;
+        let quarter = node.dimensions * 0.25;
+        let signs = [(1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)];
+        self.nodes[index].first_child = self.nodes.len() as u32;
+        for q in 0..4 {
+            let center = node.center + Vec2::new(signs[q].0 * quarter.x, signs[q].1 * quarter.y);
+            let child_start = start + child_starts[q];
+            let child_end = child_start + counts[q];
+            self.nodes.push(Node::new(center, half, child_start, child_end, node.depth + 1));
+        }
+    }
+
+    // Field per unit charge at `pos` from every source except particle `exclude`. A node is
+    // replaced by its expansion when size / distance < theta and the target is outside the
+    // softening radius; otherwise it is opened.
+    fn field_at(&self, pos: Vec2, exclude: usize, theta: f64, k: f64, min_dist: f64) -> Vec2 {
+        let mut field = Vec2::new(0.0, 0.0);
+        if self.nodes.is_empty() {
+            return field;
+        }
+        // opening a node leaves at most three siblings waiting per level
+        let mut stack = [0u32; 3 * (MAX_DEPTH as usize) + 4];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let node = &self.nodes[stack[top] as usize];
+            if node.abs_charge == 0.0 {
+                continue;
+            }
+            if node.first_child == LEAF {
+                for source in &self.sources[node.start as usize..node.end as usize] {
+                    if source.index != exclude {
+                        field += coulomb_field(k, source.charge, pos - source.pos, min_dist);
+                    }
+                }
+                continue;
+            }
+            let r = pos - node.center;
+            let distance = r.magnitude();
+            let size = f64::max(node.dimensions.x, node.dimensions.y);
+            if size < theta * distance && distance - 0.5 * size * std::f64::consts::SQRT_2 > min_dist {
+                field += node.multipole_field(r, k);
+            } else {
+                for child in 0..4 {
+                    stack[top] = node.first_child + child;
+                    top += 1;
+                }
+            }
+        }
+        field
+    }
+
+    fn rects(&self) -> Vec<NodeRect> {
+        self.nodes
+            .iter()
+            .map(|n| NodeRect { center: n.center, dimensions: n.dimensions })
+            .collect()
+    }
+}
+
+// (dimensions, center) of a padded box around the points
+fn bounding_box(points: impl Iterator<Item = Vec2>) -> (Vec2, Vec2) {
+    let mut min = Vec2::new(f64::MAX, f64::MAX);
+    let mut max = Vec2::new(f64::MIN, f64::MIN);
+    for p in points {
+        min.x = min.x.min(p.x);
+        min.y = min.y.min(p.y);
+        max.x = max.x.max(p.x);
+        max.y = max.y.max(p.y);
+    }
+    if min.x > max.x {
+        // no points
+        return (Vec2::new(2.0 * ROOT_PADDING, 2.0 * ROOT_PADDING), Vec2::new(0.0, 0.0));
+    }
+    min = min - Vec2::new(ROOT_PADDING, ROOT_PADDING);
+    max += Vec2::new(ROOT_PADDING, ROOT_PADDING);
+    let dimensions = max - min;
+    (dimensions, min + dimensions * 0.5)
+}
+
+impl Universe {
+    // Rebuilds `tree` over `positions` (particle i at positions[i]); tracers never act as
+    // sources, so they are left out
+    fn rebuild_quadtree(&self, tree: &mut QuadTree, positions: &[Vec2]) {
+        tree.rebuild(
+            self.particles
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| !p.tracer)
+                .map(|(index, p)| QuadSource { index, pos: positions[index], charge: p.charge })
+        );
+    }
+
+    // Barnes-Hut Coulomb accelerations with particle i at positions[i]. Works for any
+    // position array, so every integrator stage can use it.
+    pub(crate) fn quadtree_accelerations(&self, positions: &[Vec2]) -> Vec<Vec2> {
+        let mut tree = self.quadtree.borrow_mut();
+        self.rebuild_quadtree(&mut tree, positions);
+        let tree = &*tree;
+        let theta = self.quadtree_theta;
+        let (k, min_dist) = (self.coulomb_constant, self.min_interaction_distance);
+        let fields = parallel::map(positions.len(), |i| {
+            tree.field_at(positions[i], i, theta, k, min_dist)
+        });
+        self.fields_to_accelerations(fields)
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Node rectangles [{ center, dimensions }] of a tree over the current positions, for the
+    // quadtree overlay
+    pub fn get_quadtree(&self) -> JsValue {
+        let positions: Vec<Vec2> = self.particles
+            .iter()
+            .map(|p| p.pos)
+            .collect();
+        let mut tree = self.quadtree.borrow_mut();
+        self.rebuild_quadtree(&mut tree, &positions);
+        serde_wasm_bindgen::to_value(&tree.rects()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ ForceBackend, Implementation };
+    use crate::spawn::{ SpawnOptions, SpawnShape, ValueDistribution };
+
+    fn blob(backend: ForceBackend, implementation: Implementation) -> Universe {
+        let mut universe = Universe::new_empty();
+        universe.set_seed(3);
+        let mut options = SpawnOptions::new(300, SpawnShape::Gaussian);
+        options.size = 300.0;
+        options.charge = ValueDistribution::Alternating;
+        options.radius = 2.0;
+        universe.spawn_particles(&options);
+        universe.set_force_backend(backend);
+        universe.set_implementation(implementation);
+        // a coarse opening angle so the tree visibly differs from the direct sum
+        universe.set_quadtree_theta(1.0);
+        universe
+    }
+
+    #[test]
+    fn every_integrator_steps_through_the_tree() {
+        for (name, implementation) in [
+            ("Euler", Implementation::Euler),
+            ("RK4", Implementation::RK4),
+            ("Verlet", Implementation::Verlet),
+            ("Leapfrog", Implementation::Leapfrog),
+        ] {
+            let mut tree = blob(ForceBackend::Quadtree, implementation);
+            let mut direct = blob(ForceBackend::Direct, implementation);
+            tree.time_step(0.016);
+            direct.time_step(0.016);
+
+            assert!(tree.quadtree.borrow().nodes.len() > 1, "{}", name);
+            assert!(direct.quadtree.borrow().nodes.is_empty(), "{}", name);
+            let mut max_diff: f64 = 0.0;
+            for (a, b) in tree.particles.iter().zip(&direct.particles) {
+                max_diff = max_diff.max(a.pos.distance_from(b.pos));
+            }
+            assert!(max_diff > 0.0, "{} ignored the tree", name);
+            assert!(max_diff < 1e-3, "{}: {}", name, max_diff);
+        }
+    }
+
+    #[test]
+    fn distant_neutral_dipoles_keep_their_far_field() {
+        let mut universe = Universe::new_empty();
+        // 16 aligned +/- pairs: no net charge, but a net dipole along x
+        for i in 0..16 {
+            let y = (i as f64) * 10.0 - 75.0;
+            universe.add_particle_simple(5.0, y, 0.0, 0.0, 1.0);
+            universe.add_particle_simple(-5.0, y, 0.0, 0.0, -1.0);
+        }
+        universe.add_tracer(3000.0, 400.0, 0.0, 0.0, 1.0);
+        universe.set_quadtree_theta(0.5);
+        let positions: Vec<Vec2> = universe.particles
+            .iter()
+            .map(|p| p.pos)
+            .collect();
+
+        let probe = positions.len() - 1;
+        let tree = universe.quadtree_accelerations(&positions)[probe];
+        let direct = universe.direct_accelerations(&positions)[probe];
+        assert!(direct.magnitude() > 0.0);
+        let error = (tree - direct).magnitude() / direct.magnitude();
+        assert!(error < 1e-3, "{}", error);
+    }
+
+    #[test]
+    fn coincident_particles_stop_at_the_depth_cap() {
+        let mut universe = Universe::new_empty();
+        for i in 0..(4 * LEAF_CAPACITY) {
+            let charge = if i % 2 == 0 { 1.0 } else { -2.0 };
+            universe.add_particle_simple(12.5, -7.0, 0.0, 0.0, charge);
+        }
+        let positions: Vec<Vec2> = universe.particles
+            .iter()
+            .map(|p| p.pos)
+            .collect();
+
+        let accelerations = universe.quadtree_accelerations(&positions);
+        assert!(accelerations.iter().all(|a| a.x.is_finite() && a.y.is_finite()));
+        let tree = universe.quadtree.borrow();
+        let deepest = tree.nodes
+            .iter()
+            .map(|n| n.depth)
+            .max();
+        assert_eq!(deepest, Some(MAX_DEPTH));
+        // all of them end up together in one capped leaf
+        assert!(tree.nodes.iter().any(|n| n.depth == MAX_DEPTH && n.len() == 4 * LEAF_CAPACITY));
+    }
+}
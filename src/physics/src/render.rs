@@ -0,0 +1,249 @@
+use js_sys::{ Float64Array, Uint32Array };
+use wasm_bindgen::prelude::*;
+
+use crate::Universe;
+
+// Bit flags stored per particle in `RenderBuffers::flags`
+const RENDER_FLAG_FIXED: u32 = 1;
+const RENDER_FLAG_TRACER: u32 = 2;
+
+// Structure-of-arrays copy of everything the renderer draws. It is owned by the engine and
+// refilled in place, so once capacity has grown no frame allocates.
+#[derive(Clone, Default)]
+pub(crate) struct RenderBuffers {
+    // Particles
+    positions: Vec<f64>, // interleaved x, y
+    velocities: Vec<f64>, // interleaved x, y
+    radii: Vec<f64>,
+    charges: Vec<f64>,
+    colors: Vec<u32>,
+    flags: Vec<u32>,
+    // Trails, oldest point first; empty while trails are hidden
+    trail_points: Vec<f64>, // interleaved x, y
+    trail_colors: Vec<u32>,
+    trail_offsets: Vec<u32>, // first point of each particle's trail, then the total count
+    // Magnets
+    magnet_poses: Vec<f64>, // interleaved x, y, angle
+    magnet_dimensions: Vec<f64>, // interleaved size, thickness
+    magnet_strengths: Vec<f64>,
+    magnet_colors: Vec<u32>, // interleaved north, south
+}
+
+impl RenderBuffers {
+    pub(crate) fn refresh(&mut self, universe: &Universe) {
+        self.positions.clear();
+        self.velocities.clear();
+        self.radii.clear();
+        self.charges.clear();
+        self.colors.clear();
+        self.flags.clear();
+        for p in &universe.particles {
+            self.positions.extend_from_slice(&[p.pos.x, p.pos.y]);
+            self.velocities.extend_from_slice(&[p.vel.x, p.vel.y]);
+            self.radii.push(p.radius as f64);
+            self.charges.push(p.charge);
+            self.colors.push(p.color);
+            let mut flags = 0;
+            if p.fixed {
+                flags |= RENDER_FLAG_FIXED;
+            }
+            if p.tracer {
+                flags |= RENDER_FLAG_TRACER;
+            }
+            self.flags.push(flags);
+        }
+
+        self.trail_points.clear();
+        self.trail_colors.clear();
+        self.trail_offsets.clear();
+        if universe.show_trails {
+            for i in 0..universe.particles.len() {
+                self.trail_offsets.push(self.trail_colors.len() as u32);
+                universe.trails.for_each_point(i, |point| {
+                    self.trail_points.extend_from_slice(&[point.pos.x, point.pos.y]);
+                    self.trail_colors.push(point.color);
+                });
+            }
+            self.trail_offsets.push(self.trail_colors.len() as u32);
+        }
+
+        self.magnet_poses.clear();
+        self.magnet_dimensions.clear();
+        self.magnet_strengths.clear();
+        self.magnet_colors.clear();
+        for m in &universe.magnets {
+            self.magnet_poses.extend_from_slice(&[m.pos.x, m.pos.y, m.angle]);
+            self.magnet_dimensions.extend_from_slice(&[m.size as f64, m.thickness as f64]);
+            self.magnet_strengths.push(m.strength);
+            self.magnet_colors.extend_from_slice(&[m.color_north, m.color_south]);
+        }
+    }
+}
+
+// View of `values` in wasm memory, valid until the next call into the engine
+fn f64_view(values: &[f64]) -> Float64Array {
+    // SAFETY: the view aliases `values` without copying. JS must drop it before calling into
+    // the engine again, since any call may refill (reallocate) the buffer or grow wasm memory
+    // and leave the view pointing at freed or detached memory.
+    unsafe { Float64Array::view(values) }
+}
+
+fn u32_view(values: &[u32]) -> Uint32Array {
+    // SAFETY: as for f64_view, the view is invalidated by the next call into the engine
+    unsafe { Uint32Array::view(values) }
+}
+
+// Zero-copy views into wasm memory. A view is only valid until the next call into the engine
+// (a refresh may reallocate a buffer and any allocation may grow wasm memory), so re-acquire
+// the views each frame after `time_step` / `refresh_render_buffers` instead of caching them.
+#[wasm_bindgen]
+impl Universe {
+    // Refill the render buffers from the current state (time_step does this automatically;
+    // call it after edits made while paused)
+    pub fn refresh_render_buffers(&mut self) {
+        // the buffers are moved out so they can be filled from the rest of the universe
+        let mut render = std::mem::take(&mut self.render);
+        render.refresh(self);
+        self.render = render;
+    }
+
+    pub fn get_render_particle_count(&self) -> usize {
+        self.render.radii.len()
+    }
+
+    pub fn get_render_magnet_count(&self) -> usize {
+        self.render.magnet_strengths.len()
+    }
+
+    pub fn render_positions(&self) -> Float64Array {
+        f64_view(&self.render.positions)
+    }
+
+    pub fn render_velocities(&self) -> Float64Array {
+        f64_view(&self.render.velocities)
+    }
+
+    pub fn render_radii(&self) -> Float64Array {
+        f64_view(&self.render.radii)
+    }
+
+    pub fn render_charges(&self) -> Float64Array {
+        f64_view(&self.render.charges)
+    }
+
+    pub fn render_colors(&self) -> Uint32Array {
+        u32_view(&self.render.colors)
+    }
+
+    // Per particle: bit 0 = fixed, bit 1 = tracer
+    pub fn render_flags(&self) -> Uint32Array {
+        u32_view(&self.render.flags)
+    }
+
+    pub fn render_trail_points(&self) -> Float64Array {
+        f64_view(&self.render.trail_points)
+    }
+
+    pub fn render_trail_colors(&self) -> Uint32Array {
+        u32_view(&self.render.trail_colors)
+    }
+
+    // Trail of particle i is points trail_offsets[i]..trail_offsets[i + 1]; empty when trails
+    // are hidden
+    pub fn render_trail_offsets(&self) -> Uint32Array {
+        u32_view(&self.render.trail_offsets)
+    }
+
+    pub fn render_magnet_poses(&self) -> Float64Array {
+        f64_view(&self.render.magnet_poses)
+    }
+
+    pub fn render_magnet_dimensions(&self) -> Float64Array {
+        f64_view(&self.render.magnet_dimensions)
+    }
+
+    pub fn render_magnet_strengths(&self) -> Float64Array {
+        f64_view(&self.render.magnet_strengths)
+    }
+
+    pub fn render_magnet_colors(&self) -> Uint32Array {
+        u32_view(&self.render.magnet_colors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Universe;
+
+    fn scene() -> Universe {
+        let mut universe = Universe::new_empty();
+        universe.add_particle(-50.0, 10.0, 1.0, 2.0, 5.0, 1.0, 0x112233, 2.0);
+        universe.add_tracer(40.0, -20.0, -3.0, 0.5, -1.0);
+        universe.update_particle_fixed(0, true);
+        universe.add_magnet(0.0, 200.0, 0.5, 60.0, 10.0, 1.0, 0xff0000, 0x0000ff, 3.0, true);
+        universe
+    }
+
+    #[test]
+    fn buffers_hold_one_entry_per_particle_and_magnet() {
+        let mut universe = scene();
+        universe.refresh_render_buffers();
+        let render = &universe.render;
+        assert_eq!(universe.get_render_particle_count(), 2);
+        assert_eq!(universe.get_render_magnet_count(), 1);
+        assert_eq!(render.positions, vec![-50.0, 10.0, 40.0, -20.0]);
+        assert_eq!(render.velocities, vec![1.0, 2.0, -3.0, 0.5]);
+        assert_eq!(render.radii[0], 5.0);
+        assert_eq!(render.charges, vec![2.0, -1.0]);
+        assert_eq!(render.colors[0], 0x112233);
+        assert_eq!(render.flags, vec![super::RENDER_FLAG_FIXED, super::RENDER_FLAG_TRACER]);
+        assert_eq!(render.magnet_poses, vec![0.0, 200.0, 0.5]);
+        assert_eq!(render.magnet_dimensions, vec![60.0, 10.0]);
+        assert_eq!(render.magnet_strengths, vec![3.0]);
+        assert_eq!(render.magnet_colors, vec![0xff0000, 0x0000ff]);
+    }
+
+    #[test]
+    fn trails_are_packed_by_offset() {
+        let mut universe = scene();
+        universe.set_trail_interval(1);
+        for _ in 0..5 {
+            universe.time_step(0.016);
+        }
+        let render = &universe.render;
+        assert_eq!(render.trail_offsets.len(), 3);
+        assert_eq!(render.trail_offsets[0], 0);
+        assert_eq!(*render.trail_offsets.last().unwrap() as usize, render.trail_colors.len());
+        assert_eq!(render.trail_points.len(), 2 * render.trail_colors.len());
+        for i in 0..2 {
+            let trail = universe.trails.get(i);
+            let (start, end) = (render.trail_offsets[i] as usize, render.trail_offsets[i + 1] as usize);
+            assert_eq!(end - start, 5);
+            for (point, packed) in trail.iter().zip(start..end) {
+                assert_eq!(render.trail_points[2 * packed], point.pos.x);
+                assert_eq!(render.trail_points[2 * packed + 1], point.pos.y);
+                assert_eq!(render.trail_colors[packed], point.color);
+            }
+        }
+
+        universe.set_show_trails(false);
+        universe.refresh_render_buffers();
+        assert!(universe.render.trail_points.is_empty());
+        assert!(universe.render.trail_offsets.is_empty());
+    }
+
+    #[test]
+    fn refresh_follows_edits_made_while_paused() {
+        let mut universe = scene();
+        universe.set_is_paused(true);
+        universe.refresh_render_buffers();
+        universe.remove_particle(0);
+        universe.remove_magnet(0);
+        // stale until refreshed
+        assert_eq!(universe.get_render_particle_count(), 2);
+        universe.refresh_render_buffers();
+        assert_eq!(universe.get_render_particle_count(), 1);
+        assert_eq!(universe.get_render_magnet_count(), 0);
+        assert_eq!(universe.render.positions, vec![40.0, -20.0]);
+    }
+}
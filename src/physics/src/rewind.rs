@@ -0,0 +1,304 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::Universe;
+
+const DEFAULT_KEYFRAME_INTERVAL: usize = 60;
+const DEFAULT_MEMORY_BUDGET: usize = 32 * 1024 * 1024;
+// Bytes charged per logged step (dt and the simulation time after it)
+const LOGGED_STEP_BYTES: usize = 16;
+
+struct Keyframe {
+    // absolute number of steps taken before this state
+    step: usize,
+    time: f64,
+    snapshot: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+struct LoggedStep {
+    dt: f64,
+    // simulation time after the step
+    time: f64,
+}
+
+// Rewind history: keyframe snapshots taken during time_step plus the dt of every step since
+// the oldest keyframe. Any recorded moment is reached by restoring the nearest earlier
+// keyframe and replaying the logged steps, which reproduces the original run exactly because
+// stepping is deterministic. Edits mark the timeline dirty so the next step takes a keyframe
+// that includes them.
+pub(crate) struct Timeline {
+    enabled: bool,
+    keyframes: VecDeque<Keyframe>,
+    steps: VecDeque<LoggedStep>,
+    // absolute number of the step at steps[0]
+    first_step: usize,
+    // absolute number of steps the current state is at (less than the end after a seek)
+    cursor: usize,
+    keyframe_interval: usize,
+    memory_budget: usize,
+    memory_used: usize,
+    dirty: bool,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline {
+            enabled: true,
+            keyframes: VecDeque::new(),
+            steps: VecDeque::new(),
+            first_step: 0,
+            cursor: 0,
+            keyframe_interval: DEFAULT_KEYFRAME_INTERVAL,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+            memory_used: 0,
+            dirty: false,
+        }
+    }
+}
+
+// Snapshots are not cheap to copy and a cloned universe should start its own timeline
+impl Clone for Timeline {
+    fn clone(&self) -> Self {
+        Timeline {
+            enabled: self.enabled,
+            keyframe_interval: self.keyframe_interval,
+            memory_budget: self.memory_budget,
+            ..Timeline::default()
+        }
+    }
+}
+
+impl Timeline {
+    fn end(&self) -> usize {
+        self.first_step + self.steps.len()
+    }
+
+    fn clear(&mut self) {
+        self.keyframes.clear();
+        self.steps.clear();
+        self.first_step = self.cursor;
+        self.memory_used = 0;
+        self.dirty = false;
+    }
+
+    // Forget everything after the cursor (stepping on from an earlier moment starts a new branch)
+    fn truncate_to_cursor(&mut self) {
+        while self.keyframes.back().is_some_and(|k| k.step > self.cursor) {
+            let keyframe = self.keyframes.pop_back().unwrap();
+            self.memory_used -= keyframe.snapshot.len();
+        }
+        let kept = self.cursor.saturating_sub(self.first_step).min(self.steps.len());
+        self.memory_used -= (self.steps.len() - kept) * LOGGED_STEP_BYTES;
+        self.steps.truncate(kept);
+    }
+
+    fn push_keyframe(&mut self, time: f64, snapshot: Vec<u8>) {
+        if self.keyframes.back().is_some_and(|k| k.step == self.cursor) {
+            let replaced = self.keyframes.pop_back().unwrap();
+            self.memory_used -= replaced.snapshot.len();
+        }
+        if self.keyframes.is_empty() {
+            self.first_step = self.cursor;
+        }
+        self.memory_used += snapshot.len();
+        self.keyframes.push_back(Keyframe {
+            step: self.cursor,
+            time,
+            snapshot,
+        });
+    }
+
+    fn push_step(&mut self, dt: f64, time: f64) {
+        self.steps.push_back(LoggedStep { dt, time });
+        self.memory_used += LOGGED_STEP_BYTES;
+        self.cursor += 1;
+    }
+
+    // Drops the oldest keyframes (and the steps only they could replay) until within budget
+    fn enforce_budget(&mut self) {
+        while self.memory_used > self.memory_budget && self.keyframes.len() > 1 {
+            let dropped = self.keyframes.pop_front().unwrap();
+            self.memory_used -= dropped.snapshot.len();
+            let new_first = self.keyframes[0].step;
+            let removed = (new_first - self.first_step).min(self.steps.len());
+            self.steps.drain(..removed);
+            self.memory_used -= removed * LOGGED_STEP_BYTES;
+            self.first_step = new_first;
+        }
+    }
+
+    fn start_time(&self) -> Option<f64> {
+        self.keyframes.front().map(|k| k.time)
+    }
+
+    fn end_time(&self) -> Option<f64> {
+        match self.steps.back() {
+            Some(step) => Some(step.time),
+            None => self.keyframes.back().map(|k| k.time),
+        }
+    }
+}
+
+impl Universe {
+    // Called whenever the state changes outside of stepping
+    pub(crate) fn mark_timeline_dirty(&mut self) {
+        self.timeline.dirty = true;
+    }
+
+    // `advance` plus keyframe bookkeeping
+    pub(crate) fn advance_recorded(&mut self, dt: f64) -> u8 {
+        let idle = self.is_paused || (self.particles.is_empty() && self.magnets.is_empty());
+        if !self.timeline.enabled || idle {
+            return self.advance(dt);
+        }
+
+        if self.timeline.cursor != self.timeline.end() {
+            self.timeline.truncate_to_cursor();
+        }
+        // time jumped backwards (reset, loaded scene): the old timeline no longer applies
+        if self.timeline.end_time().is_some_and(|end| self.time < end) {
+            self.timeline.clear();
+        }
+        let keyframe_due = match self.timeline.keyframes.back() {
+            Some(keyframe) => self.timeline.cursor - keyframe.step >= self.timeline.keyframe_interval,
+            None => true,
+        };
+        if keyframe_due || self.timeline.dirty {
+            let snapshot = self.encode_snapshot(false);
+            self.timeline.push_keyframe(self.time, snapshot);
+            self.timeline.dirty = false;
+        }
+
+        let result = self.advance(dt);
+        self.timeline.push_step(dt, self.time);
+        self.timeline.enforce_budget();
+        result
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Simulation time, advanced by every time_step
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    // Jump to the latest recorded moment at or before `t` (clamped to history_range()) and
+    // return its time. Trails restart from the keyframe used; pause state and undo history
+    // are kept. Stepping from an earlier moment discards the recorded future.
+    pub fn seek(&mut self, t: f64) -> f64 {
+        let keyframe = match
+            self.timeline.keyframes
+                .iter()
+                .rev()
+                .find(|k| k.time <= t)
+                .or(self.timeline.keyframes.front())
+        {
+            Some(keyframe) => keyframe,
+            None => {
+                return self.time;
+            }
+        };
+        let restored = match Universe::decode_snapshot(&keyframe.snapshot) {
+            Ok(universe) => universe,
+            Err(_) => {
+                return self.time;
+            }
+        };
+        let mut step = keyframe.step;
+
+        let is_paused = self.is_paused;
+        self.replace_state(restored);
+
+        while step < self.timeline.end() {
+            let logged = self.timeline.steps[step - self.timeline.first_step];
+            if logged.time > t {
+                break;
+            }
+            self.advance(logged.dt);
+            step += 1;
+        }
+        self.timeline.cursor = step;
+        self.timeline.dirty = false;
+        self.is_paused = is_paused;
+        self.refresh_render_buffers();
+        self.time
+    }
+
+    // [start, end] of the recorded history in simulation time, or empty when nothing is recorded
+    pub fn history_range(&self) -> Vec<f64> {
+        match (self.timeline.start_time(), self.timeline.end_time()) {
+            (Some(start), Some(end)) => vec![start, end],
+            _ => vec![],
+        }
+    }
+
+    pub fn clear_history(&mut self) {
+        self.timeline.clear();
+    }
+
+    pub fn set_history_enabled(&mut self, enabled: bool) {
+        self.timeline.enabled = enabled;
+        if !enabled {
+            self.timeline.clear();
+        }
+    }
+
+    pub fn get_history_enabled(&self) -> bool {
+        self.timeline.enabled
+    }
+
+    // Memory budget for keyframes and the step log, in bytes; the oldest history is dropped
+    // to stay within it
+    pub fn set_history_memory_budget(&mut self, bytes: usize) {
+        self.timeline.memory_budget = bytes;
+        self.timeline.enforce_budget();
+    }
+
+    pub fn get_history_memory_budget(&self) -> usize {
+        self.timeline.memory_budget
+    }
+
+    pub fn get_history_memory_used(&self) -> usize {
+        self.timeline.memory_used
+    }
+
+    // Steps between keyframes: fewer means faster seeks but more memory per second of history
+    pub fn set_keyframe_interval(&mut self, steps: usize) {
+        self.timeline.keyframe_interval = steps.max(1);
+    }
+
+    pub fn get_keyframe_interval(&self) -> usize {
+        self.timeline.keyframe_interval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Universe;
+
+    #[test]
+    fn seek_reproduces_recorded_state_bit_for_bit() {
+        let mut universe = Universe::new_empty();
+        universe.add_particle_simple(-40.0, 0.0, 0.0, 3.0, 1.0);
+        universe.add_particle_simple(40.0, 0.0, 0.0, -3.0, -1.0);
+        universe.add_particle_simple(0.0, 25.0, 2.0, 0.0, 2.0);
+        universe.set_history_enabled(true);
+        // the target falls between keyframes, so seeking has to replay logged steps
+        universe.set_keyframe_interval(7);
+
+        let mut recorded = None;
+        for step in 0..40 {
+            universe.time_step(0.016);
+            if step == 23 {
+                recorded = Some((universe.get_time(), universe.snapshot()));
+            }
+        }
+        let (time, snapshot) = recorded.unwrap();
+
+        assert_eq!(universe.seek(time).to_bits(), time.to_bits());
+        assert!(universe.snapshot() == snapshot);
+    }
+}
@@ -0,0 +1,216 @@
+use rand::{ Rng, RngCore };
+use serde::{ Serialize, Deserialize };
+use wasm_bindgen::prelude::*;
+
+use crate::{ Universe, Vec2 };
+
+// xoshiro256** generator owned by the Universe. Its whole state is serialized with the
+// universe (scenes and snapshots), so a restored universe draws the same numbers as the
+// original, and the same seed always produces the same sequence on every platform.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(into = "SimRngRepr", try_from = "SimRngRepr")]
+pub(crate) struct SimRng {
+    seed: u64,
+    state: [u64; 4],
+}
+
+// Serialized form: 64-bit words as hex strings, since JS numbers cannot hold them exactly
+#[derive(Serialize, Deserialize)]
+struct SimRngRepr {
+    seed: String,
+    state: [String; 4],
+}
+
+impl From<SimRng> for SimRngRepr {
+    fn from(rng: SimRng) -> Self {
+        SimRngRepr {
+            seed: format!("{:016x}", rng.seed),
+            state: rng.state.map(|word| format!("{:016x}", word)),
+        }
+    }
+}
+
+impl TryFrom<SimRngRepr> for SimRng {
+    type Error = String;
+
+    fn try_from(repr: SimRngRepr) -> Result<Self, Self::Error> {
+        let parse = |text: &str| u64::from_str_radix(text, 16).map_err(|e| e.to_string());
+        let mut state = [0; 4];
+        for (word, text) in state.iter_mut().zip(&repr.state) {
+            *word = parse(text)?;
+        }
+        // all zeros is a fixed point of xoshiro: every draw after it would be 0
+        if state == [0; 4] {
+            return Err("random generator state must not be all zero".to_string());
+        }
+        Ok(SimRng {
+            seed: parse(&repr.seed)?,
+            state,
+        })
+    }
+}
+
+fn splitmix64(x: &mut u64) -> u64 {
+    *x = x.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *x;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl SimRng {
+    pub(crate) fn from_seed(seed: u64) -> SimRng {
+        let mut x = seed;
+        SimRng {
+            seed,
+            state: [splitmix64(&mut x), splitmix64(&mut x), splitmix64(&mut x), splitmix64(&mut x)],
+        }
+    }
+
+    // Seeded from the OS; the only nondeterministic input to the engine
+    pub(crate) fn from_entropy() -> SimRng {
+        SimRng::from_seed(rand::random())
+    }
+}
+
+impl Default for SimRng {
+    fn default() -> Self {
+        SimRng::from_entropy()
+    }
+}
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        for chunk in dst.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+impl Universe {
+    // Uniform in [min, max); returns min for an empty or inverted range
+    pub(crate) fn random_between(&mut self, min: f64, max: f64) -> f64 {
+        if max > min {
+            self.rng.random_range(min..max)
+        } else {
+            min
+        }
+    }
+
+    pub(crate) fn random_index(&mut self, len: usize) -> usize {
+        self.rng.random_range(0..len)
+    }
+
+    // Standard normal sample (Box-Muller)
+    pub(crate) fn random_gaussian(&mut self) -> f64 {
+        let u1: f64 = 1.0 - self.rng.random::<f64>();
+        let u2: f64 = self.rng.random();
+        f64::sqrt(-2.0 * u1.ln()) * f64::cos(2.0 * std::f64::consts::PI * u2)
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Restart the random sequence from `seed` (reproducible runs for bug reports and tests)
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = SimRng::from_seed(seed);
+        self.mark_timeline_dirty();
+    }
+
+    // Seed the current random sequence was started from
+    pub fn get_seed(&self) -> u64 {
+        self.rng.seed
+    }
+
+    // Uniform random number in [min, max) from the universe's generator, for UI helpers that
+    // should stay reproducible under a fixed seed
+    pub fn random_range(&mut self, min: f64, max: f64) -> f64 {
+        self.random_between(min, max)
+    }
+
+    // Random point in the spawn square (±spawn_range / 2 around the origin)
+    pub fn random_spawn_position(&mut self) -> Vec2 {
+        let half = self.spawn_range * 0.5;
+        Vec2::new(self.random_between(-half, half), self.random_between(-half, half))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn::{ SpawnOptions, SpawnShape, ValueDistribution, VelocityDistribution };
+
+    fn seeded_run(seed: u64) -> Vec<u8> {
+        let mut universe = Universe::new_empty();
+        universe.set_seed(seed);
+        let mut options = SpawnOptions::new(40, SpawnShape::Disc);
+        options.size = 300.0;
+        options.velocity = VelocityDistribution::MaxwellBoltzmann;
+        options.temperature = 50.0;
+        options.charge = ValueDistribution::Gaussian;
+        options.charge_spread = 2.0;
+        universe.spawn_particles(&options);
+        universe.random_spawn_position();
+        for _ in 0..20 {
+            universe.time_step(0.016);
+        }
+        universe.snapshot()
+    }
+
+    #[test]
+    fn same_scene_and_seed_reproduce_the_run() {
+        assert!(seeded_run(42) == seeded_run(42));
+        assert!(seeded_run(42) != seeded_run(43));
+    }
+
+    #[test]
+    fn set_seed_restarts_the_sequence() {
+        let mut universe = Universe::new_empty();
+        universe.set_seed(9);
+        let first: Vec<f64> = (0..5).map(|_| universe.random_range(0.0, 1.0)).collect();
+        universe.set_seed(9);
+        let again: Vec<f64> = (0..5).map(|_| universe.random_range(0.0, 1.0)).collect();
+        assert_eq!(first, again);
+        assert_eq!(universe.get_seed(), 9);
+    }
+
+    #[test]
+    fn state_round_trips_through_hex() {
+        let mut rng = SimRng::from_seed(u64::MAX);
+        rng.next_u64();
+        let value = serde_json::to_value(&rng).unwrap();
+        assert_eq!(value["seed"], "ffffffffffffffff");
+        assert!(value["state"].as_array().unwrap().iter().all(|w| w.as_str().unwrap().len() == 16));
+
+        let mut restored: SimRng = serde_json::from_value(value).unwrap();
+        assert!(restored == rng);
+        assert_eq!(restored.next_u64(), rng.next_u64());
+    }
+
+    #[test]
+    fn rejects_bad_states() {
+        let zero = "0000000000000000";
+        let json = serde_json::json!({ "seed": "1", "state": [zero, zero, zero, zero] });
+        assert!(serde_json::from_value::<SimRng>(json).is_err());
+        let json = serde_json::json!({ "seed": "1", "state": ["xyz", "1", "2", "3"] });
+        assert!(serde_json::from_value::<SimRng>(json).is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::Universe;
+
+// Scene files are JSON envelopes: { "format": SCENE_FORMAT, "version": N, "universe": {...} }
+pub const SCENE_FORMAT: &str = "electromagnetism-scene";
+// Version history:
+//   0 - raw `Universe::get_data()` output with no envelope (quadtree cache and per-particle
+//       trails included, no entity ids)
+//   1 - versioned envelope; derived data (quadtree, trails, render buffers) dropped
+//   2 - `use_quadtree` flag replaced by `force_backend`
+pub const SCENE_VERSION: u64 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneError {
+    // Not JSON, or JSON of the wrong shape (message carries serde's line/column or field path)
+    Parse(String),
+    // Valid JSON that is not an electromagnetism scene
+    WrongFormat,
+    // Written by a newer engine than this one
+    UnsupportedVersion(u64),
+    // Well-formed but physically unusable values
+    Invalid(String),
+    // State that JSON cannot represent (NaN or infinite values)
+    Serialize(String),
+    // Binary snapshot whose payload does not match its checksum
+    ChecksumMismatch,
+    // Text scene description that does not parse, with the 1-based position of the problem
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Parse(msg) => write!(f, "malformed scene: {}", msg),
+            SceneError::WrongFormat => write!(f, "not an electromagnetism scene"),
+            SceneError::UnsupportedVersion(v) => {
+                write!(f, "scene version {} is newer than supported version {}", v, SCENE_VERSION)
+            }
+            SceneError::Invalid(msg) => write!(f, "invalid scene: {}", msg),
+            SceneError::Serialize(msg) => write!(f, "cannot save scene: {}", msg),
+            SceneError::ChecksumMismatch => write!(f, "snapshot is corrupted (checksum mismatch)"),
+            SceneError::Syntax { line, column, message } => {
+                write!(f, "line {}, column {}: {}", line, column, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<SceneError> for JsValue {
+    fn from(error: SceneError) -> JsValue {
+        let js_error = js_sys::Error::new(&error.to_string());
+        if let SceneError::Syntax { line, column, .. } = error {
+            let _ = js_sys::Reflect::set(&js_error, &"line".into(), &(line as u32).into());
+            let _ = js_sys::Reflect::set(&js_error, &"column".into(), &(column as u32).into());
+        }
+        js_error.into()
+    }
+}
+
+#[derive(Serialize)]
+struct SceneEnvelope<'a> {
+    format: &'a str,
+    version: u64,
+    universe: &'a Universe,
+}
+
+fn detect_version(value: &Value) -> Result<u64, SceneError> {
+    let object = value.as_object().ok_or(SceneError::WrongFormat)?;
+    match object.get("format") {
+        Some(format) if format == SCENE_FORMAT => {
+            object
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| SceneError::Parse("missing or non-integer \"version\"".to_string()))
+        }
+        Some(_) => Err(SceneError::WrongFormat),
+        // unversioned get_data() dumps are recognisable by their particle list
+        None if object.contains_key("particles") => Ok(0),
+        None => Err(SceneError::WrongFormat),
+    }
+}
+
+// Upgrades the universe object of a version-0 scene to the version-1 layout
+fn migrate_v0(universe: &mut Value) {
+    if let Some(object) = universe.as_object_mut() {
+        object.remove("quadtree");
+        if let Some(Value::Array(particles)) = object.get_mut("particles") {
+            for particle in particles.iter_mut().filter_map(Value::as_object_mut) {
+                particle.remove("trail");
+            }
+        }
+    }
+}
+
+// Upgrades the universe object of a version-1 scene to the version-2 layout
+fn migrate_v1(universe: &mut Value) {
+    if let Some(object) = universe.as_object_mut() {
+        if let Some(Value::Bool(use_quadtree)) = object.remove("use_quadtree") {
+            let backend = if use_quadtree { "Quadtree" } else { "Direct" };
+            object.insert("force_backend".to_string(), Value::from(backend));
+        }
+    }
+}
+
+fn check_finite(what: &str, values: &[f64]) -> Result<(), SceneError> {
+    if values.iter().all(|v| v.is_finite()) {
+        Ok(())
+    } else {
+        Err(SceneError::Invalid(format!("{} must be finite", what)))
+    }
+}
+
+impl Universe {
+    pub(crate) fn validate(&self) -> Result<(), SceneError> {
+        for (i, p) in self.particles.iter().enumerate() {
+            let what = format!("particle {}", i);
+            check_finite(&what, &[p.pos.x, p.pos.y, p.vel.x, p.vel.y, p.charge, p.mass])?;
+            if p.mass <= 0.0 {
+                return Err(SceneError::Invalid(format!("{} has non-positive mass", what)));
+            }
+        }
+        for (i, m) in self.magnets.iter().enumerate() {
+            let what = format!("magnet {}", i);
+            let values = [m.pos.x, m.pos.y, m.vel.x, m.vel.y, m.angle, m.spin, m.strength, m.mass];
+            check_finite(&what, &values)?;
+            if m.mass <= 0.0 {
+                return Err(SceneError::Invalid(format!("{} has non-positive mass", what)));
+            }
+        }
+        check_finite("settings", &[
+            self.coulomb_constant,
+            self.speed,
+            self.default_mass,
+            self.default_charge,
+            self.min_interaction_distance,
+            self.quadtree_theta,
+            self.restitution,
+            self.external_field_x,
+            self.external_field_y,
+            self.magnetic_field_z,
+            self.damping,
+            self.boundary_width,
+            self.boundary_height,
+        ])?;
+        if self.min_interaction_distance < 0.0 {
+            return Err(SceneError::Invalid("min_interaction_distance is negative".to_string()));
+        }
+        if self.boundary_width <= 0.0 || self.boundary_height <= 0.0 {
+            return Err(SceneError::Invalid("boundary size must be positive".to_string()));
+        }
+        Ok(())
+    }
+
+    // Rebuilds everything a scene file leaves out
+    pub(crate) fn finish_load(&mut self) {
+        self.repair_ids();
+        self.trails.clear();
+        self.refresh_render_buffers();
+    }
+
+    pub(crate) fn parse_scene(json: &str) -> Result<Universe, SceneError> {
+        let mut value: Value = serde_json::from_str(json).map_err(|e| SceneError::Parse(e.to_string()))?;
+        let version = detect_version(&value)?;
+        if version > SCENE_VERSION {
+            return Err(SceneError::UnsupportedVersion(version));
+        }
+
+        let mut universe_value = if version == 0 { value } else { value["universe"].take() };
+        if version < 1 {
+            migrate_v0(&mut universe_value);
+        }
+        if version < 2 {
+            migrate_v1(&mut universe_value);
+        }
+
+        let mut universe: Universe = serde_json::from_value(universe_value).map_err(|e|
+            SceneError::Parse(e.to_string())
+        )?;
+        universe.validate()?;
+        universe.finish_load();
+        Ok(universe)
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Serialize settings, particles and magnets as a versioned JSON scene
+    pub fn to_json(&self) -> Result<String, SceneError> {
+        // refuse anything from_json would reject, e.g. NaN/infinity that serde_json writes as
+        // null
+        self.validate()?;
+        let envelope = SceneEnvelope {
+            format: SCENE_FORMAT,
+            version: SCENE_VERSION,
+            universe: self,
+        };
+        serde_json::to_string(&envelope).map_err(|e| SceneError::Serialize(e.to_string()))
+    }
+
+    // Build a universe from a JSON scene (any supported version, migrated on load)
+    pub fn from_json(json: &str) -> Result<Universe, SceneError> {
+        Universe::parse_scene(json)
+    }
+
+    // Replace this universe's state with a JSON scene (undoable); on error the current
+    // state is kept
+    pub fn load_json(&mut self, json: &str) -> Result<(), SceneError> {
+        let scene = Universe::parse_scene(json)?;
+        self.replace_undoable(scene);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ Boundary, ForceBackend };
+
+    fn scene() -> Universe {
+        let mut universe = Universe::new_empty();
+        universe.set_seed(11);
+        universe.add_particle(-50.0, 10.0, 1.5, -2.0, 5.0, 2.0, 0x112233, 3.0);
+        universe.add_tracer(40.0, -20.0, -3.0, 0.5, -1.0);
+        universe.add_magnet(0.0, 200.0, 0.5, 60.0, 10.0, 1.0, 0xff0000, 0x0000ff, 3.0, false);
+        universe.set_force_backend(ForceBackend::Fmm);
+        universe.set_boundary(Boundary::Periodic, 1000.0, 800.0);
+        universe.time_step(0.016);
+        universe
+    }
+
+    fn parse_error(json: &str) -> SceneError {
+        match Universe::parse_scene(json) {
+            Err(error) => error,
+            Ok(_) => panic!("{} parsed", json),
+        }
+    }
+
+    #[test]
+    fn json_round_trip_is_exact() {
+        let universe = scene();
+        let json = universe.to_json().unwrap();
+        let loaded = Universe::parse_scene(&json).unwrap();
+        assert!(loaded.particles == universe.particles);
+        assert_eq!(loaded.get_time().to_bits(), universe.get_time().to_bits());
+        assert_eq!(loaded.to_json().unwrap(), json);
+    }
+
+    // The universe object as version 1 wrote it: `use_quadtree` instead of `force_backend`
+    fn version_1_universe(universe: &Universe, use_quadtree: bool) -> Value {
+        let mut value = serde_json::to_value(universe).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove("force_backend");
+        object.insert("use_quadtree".to_string(), Value::from(use_quadtree));
+        value
+    }
+
+    #[test]
+    fn migrates_version_1_scenes() {
+        let universe = scene();
+        for (use_quadtree, backend) in [(true, ForceBackend::Quadtree), (false, ForceBackend::Direct)] {
+            let envelope = serde_json::json!({
+                "format": SCENE_FORMAT,
+                "version": 1,
+                "universe": version_1_universe(&universe, use_quadtree),
+            });
+            let loaded = Universe::parse_scene(&envelope.to_string()).unwrap();
+            assert!(loaded.get_force_backend() == backend);
+            assert!(loaded.particles == universe.particles);
+        }
+    }
+
+    #[test]
+    fn migrates_unversioned_dumps() {
+        let universe = scene();
+        // get_data() output: no envelope, no ids, plus the quadtree and per-particle trails
+        let mut value = version_1_universe(&universe, true);
+        let object = value.as_object_mut().unwrap();
+        object.remove("next_id");
+        object.insert("quadtree".to_string(), serde_json::json!({ "nodes": [] }));
+        for particle in object["particles"].as_array_mut().unwrap() {
+            let particle = particle.as_object_mut().unwrap();
+            particle.remove("id");
+            particle.insert("trail".to_string(), serde_json::json!([{ "x": 1.0 }]));
+        }
+
+        let loaded = Universe::parse_scene(&value.to_string()).unwrap();
+        assert!(loaded.get_force_backend() == ForceBackend::Quadtree);
+        assert_eq!(loaded.particles.len(), 2);
+        for (a, b) in loaded.particles.iter().zip(&universe.particles) {
+            assert_eq!((a.pos.x, a.pos.y, a.charge), (b.pos.x, b.pos.y, b.charge));
+        }
+        // ids are handed out afresh
+        assert!(loaded.particles.iter().all(|p| p.id != 0));
+        assert_ne!(loaded.particles[0].id, loaded.particles[1].id);
+    }
+
+    #[test]
+    fn rejects_malformed_scenes() {
+        assert!(matches!(parse_error("{\"format\": "), SceneError::Parse(_)));
+        assert!(matches!(parse_error("[1, 2]"), SceneError::WrongFormat));
+        assert!(matches!(parse_error("{}"), SceneError::WrongFormat));
+        let other = "{\"format\": \"other\", \"version\": 1}";
+        assert!(matches!(parse_error(other), SceneError::WrongFormat));
+        let missing_version = format!("{{\"format\": \"{}\"}}", SCENE_FORMAT);
+        assert!(matches!(parse_error(&missing_version), SceneError::Parse(_)));
+        let newer = format!("{{\"format\": \"{}\", \"version\": {}}}", SCENE_FORMAT, SCENE_VERSION + 1);
+        assert_eq!(parse_error(&newer), SceneError::UnsupportedVersion(SCENE_VERSION + 1));
+
+        // right shape, wrong types
+        let mut value: Value = serde_json::from_str(&scene().to_json().unwrap()).unwrap();
+        value["universe"]["particles"][0]["mass"] = Value::from("heavy");
+        assert!(matches!(parse_error(&value.to_string()), SceneError::Parse(_)));
+        // well-formed but unusable
+        value["universe"]["particles"][0]["mass"] = Value::from(-1.0);
+        assert!(matches!(parse_error(&value.to_string()), SceneError::Invalid(_)));
+    }
+
+    #[test]
+    fn refuses_to_save_what_it_could_not_load() {
+        let mut universe = scene();
+        universe.particles[0].vel.x = f64::NAN;
+        assert!(matches!(universe.to_json(), Err(SceneError::Invalid(_))));
+    }
+}
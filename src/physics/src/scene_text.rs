@@ -0,0 +1,924 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    Boundary,
+    ForceBackend,
+    Implementation,
+    Magnet,
+    MeshAssignment,
+    Particle,
+    SceneError,
+    Universe,
+    Vec2,
+};
+use crate::expr::{ error_at, is_reserved, tokenize, Dim, ExprParser, Pos, Quantity, Token };
+use crate::spawn::{ SpawnOptions, SpawnShape, ValueDistribution, VelocityDistribution };
+use crate::rng::SimRng;
+use crate::fmm::{ MAX_FMM_ORDER, MIN_FMM_ORDER };
+use crate::pm::{ MAX_MESH_SIZE, MIN_MESH_SIZE };
+
+// Hand-editable scene descriptions in a TOML-style layout, meant to be kept under version
+// control next to lesson material:
+//
+//     title = "Dipole in a uniform field"
+//
+//     [settings]
+//     integrator = "rk4"
+//     softening = 10 px
+//     seed = 42
+//
+//     [fields]
+//     electric = [20, 0]
+//     damping = 0.1 / s
+//
+//     [boundary]
+//     kind = "reflecting"
+//     width = 1200 px
+//     height = 800 px
+//
+//     [define]
+//     q = 30 e
+//
+//     [[group]]
+//     name = "dipole"
+//     offset = [0, 100 px]
+//     fixed = true
+//
+//     [[particle]]
+//     group = "dipole"
+//     position = [-50 px, 0]
+//     charge = q
+//
+//     [[particle]]
+//     group = "dipole"
+//     position = [50 px, 0]
+//     charge = -q
+//
+//     [[ring]]
+//     count = 24
+//     radius = 300 px
+//     charge = -1 e
+//     tracer = true
+//
+// Values are strings, booleans, [x, y] pairs or arithmetic expressions with units (px, s, ms,
+// e, u, deg, rad), constants (pi, tau), functions (sqrt, abs, sin, cos, tan, min, max) and
+// names from [define] sections. Bare numbers are taken in engine units; numbers with units
+// are checked against what the key expects. Generators are [[lattice]], [[ring]] and
+// [[cloud]]. Errors report the line and column they occur at.
+
+// [name] sections ("" is the part before the first header) and [[name]] sections
+const SINGLE_SECTIONS: &[&str] = &["", "settings", "fields", "boundary", "define"];
+const REPEATED_SECTIONS: &[&str] = &["group", "particle", "magnet", "lattice", "ring", "cloud"];
+
+#[derive(Clone)]
+enum Value {
+    Str(String),
+    Bool(bool),
+    Number(Quantity),
+    List(Vec<(Quantity, Pos)>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Str(_) => "a string",
+            Value::Bool(_) => "a boolean",
+            Value::Number(_) => "a number",
+            Value::List(_) => "a list",
+        }
+    }
+}
+
+struct Entry {
+    key: String,
+    key_pos: Pos,
+    value: Value,
+    value_pos: Pos,
+}
+
+struct Table {
+    name: String,
+    // declared with [[name]]
+    array: bool,
+    pos: Pos,
+    entries: Vec<Entry>,
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_') &&
+        chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// Byte offset of a `#` comment outside any string
+fn comment_start(line: &str) -> Option<usize> {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => {
+                escaped = false;
+            }
+            '\\' if in_string => {
+                escaped = true;
+            }
+            '"' => {
+                in_string = !in_string;
+            }
+            '#' if !in_string => {
+                return Some(i);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// 1-based column of a byte offset
+fn column_of(line: &str, offset: usize) -> usize {
+    line[..offset].chars().count() + 1
+}
+
+fn parse_value(
+    text: &str,
+    pos: Pos,
+    defines: &HashMap<String, Quantity>
+) -> Result<Value, SceneError> {
+    let tokens = tokenize(text, pos)?;
+    let end = Pos { line: pos.line, column: pos.column + text.chars().count() };
+    let lookup = |name: &str| defines.get(name).copied();
+    let mut parser = ExprParser::new(&tokens, end, &lookup);
+
+    let value = match tokens.first().map(|(token, _)| token) {
+        None => {
+            return Err(error_at(pos, "missing value"));
+        }
+        Some(Token::Str(text)) => {
+            parser.skip();
+            Value::Str(text.clone())
+        }
+        Some(Token::Ident(name)) if name == "true" || name == "false" => {
+            parser.skip();
+            Value::Bool(name == "true")
+        }
+        Some(Token::Symbol('[')) => {
+            parser.expect('[')?;
+            let mut items = vec![];
+            while !parser.eat(']') {
+                let item_pos = parser.pos();
+                items.push((parser.expression()?, item_pos));
+                if !parser.eat(',') {
+                    parser.expect(']')?;
+                    break;
+                }
+            }
+            Value::List(items)
+        }
+        Some(_) => Value::Number(parser.expression()?),
+    };
+    if !parser.at_end() {
+        return Err(parser.unexpected("end of value"));
+    }
+    Ok(value)
+}
+
+fn parse_document(text: &str) -> Result<Vec<Table>, SceneError> {
+    let mut tables = vec![Table {
+        name: String::new(),
+        array: false,
+        pos: Pos { line: 1, column: 1 },
+        entries: vec![],
+    }];
+    let mut defines: HashMap<String, Quantity> = HashMap::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = match comment_start(raw) {
+            Some(offset) => &raw[..offset],
+            None => raw,
+        };
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        let pos = Pos { line: line_number, column: column_of(line, indent) };
+
+        if trimmed.starts_with('[') {
+            let array = trimmed.starts_with("[[");
+            let close = if array { "]]" } else { "]" };
+            if !trimmed.ends_with(close) || trimmed.len() < 2 * close.len() {
+                let end = Pos { line: line_number, column: column_of(line, line.trim_end().len()) };
+                return Err(error_at(end, format!("expected `{}` to close the section header", close)));
+            }
+            let name = trimmed[close.len()..trimmed.len() - close.len()].trim();
+            if !is_identifier(name) {
+                return Err(error_at(pos, format!("invalid section name `{}`", name)));
+            }
+            tables.push(Table { name: name.to_string(), array, pos, entries: vec![] });
+            continue;
+        }
+
+        let eq = line
+            .find('=')
+            .ok_or_else(|| error_at(pos, "expected `key = value` or a [section] header"))?;
+        let key = line[..eq].trim();
+        if !is_identifier(key) {
+            return Err(error_at(pos, format!("invalid key `{}`", key)));
+        }
+        let rest = &line[eq + 1..];
+        let value_offset = eq + 1 + (rest.len() - rest.trim_start().len());
+        let value_pos = Pos { line: line_number, column: column_of(line, value_offset) };
+        let value = parse_value(rest.trim(), value_pos, &defines)?;
+
+        let table = tables.last_mut().unwrap();
+        if table.entries.iter().any(|e| e.key == key) {
+            return Err(error_at(pos, format!("duplicate key `{}`", key)));
+        }
+        if table.name == "define" && !table.array {
+            if is_reserved(key) {
+                return Err(error_at(pos, format!("`{}` is a built-in name and cannot be redefined", key)));
+            }
+            match &value {
+                Value::Number(q) => {
+                    defines.insert(key.to_string(), *q);
+                }
+                other => {
+                    let message = format!("definitions must be numbers, found {}", other.kind());
+                    return Err(error_at(value_pos, message));
+                }
+            }
+        }
+        table.entries.push(Entry { key: key.to_string(), key_pos: pos, value, value_pos });
+    }
+    Ok(tables)
+}
+
+// Typed access to one table's keys; anything left unread is reported as unknown
+struct Fields<'a> {
+    table: &'a Table,
+    used: Vec<bool>,
+}
+
+fn check_dim(q: Quantity, expected: Dim, pos: Pos) -> Result<f64, SceneError> {
+    // bare numbers are already in engine units
+    if q.dim == Dim::NONE || q.dim == expected {
+        Ok(q.value)
+    } else {
+        Err(error_at(pos, format!("expected {}, found {}", expected, q.dim)))
+    }
+}
+
+impl<'a> Fields<'a> {
+    fn new(table: &'a Table) -> Fields<'a> {
+        Fields { table, used: vec![false; table.entries.len()] }
+    }
+
+    fn section(&self) -> String {
+        if self.table.array { format!("[[{}]]", self.table.name) } else { format!("[{}]", self.table.name) }
+    }
+
+    fn get(&mut self, key: &str) -> Option<&'a Entry> {
+        let index = self.table.entries.iter().position(|e| e.key == key)?;
+        self.used[index] = true;
+        Some(&self.table.entries[index])
+    }
+
+    fn wrong_type(entry: &Entry, wanted: &str) -> SceneError {
+        error_at(entry.value_pos, format!("`{}` must be {}, found {}", entry.key, wanted, entry.value.kind()))
+    }
+
+    fn number(&mut self, key: &str, dim: Dim) -> Result<Option<f64>, SceneError> {
+        let Some(entry) = self.get(key) else {
+            return Ok(None);
+        };
+        match &entry.value {
+            Value::Number(q) => check_dim(*q, dim, entry.value_pos).map(Some),
+            _ => Err(Fields::wrong_type(entry, &dim.to_string())),
+        }
+    }
+
+    fn non_negative(&mut self, key: &str, dim: Dim) -> Result<Option<f64>, SceneError> {
+        let pos = self.get(key).map(|e| e.value_pos);
+        let value = self.number(key, dim)?;
+        match (value, pos) {
+            (Some(v), Some(pos)) if v < 0.0 => {
+                Err(error_at(pos, format!("`{}` must not be negative", key)))
+            }
+            _ => Ok(value),
+        }
+    }
+
+    fn positive(&mut self, key: &str, dim: Dim) -> Result<Option<f64>, SceneError> {
+        let pos = self.get(key).map(|e| e.value_pos);
+        let value = self.number(key, dim)?;
+        match (value, pos) {
+            (Some(v), Some(pos)) if v <= 0.0 => {
+                Err(error_at(pos, format!("`{}` must be positive", key)))
+            }
+            _ => Ok(value),
+        }
+    }
+
+    fn integer(&mut self, key: &str) -> Result<Option<u64>, SceneError> {
+        let pos = self.get(key).map(|e| e.value_pos);
+        match (self.number(key, Dim::NONE)?, pos) {
+            (Some(v), Some(pos)) => {
+                if v < 0.0 || v.fract() != 0.0 || v > 9007199254740992.0 {
+                    return Err(error_at(pos, format!("`{}` must be a whole number", key)));
+                }
+                Ok(Some(v as u64))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn vec2(&mut self, key: &str, dim: Dim) -> Result<Option<Vec2>, SceneError> {
+        let Some(entry) = self.get(key) else {
+            return Ok(None);
+        };
+        match &entry.value {
+            Value::List(items) if items.len() == 2 => {
+                let x = check_dim(items[0].0, dim, items[0].1)?;
+                let y = check_dim(items[1].0, dim, items[1].1)?;
+                Ok(Some(Vec2::new(x, y)))
+            }
+            _ => Err(Fields::wrong_type(entry, &format!("[x, y] with {}", dim))),
+        }
+    }
+
+    fn boolean(&mut self, key: &str) -> Result<Option<bool>, SceneError> {
+        let Some(entry) = self.get(key) else {
+            return Ok(None);
+        };
+        match entry.value {
+            Value::Bool(b) => Ok(Some(b)),
+            _ => Err(Fields::wrong_type(entry, "true or false")),
+        }
+    }
+
+    fn string(&mut self, key: &str) -> Result<Option<(&'a str, Pos)>, SceneError> {
+        let Some(entry) = self.get(key) else {
+            return Ok(None);
+        };
+        match &entry.value {
+            Value::Str(s) => Ok(Some((s.as_str(), entry.value_pos))),
+            _ => Err(Fields::wrong_type(entry, "a string")),
+        }
+    }
+
+    // One of several named options, matched case-insensitively
+    fn choice<T: Copy>(&mut self, key: &str, options: &[(&str, T)]) -> Result<Option<T>, SceneError> {
+        let Some((text, pos)) = self.string(key)? else {
+            return Ok(None);
+        };
+        let lower = text.to_ascii_lowercase();
+        match options.iter().find(|(name, _)| *name == lower) {
+            Some((_, value)) => Ok(Some(*value)),
+            None => {
+                let names: Vec<String> = options
+                    .iter()
+                    .map(|(name, _)| format!("\"{}\"", name))
+                    .collect();
+                Err(error_at(pos, format!("`{}` must be one of {}", key, names.join(", "))))
+            }
+        }
+    }
+
+    // "#rrggbb" or a number such as 0xff8800
+    fn color(&mut self, key: &str) -> Result<Option<u32>, SceneError> {
+        let Some(entry) = self.get(key) else {
+            return Ok(None);
+        };
+        let parsed = match &entry.value {
+            Value::Str(s) => {
+                s.strip_prefix('#')
+                    .filter(|hex| hex.len() == 6)
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            }
+            Value::Number(q) if q.dim == Dim::NONE => {
+                Some(q.value)
+                    .filter(|v| v.fract() == 0.0 && (0.0..=16777215.0).contains(v))
+                    .map(|v| v as u32)
+            }
+            _ => None,
+        };
+        parsed
+            .map(Some)
+            .ok_or_else(|| Fields::wrong_type(entry, "a color like \"#ff8800\" or 0xff8800"))
+    }
+
+    fn required<T>(&self, key: &str, value: Option<T>) -> Result<T, SceneError> {
+        value.ok_or_else(|| error_at(self.table.pos, format!("{} needs `{}`", self.section(), key)))
+    }
+
+    fn finish(self) -> Result<(), SceneError> {
+        match self.used.iter().position(|used| !used) {
+            Some(index) => {
+                let entry = &self.table.entries[index];
+                Err(error_at(entry.key_pos, format!("unknown key `{}` in {}", entry.key, self.section())))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+// Per-particle values a [[group]] can provide for its members
+#[derive(Clone)]
+struct ParticleDefaults {
+    charge: f64,
+    mass: f64,
+    radius: f64,
+    color: Option<u32>,
+    fixed: bool,
+    tracer: bool,
+}
+
+impl Default for ParticleDefaults {
+    fn default() -> Self {
+        ParticleDefaults {
+            charge: 1.0,
+            mass: 1.0,
+            radius: 10.0,
+            color: None,
+            fixed: false,
+            tracer: false,
+        }
+    }
+}
+
+impl ParticleDefaults {
+    fn read(fields: &mut Fields, base: &ParticleDefaults) -> Result<ParticleDefaults, SceneError> {
+        Ok(ParticleDefaults {
+            charge: fields.number("charge", Dim::CHARGE)?.unwrap_or(base.charge),
+            mass: fields.positive("mass", Dim::MASS)?.unwrap_or(base.mass),
+            radius: fields.non_negative("radius", Dim::LENGTH)?.unwrap_or(base.radius),
+            color: fields.color("color")?.or(base.color),
+            fixed: fields.boolean("fixed")?.unwrap_or(base.fixed),
+            tracer: fields.boolean("tracer")?.unwrap_or(base.tracer),
+        })
+    }
+}
+
+#[derive(Clone, Default)]
+struct Group {
+    offset: Vec2,
+    velocity: Vec2,
+    defaults: ParticleDefaults,
+}
+
+struct Builder {
+    universe: Universe,
+    groups: HashMap<String, Group>,
+}
+
+impl Builder {
+    fn group(&self, fields: &mut Fields) -> Result<Group, SceneError> {
+        match fields.string("group")? {
+            Some((name, pos)) => {
+                self.groups
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(||
+                        error_at(pos, format!("unknown group \"{}\" (declare groups before use)", name))
+                    )
+            }
+            None => Ok(Group::default()),
+        }
+    }
+
+    fn settings(&mut self, fields: &mut Fields) -> Result<(), SceneError> {
+        let u = &mut self.universe;
+        if let Some(seed) = fields.integer("seed")? {
+            u.rng = SimRng::from_seed(seed);
+        }
+        let integrators = [
+            ("euler", Implementation::Euler),
+            ("rk4", Implementation::RK4),
+            ("verlet", Implementation::Verlet),
+            ("leapfrog", Implementation::Leapfrog),
+        ];
+        if let Some(implementation) = fields.choice("integrator", &integrators)? {
+            u.implementation = implementation;
+        }
+        if let Some(softening) = fields.non_negative("softening", Dim::LENGTH)? {
+            u.min_interaction_distance = softening;
+        }
+        if let Some(speed) = fields.positive("speed", Dim::NONE)? {
+            u.speed = speed;
+        }
+        if let Some(k) = fields.non_negative("coulomb_constant", Dim::NONE)? {
+            u.coulomb_constant = k;
+        }
+        if let Some(collisions) = fields.boolean("collisions")? {
+            u.collisions_enabled = collisions;
+        }
+        if let Some(restitution) = fields.non_negative("restitution", Dim::NONE)? {
+            u.restitution = restitution.min(1.0);
+        }
+        if let Some(quadtree) = fields.boolean("quadtree")? {
+            u.force_backend = if quadtree { ForceBackend::Quadtree } else { ForceBackend::Direct };
+        }
+        let backends = [
+            ("direct", ForceBackend::Direct),
+            ("quadtree", ForceBackend::Quadtree),
+            ("fmm", ForceBackend::Fmm),
+            ("pm", ForceBackend::ParticleMesh),
+            ("auto", ForceBackend::Auto),
+        ];
+        if let Some(backend) = fields.choice("backend", &backends)? {
+            u.force_backend = backend;
+        }
+        if let Some(threshold) = fields.integer("quadtree_threshold")? {
+            u.quadtree_threshold = threshold as usize;
+        }
+        if let Some(threshold) = fields.integer("fmm_threshold")? {
+            u.fmm_threshold = threshold as usize;
+        }
+        if let Some(timing) = fields.boolean("auto_timing")? {
+            u.auto_backend_timing = timing;
+        }
+        if let Some(order) = fields.integer("fmm_order")? {
+            u.fmm_order = (order as usize).clamp(MIN_FMM_ORDER, MAX_FMM_ORDER);
+        }
+        if let Some(size) = fields.integer("mesh_size")? {
+            u.mesh_size = (size as usize).clamp(MIN_MESH_SIZE, MAX_MESH_SIZE).next_power_of_two();
+        }
+        let assignments = [("cic", MeshAssignment::Cic), ("tsc", MeshAssignment::Tsc)];
+        if let Some(assignment) = fields.choice("mesh_assignment", &assignments)? {
+            u.mesh_assignment = assignment;
+        }
+        if let Some(p3m) = fields.boolean("p3m")? {
+            u.mesh_short_range = p3m;
+        }
+        if let Some(theta) = fields.non_negative("theta", Dim::NONE)? {
+            u.quadtree_theta = theta;
+        }
+        if let Some(mass_calculation) = fields.boolean("mass_calculation")? {
+            u.mass_calculation = mass_calculation;
+        }
+        if let Some(trails) = fields.boolean("trails")? {
+            u.show_trails = trails;
+        }
+        if let Some(length) = fields.integer("trail_length")? {
+            u.trail_length = length as usize;
+        }
+        Ok(())
+    }
+
+    fn fields(&mut self, fields: &mut Fields) -> Result<(), SceneError> {
+        let u = &mut self.universe;
+        if let Some(e) = fields.vec2("electric", Dim::ELECTRIC_FIELD)? {
+            u.external_field_x = e.x;
+            u.external_field_y = e.y;
+        }
+        if let Some(bz) = fields.number("magnetic", Dim::MAGNETIC_FIELD)? {
+            u.magnetic_field_z = bz;
+        }
+        if let Some(damping) = fields.non_negative("damping", Dim::RATE)? {
+            u.damping = damping;
+        }
+        Ok(())
+    }
+
+    fn boundary(&mut self, fields: &mut Fields) -> Result<(), SceneError> {
+        let kinds = [
+            ("open", Boundary::Open),
+            ("reflecting", Boundary::Reflecting),
+            ("periodic", Boundary::Periodic),
+        ];
+        let kind = fields.choice("kind", &kinds)?;
+        let u = &mut self.universe;
+        u.boundary = fields.required("kind", kind)?;
+        if let Some(width) = fields.positive("width", Dim::LENGTH)? {
+            u.boundary_width = width;
+        }
+        if let Some(height) = fields.positive("height", Dim::LENGTH)? {
+            u.boundary_height = height;
+        }
+        Ok(())
+    }
+
+    fn add_group(&mut self, fields: &mut Fields) -> Result<(), SceneError> {
+        let name = fields.string("name")?;
+        let (name, pos) = fields.required("name", name)?;
+        if self.groups.contains_key(name) {
+            return Err(error_at(pos, format!("group \"{}\" is already defined", name)));
+        }
+        let parent = self.group(fields)?;
+        let group = Group {
+            offset: parent.offset + fields.vec2("offset", Dim::LENGTH)?.unwrap_or_default(),
+            velocity: parent.velocity + fields.vec2("velocity", Dim::VELOCITY)?.unwrap_or_default(),
+            defaults: ParticleDefaults::read(fields, &parent.defaults)?,
+        };
+        self.groups.insert(name.to_string(), group);
+        Ok(())
+    }
+
+    fn add_particle(&mut self, fields: &mut Fields) -> Result<(), SceneError> {
+        let group = self.group(fields)?;
+        let position = fields.vec2("position", Dim::LENGTH)?;
+        let pos = group.offset + fields.required("position", position)?;
+        let vel = group.velocity + fields.vec2("velocity", Dim::VELOCITY)?.unwrap_or_default();
+        let d = ParticleDefaults::read(fields, &group.defaults)?;
+        let color = match d.color {
+            Some(color) => color,
+            None if d.charge < 0.0 => 0x0000ff,
+            None if d.charge > 0.0 => 0xff0000,
+            None => self.universe.random_color(),
+        };
+        let mut particle = Particle::new(
+            pos.x,
+            pos.y,
+            d.radius as f32,
+            d.mass,
+            color,
+            vel.x,
+            vel.y,
+            d.charge
+        );
+        particle.fixed = d.fixed;
+        particle.tracer = d.tracer;
+        self.universe.insert_particle(particle);
+        Ok(())
+    }
+
+    fn add_magnet(&mut self, fields: &mut Fields) -> Result<(), SceneError> {
+        let group = self.group(fields)?;
+        let position = fields.vec2("position", Dim::LENGTH)?;
+        let pos = group.offset + fields.required("position", position)?;
+        let vel = group.velocity + fields.vec2("velocity", Dim::VELOCITY)?.unwrap_or_default();
+        let mut magnet = Magnet::new(
+            pos.x,
+            pos.y,
+            fields.number("angle", Dim::NONE)?.unwrap_or(0.0),
+            fields.positive("size", Dim::LENGTH)?.unwrap_or(60.0) as f32,
+            fields.positive("thickness", Dim::LENGTH)?.unwrap_or(20.0) as f32,
+            fields.positive("mass", Dim::MASS)?.unwrap_or(1.0),
+            fields.color("north_color")?.unwrap_or(0xff0000),
+            fields.color("south_color")?.unwrap_or(0x0000ff),
+            fields.number("strength", Dim::CHARGE)?.unwrap_or(10.0),
+            fields.boolean("fixed")?.unwrap_or(false)
+        );
+        magnet.vel = vel;
+        magnet.spin = fields.number("spin", Dim::RATE)?.unwrap_or(0.0);
+        self.universe.insert_magnet(magnet);
+        Ok(())
+    }
+
+    // [[lattice]], [[ring]] and [[cloud]] all go through spawn_particles
+    fn add_generated(&mut self, fields: &mut Fields, kind: &str) -> Result<(), SceneError> {
+        let group = self.group(fields)?;
+        let count = fields.integer("count")?;
+        let count = fields.required("count", count)? as usize;
+        let shape = match kind {
+            "lattice" => {
+                let kinds = [("square", SpawnShape::SquareLattice), ("hex", SpawnShape::HexLattice)];
+                fields.choice("kind", &kinds)?.unwrap_or(SpawnShape::SquareLattice)
+            }
+            "ring" => SpawnShape::Ring,
+            _ => {
+                let shapes = [
+                    ("square", SpawnShape::Square),
+                    ("disc", SpawnShape::Disc),
+                    ("gaussian", SpawnShape::Gaussian),
+                ];
+                fields.choice("shape", &shapes)?.unwrap_or(SpawnShape::Disc)
+            }
+        };
+
+        let mut options = SpawnOptions::new(count, shape);
+        let center = group.offset + fields.vec2("center", Dim::LENGTH)?.unwrap_or_default();
+        options.center_x = center.x;
+        options.center_y = center.y;
+        let size_key = if kind == "ring" { "radius" } else { "size" };
+        options.size = fields.positive(size_key, Dim::LENGTH)?.unwrap_or(0.0);
+        if kind == "lattice" {
+            options.spacing = fields.positive("spacing", Dim::LENGTH)?.unwrap_or(0.0);
+        }
+
+        let d = ParticleDefaults::read(fields, &group.defaults)?;
+        options.charge_value = d.charge;
+        options.mass_value = d.mass;
+        options.radius = d.radius as f32;
+        options.color = d.color.unwrap_or(0);
+        options.fixed = d.fixed;
+        options.tracer = d.tracer;
+        if fields.boolean("alternate")?.unwrap_or(false) {
+            options.charge = ValueDistribution::Alternating;
+        }
+        if kind == "cloud" {
+            if let Some(spread) = fields.non_negative("charge_spread", Dim::CHARGE)? {
+                options.charge = ValueDistribution::Gaussian;
+                options.charge_spread = spread;
+            }
+            if let Some(spread) = fields.non_negative("mass_spread", Dim::MASS)? {
+                options.mass = ValueDistribution::Gaussian;
+                options.mass_spread = spread;
+            }
+        }
+
+        let temperature_pos = fields.get("temperature").map(|e| e.value_pos);
+        let temperature = fields.non_negative("temperature", Dim::ENERGY)?;
+        let (motion_key, motion) = match kind {
+            "ring" => ("rotation", fields.number("rotation", Dim::RATE)?),
+            "cloud" => ("speed", fields.non_negative("speed", Dim::VELOCITY)?),
+            _ => ("", None),
+        };
+        match (temperature, motion) {
+            (Some(_), Some(_)) => {
+                let message = format!("use either `temperature` or `{}`", motion_key);
+                return Err(error_at(temperature_pos.unwrap(), message));
+            }
+            (Some(t), None) => {
+                options.velocity = VelocityDistribution::MaxwellBoltzmann;
+                options.temperature = t;
+            }
+            (None, Some(m)) => {
+                options.velocity = if kind == "ring" {
+                    VelocityDistribution::Rotation
+                } else {
+                    VelocityDistribution::Uniform
+                };
+                options.speed = m;
+            }
+            (None, None) => {}
+        }
+
+        let ids = self.universe.spawn_particles(&options);
+        if group.velocity != Vec2::default() {
+            for id in ids {
+                if let Some(index) = self.universe.get_particle_index(id) {
+                    self.universe.particles[index].vel += group.velocity;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Universe {
+    pub(crate) fn parse_scene_text(text: &str) -> Result<Universe, SceneError> {
+        let tables = parse_document(text)?;
+        let mut builder = Builder {
+            universe: Universe::new_empty(),
+            groups: HashMap::new(),
+        };
+
+        // settings first, wherever they appear, so generators see the final collision and
+        // seed settings
+        let mut seen: Vec<&str> = vec![];
+        for table in &tables {
+            let name = table.name.as_str();
+            let known = if table.array { REPEATED_SECTIONS.contains(&name) } else { SINGLE_SECTIONS.contains(&name) };
+            if !known {
+                let header = if table.array { format!("[[{}]]", name) } else { format!("[{}]", name) };
+                let hint = if SINGLE_SECTIONS.contains(&name) {
+                    format!(" (use [{}])", table.name)
+                } else if REPEATED_SECTIONS.contains(&name) {
+                    format!(" (use [[{}]])", table.name)
+                } else {
+                    String::new()
+                };
+                return Err(error_at(table.pos, format!("unknown section {}{}", header, hint)));
+            }
+            if !table.array && table.name != "define" {
+                if seen.contains(&table.name.as_str()) {
+                    return Err(error_at(table.pos, format!("section [{}] appears twice", table.name)));
+                }
+                seen.push(&table.name);
+            }
+
+            let mut fields = Fields::new(table);
+            match (table.array, table.name.as_str()) {
+                (false, "") => {
+                    // descriptive metadata, not used by the engine
+                    for key in ["title", "description", "author"] {
+                        fields.string(key)?;
+                    }
+                }
+                (false, "settings") => builder.settings(&mut fields)?,
+                (false, "fields") => builder.fields(&mut fields)?,
+                (false, "boundary") => builder.boundary(&mut fields)?,
+                (false, "define") => {
+                    for entry in &table.entries {
+                        fields.get(&entry.key);
+                    }
+                }
+                _ => {
+                    continue;
+                }
+            }
+            fields.finish()?;
+        }
+
+        for table in tables.iter().filter(|t| t.array) {
+            let mut fields = Fields::new(table);
+            match table.name.as_str() {
+                "group" => builder.add_group(&mut fields)?,
+                "particle" => builder.add_particle(&mut fields)?,
+                "magnet" => builder.add_magnet(&mut fields)?,
+                kind => builder.add_generated(&mut fields, kind)?,
+            }
+            fields.finish()?;
+        }
+
+        let mut universe = builder.universe;
+        // building is not an edit of its own; loading the scene is one undo step
+        universe.clear_undo_history();
+        universe.validate()?;
+        universe.finish_load();
+        Ok(universe)
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Build a universe from a text scene description (format described in scene_text.rs).
+    // Errors carry `line` and `column` properties for highlighting in an editor.
+    pub fn from_scene_text(text: &str) -> Result<Universe, SceneError> {
+        Universe::parse_scene_text(text)
+    }
+
+    // Replace this universe's state with a text scene (undoable); on error the current
+    // state is kept
+    pub fn load_scene_text(&mut self, text: &str) -> Result<(), SceneError> {
+        let scene = Universe::parse_scene_text(text)?;
+        self.replace_undoable(scene);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syntax_error(text: &str) -> (usize, usize, String) {
+        match Universe::parse_scene_text(text) {
+            Err(SceneError::Syntax { line, column, message }) => (line, column, message),
+            Err(other) => panic!("expected a syntax error, got {}", other),
+            Ok(_) => panic!("expected a syntax error, got a scene"),
+        }
+    }
+
+    #[test]
+    fn parses_valid_scene() {
+        let text = "\
+title = \"Dipole\"
+
+[settings]
+softening = 10 px
+seed = 42
+
+[define]
+q = 30 e
+
+[[particle]]
+position = [-50 px, 0]
+charge = q
+
+[[particle]]
+position = [50 px, 0]
+charge = -q
+
+[[ring]]
+count = 6
+radius = 300 px
+charge = -1 e
+tracer = true
+";
+        let universe = match Universe::parse_scene_text(text) {
+            Ok(universe) => universe,
+            Err(error) => panic!("{}", error),
+        };
+        assert_eq!(universe.particles.len(), 8);
+        assert_eq!(universe.get_min_interaction_distance(), 10.0);
+        assert_eq!(universe.get_seed(), 42);
+        assert_eq!(universe.particles[0].pos.x, -50.0);
+        assert_eq!(universe.particles[1].charge, -universe.particles[0].charge);
+    }
+
+    #[test]
+    fn reports_wrong_unit() {
+        let (line, column, message) = syntax_error("[settings]\nsoftening = 3 s\n");
+        assert_eq!((line, column), (2, 13));
+        assert_eq!(message, "expected a length, found a time");
+    }
+
+    #[test]
+    fn reports_bad_expression_position() {
+        let (line, column, message) = syntax_error("[define]\nq = 2 * (3 + )\n");
+        // points at the `)` where an operand was expected
+        assert_eq!((line, column), (2, 14));
+        assert_eq!(message, "expected a number, name or `(`, found `)`");
+    }
+
+    #[test]
+    fn reports_unknown_key() {
+        let (line, column, message) = syntax_error("[settings]\nseed = 1\nsofening = 10 px\n");
+        assert_eq!((line, column), (3, 1));
+        assert_eq!(message, "unknown key `sofening` in [settings]");
+    }
+}
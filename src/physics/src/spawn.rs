@@ -0,0 +1,438 @@
+use wasm_bindgen::prelude::*;
+use serde::{ Serialize, Deserialize };
+
+use crate::{ Particle, Universe, Vec2 };
+
+// Random placements tried per particle before giving up on a non-overlapping spot
+const MAX_PLACEMENT_ATTEMPTS: usize = 50;
+// Gap factor between particle surfaces when overlap avoidance is on
+const OVERLAP_MARGIN: f64 = 1.05;
+// Smallest mass a distribution may produce, so accelerations stay finite
+const MIN_SPAWN_MASS: f64 = 1e-3;
+
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SpawnShape {
+    // Uniform in a square of side `size`
+    Square,
+    // Uniform in a disc of radius `size`
+    Disc,
+    // Gaussian blob with standard deviation `size`
+    Gaussian,
+    // Square lattice filling a square of side `size` (or with explicit `spacing`)
+    SquareLattice,
+    // Hexagonal (triangular) lattice filling a square of side `size` (or explicit `spacing`)
+    HexLattice,
+    // Evenly spaced on a circle of radius `size`
+    Ring,
+}
+
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum VelocityDistribution {
+    Zero,
+    // Each component uniform in [-speed, speed]
+    Uniform,
+    // Maxwell-Boltzmann at `temperature` (k_B = 1): components ~ N(0, sqrt(T / m))
+    MaxwellBoltzmann,
+    // Rigid rotation about the centre with angular velocity `speed` (rad per time unit)
+    Rotation,
+}
+
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ValueDistribution {
+    // Always `value`
+    Fixed,
+    // Uniform in [value - spread, value + spread]
+    Uniform,
+    // Normal with mean `value` and standard deviation `spread`
+    Gaussian,
+    // +value and -value alternating like a checkerboard (lattices) or by index (other shapes);
+    // for mass, which cannot be negative, value and `mass_alternate` alternate instead
+    Alternating,
+}
+
+// Settings for `spawn_particles`. Sizes <= 0 fall back to the universe's spawn_range.
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct SpawnOptions {
+    pub count: usize,
+    pub shape: SpawnShape,
+    pub center_x: f64,
+    pub center_y: f64,
+    // Side, radius or standard deviation depending on the shape
+    pub size: f64,
+    // Lattice spacing; <= 0 fits `count` sites into `size`
+    pub spacing: f64,
+    pub velocity: VelocityDistribution,
+    // Maximum component speed (Uniform) or angular velocity (Rotation)
+    pub speed: f64,
+    pub temperature: f64,
+    pub charge: ValueDistribution,
+    pub charge_value: f64,
+    pub charge_spread: f64,
+    pub mass: ValueDistribution,
+    pub mass_value: f64,
+    pub mass_spread: f64,
+    // Mass of every other particle when `mass` is Alternating
+    pub mass_alternate: f64,
+    pub radius: f32,
+    // 0 colors by charge sign
+    pub color: u32,
+    pub fixed: bool,
+    pub tracer: bool,
+}
+
+#[wasm_bindgen]
+impl SpawnOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(count: usize, shape: SpawnShape) -> SpawnOptions {
+        SpawnOptions {
+            count,
+            shape,
+            center_x: 0.0,
+            center_y: 0.0,
+            size: 0.0,
+            spacing: 0.0,
+            velocity: VelocityDistribution::Zero,
+            speed: 0.0,
+            temperature: 0.0,
+            charge: ValueDistribution::Fixed,
+            charge_value: 1.0,
+            charge_spread: 0.0,
+            mass: ValueDistribution::Fixed,
+            mass_value: 1.0,
+            mass_spread: 0.0,
+            mass_alternate: 1.0,
+            radius: 10.0,
+            color: 0,
+            fixed: false,
+            tracer: false,
+        }
+    }
+}
+
+impl Universe {
+    fn sample_value(
+        &mut self,
+        distribution: ValueDistribution,
+        value: f64,
+        spread: f64,
+        parity: bool
+    ) -> f64 {
+        match distribution {
+            ValueDistribution::Fixed => value,
+            ValueDistribution::Uniform => self.random_between(value - spread, value + spread),
+            ValueDistribution::Gaussian => value + spread * self.random_gaussian(),
+            ValueDistribution::Alternating => {
+                if parity { -value } else { value }
+            }
+        }
+    }
+
+    // Default extent of each shape when `size` is not given
+    fn spawn_extent(&self, options: &SpawnOptions) -> f64 {
+        if options.size > 0.0 {
+            return options.size;
+        }
+        match options.shape {
+            SpawnShape::Square | SpawnShape::SquareLattice | SpawnShape::HexLattice => {
+                self.spawn_range
+            }
+            SpawnShape::Disc | SpawnShape::Ring => self.spawn_range * 0.5,
+            SpawnShape::Gaussian => self.spawn_range / 6.0,
+        }
+    }
+
+    // Lattice and ring sites (offsets from the centre) with their checkerboard parity
+    fn structured_sites(
+        &self,
+        options: &SpawnOptions,
+        extent: f64,
+        min_gap: f64
+    ) -> Vec<(Vec2, bool)> {
+        let count = options.count;
+        let mut sites = Vec::with_capacity(count);
+        match options.shape {
+            SpawnShape::Ring => {
+                for i in 0..count {
+                    let angle = (2.0 * std::f64::consts::PI * (i as f64)) / (count as f64);
+                    let site = Vec2::new(f64::cos(angle), f64::sin(angle)) * extent;
+                    sites.push((site, i % 2 == 1));
+                }
+            }
+            SpawnShape::SquareLattice | SpawnShape::HexLattice => {
+                let hex = options.shape == SpawnShape::HexLattice;
+                let row_factor = if hex { f64::sqrt(3.0) * 0.5 } else { 1.0 };
+                // columns chosen so the lattice is roughly square
+                let columns = f64::ceil(f64::sqrt((count as f64) / row_factor)).max(1.0) as usize;
+                let rows = count.div_ceil(columns);
+                let spacing = if options.spacing > 0.0 {
+                    options.spacing
+                } else {
+                    extent / ((columns.max(2) - 1) as f64)
+                }.max(min_gap);
+                let row_spacing = spacing * row_factor;
+                let width = ((columns - 1) as f64) * spacing;
+                let height = ((rows.max(1) - 1) as f64) * row_spacing;
+                for i in 0..count {
+                    let (row, column) = (i / columns, i % columns);
+                    let shift = if hex && row % 2 == 1 { spacing * 0.5 } else { 0.0 };
+                    let site = Vec2::new(
+                        (column as f64) * spacing + shift - width * 0.5,
+                        (row as f64) * row_spacing - height * 0.5
+                    );
+                    sites.push((site, (row + column) % 2 == 1));
+                }
+            }
+            _ => {}
+        }
+        sites
+    }
+
+    fn random_site(&mut self, options: &SpawnOptions, extent: f64) -> Vec2 {
+        match options.shape {
+            SpawnShape::Disc => {
+                // sqrt keeps the density uniform over the area
+                let r = extent * f64::sqrt(self.random_between(0.0, 1.0));
+                let angle = self.random_between(0.0, 2.0 * std::f64::consts::PI);
+                Vec2::new(f64::cos(angle), f64::sin(angle)) * r
+            }
+            SpawnShape::Gaussian => {
+                Vec2::new(self.random_gaussian() * extent, self.random_gaussian() * extent)
+            }
+            _ => {
+                let half = extent * 0.5;
+                Vec2::new(self.random_between(-half, half), self.random_between(-half, half))
+            }
+        }
+    }
+
+    fn overlaps(&self, pos: Vec2, radius: f64) -> bool {
+        self.particles
+            .iter()
+            .filter(|p| !p.tracer)
+            .any(|p| p.pos.distance_from(pos) < (radius + (p.radius as f64)) * OVERLAP_MARGIN)
+    }
+
+    fn spawn_velocity(&mut self, options: &SpawnOptions, offset: Vec2, mass: f64) -> Vec2 {
+        match options.velocity {
+            VelocityDistribution::Zero => Vec2::new(0.0, 0.0),
+            VelocityDistribution::Uniform => {
+                Vec2::new(
+                    self.random_between(-options.speed, options.speed),
+                    self.random_between(-options.speed, options.speed)
+                )
+            }
+            VelocityDistribution::MaxwellBoltzmann => {
+                let sigma = f64::sqrt(options.temperature.max(0.0) / mass);
+                Vec2::new(self.random_gaussian() * sigma, self.random_gaussian() * sigma)
+            }
+            VelocityDistribution::Rotation => {
+                Vec2::new(-options.speed * offset.y, options.speed * offset.x)
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Add `options.count` particles at once (a single undo step) and return their IDs.
+    // With collisions enabled, sites overlapping existing particles are skipped (lattices,
+    // rings) or re-drawn (random shapes), so fewer than `count` IDs may come back.
+    #[allow(clippy::needless_range_loop)]
+    pub fn spawn_particles(&mut self, options: &SpawnOptions) -> Vec<u32> {
+        let center = Vec2::new(options.center_x, options.center_y);
+        let extent = self.spawn_extent(options);
+        let radius = options.radius.max(0.0) as f64;
+        let avoid_overlap = self.collisions_enabled && !options.tracer;
+        let min_gap = if avoid_overlap { 2.0 * radius * OVERLAP_MARGIN } else { 0.0 };
+        let structured = matches!(
+            options.shape,
+            SpawnShape::SquareLattice | SpawnShape::HexLattice | SpawnShape::Ring
+        );
+        let sites = if structured {
+            self.structured_sites(options, extent, min_gap)
+        } else {
+            vec![]
+        };
+
+        let mut ids = Vec::with_capacity(options.count);
+        self.begin_edit_group();
+        for i in 0..options.count {
+            let (offset, parity) = if structured {
+                let (site, parity) = sites[i];
+                if avoid_overlap && self.overlaps(center + site, radius) {
+                    continue;
+                }
+                (site, parity)
+            } else {
+                let mut placed = None;
+                for _ in 0..MAX_PLACEMENT_ATTEMPTS {
+                    let site = self.random_site(options, extent);
+                    if !avoid_overlap || !self.overlaps(center + site, radius) {
+                        placed = Some(site);
+                        break;
+                    }
+                }
+                // `i` numbers attempts, not only placed particles: parity follows it
+                match placed {
+                    Some(site) => (site, i % 2 == 1),
+                    None => {
+                        continue;
+                    }
+                }
+            };
+
+            let charge = self.sample_value(
+                options.charge,
+                options.charge_value,
+                options.charge_spread,
+                parity
+            );
+            let mass = match options.mass {
+                ValueDistribution::Alternating => {
+                    if parity { options.mass_alternate } else { options.mass_value }
+                }
+                distribution => {
+                    self.sample_value(distribution, options.mass_value, options.mass_spread, parity)
+                }
+            };
+            let mass = mass.abs().max(MIN_SPAWN_MASS);
+            let vel = self.spawn_velocity(options, offset, mass);
+            let color = if options.color != 0 {
+                options.color
+            } else if charge < 0.0 {
+                0x0000ff
+            } else if charge > 0.0 {
+                0xff0000
+            } else {
+                self.random_color()
+            };
+            let pos = center + offset;
+            let mut particle = Particle::new(
+                pos.x,
+                pos.y,
+                options.radius,
+                mass,
+                color,
+                vel.x,
+                vel.y,
+                charge
+            );
+            particle.fixed = options.fixed;
+            particle.tracer = options.tracer;
+            ids.push(self.insert_particle_undoable(particle));
+        }
+        self.end_edit_group();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(universe: &Universe) -> Vec<Vec2> {
+        universe.particles
+            .iter()
+            .map(|p| p.pos)
+            .collect()
+    }
+
+    #[test]
+    fn alternating_mass_switches_to_mass_alternate() {
+        let mut universe = Universe::new_empty();
+        let mut options = SpawnOptions::new(4, SpawnShape::SquareLattice);
+        options.spacing = 100.0;
+        options.mass = ValueDistribution::Alternating;
+        options.mass_value = 2.0;
+        options.mass_alternate = 5.0;
+        // spread plays no part in alternation
+        options.mass_spread = 9.0;
+        let ids = universe.spawn_particles(&options);
+        assert_eq!(ids.len(), 4);
+
+        let mut masses: Vec<f64> = universe.particles
+            .iter()
+            .map(|p| p.mass)
+            .collect();
+        masses.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(masses, vec![2.0, 2.0, 5.0, 5.0]);
+    }
+
+    #[test]
+    fn lattices_and_rings_fill_every_site() {
+        for (shape, count) in [
+            (SpawnShape::SquareLattice, 10),
+            (SpawnShape::HexLattice, 7),
+            (SpawnShape::Ring, 12),
+        ] {
+            let mut universe = Universe::new_empty();
+            let mut options = SpawnOptions::new(count, shape);
+            options.size = 300.0;
+            options.radius = 2.0;
+            assert_eq!(universe.spawn_particles(&options).len(), count);
+
+            let sites = positions(&universe);
+            for (i, a) in sites.iter().enumerate() {
+                for b in &sites[i + 1..] {
+                    assert!(a.distance_from(*b) > 1.0);
+                }
+            }
+            if shape == SpawnShape::Ring {
+                for site in &sites {
+                    assert!((site.distance_from(Vec2::new(0.0, 0.0)) - 300.0).abs() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn spawning_avoids_overlap_when_colliding() {
+        let mut universe = Universe::new_empty();
+        universe.set_collisions_enabled(true);
+        universe.add_particle(0.0, 0.0, 0.0, 0.0, 10.0, 1.0, 0xff0000, 1.0);
+
+        // the centre site of a 3x3 lattice is taken, so it is skipped
+        let mut lattice = SpawnOptions::new(9, SpawnShape::SquareLattice);
+        lattice.spacing = 100.0;
+        assert_eq!(universe.spawn_particles(&lattice).len(), 8);
+
+        // random sites are re-drawn until they fit
+        let mut disc = SpawnOptions::new(30, SpawnShape::Disc);
+        disc.size = 400.0;
+        disc.radius = 8.0;
+        universe.spawn_particles(&disc);
+        let particles = &universe.particles;
+        assert!(particles.len() > 30);
+        for (i, a) in particles.iter().enumerate() {
+            for b in &particles[i + 1..] {
+                let contact = (a.radius + b.radius) as f64;
+                assert!(a.pos.distance_from(b.pos) >= contact);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_spawns_the_same_particles() {
+        let spawn = |seed: u64| {
+            let mut universe = Universe::new_empty();
+            universe.set_seed(seed);
+            let mut options = SpawnOptions::new(20, SpawnShape::Gaussian);
+            options.size = 100.0;
+            options.velocity = VelocityDistribution::MaxwellBoltzmann;
+            options.temperature = 2.0;
+            options.charge = ValueDistribution::Gaussian;
+            options.charge_spread = 0.5;
+            universe.spawn_particles(&options);
+            universe.particles
+                .iter()
+                .map(|p| (p.pos.x, p.pos.y, p.vel.x, p.vel.y, p.charge))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(spawn(5), spawn(5));
+        assert_ne!(spawn(5), spawn(6));
+    }
+}
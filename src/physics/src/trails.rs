@@ -0,0 +1,317 @@
+use wasm_bindgen::prelude::*;
+use serde::{ Serialize, Deserialize };
+
+use crate::{ Particle, Trail, Universe };
+
+// How trail points are colored when they are recorded
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum TrailColorMode {
+    // The particle's own color
+    #[default]
+    Particle,
+    // Speed relative to the fastest particle at sampling time
+    Speed,
+    // Kinetic energy relative to the most energetic particle at sampling time
+    Energy,
+    // Red for positive, blue for negative, brightness by |q| relative to the largest charge
+    Charge,
+}
+
+// Fixed-capacity ring of trail points for one particle (oldest point at `start`)
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct TrailRing {
+    points: Vec<Trail>,
+    start: usize,
+    steps_since_sample: u32,
+}
+
+impl TrailRing {
+    fn push(&mut self, point: Trail, capacity: usize) {
+        if self.points.len() > capacity {
+            // capacity shrank: keep only the newest points, in order
+            let mut ordered = self.ordered();
+            ordered.drain(..ordered.len() - capacity);
+            self.points = ordered;
+            self.start = 0;
+        }
+        if capacity == 0 {
+            return;
+        }
+        if self.points.len() < capacity {
+            if self.start != 0 {
+                // capacity grew after wrapping: unroll once so appending keeps the order
+                self.points = self.ordered();
+                self.start = 0;
+            }
+            self.points.reserve_exact(capacity - self.points.len());
+            self.points.push(point);
+        } else {
+            self.points[self.start] = point;
+            self.start = (self.start + 1) % capacity;
+        }
+    }
+
+    // Visits the points oldest first without collecting them
+    fn for_each(&self, mut f: impl FnMut(&Trail)) {
+        self.points[self.start..].iter().chain(&self.points[..self.start]).for_each(&mut f);
+    }
+
+    fn ordered(&self) -> Vec<Trail> {
+        let mut ordered = Vec::with_capacity(self.points.len());
+        ordered.extend_from_slice(&self.points[self.start..]);
+        ordered.extend_from_slice(&self.points[..self.start]);
+        ordered
+    }
+}
+
+// Trail history for every particle, stored outside `Particle` so that particles stay small
+// and trails are never serialized with them. Rings are kept parallel to `Universe::particles`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub(crate) struct TrailStore {
+    rings: Vec<TrailRing>,
+}
+
+impl TrailStore {
+    pub(crate) fn remove(&mut self, index: usize) {
+        if index < self.rings.len() {
+            self.rings.remove(index);
+        }
+    }
+
+    // Empty ring for a particle re-inserted at `index` (e.g. by undo)
+    pub(crate) fn insert(&mut self, index: usize) {
+        if index <= self.rings.len() {
+            self.rings.insert(index, TrailRing::default());
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.rings.clear();
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Vec<Trail> {
+        self.rings
+            .get(index)
+            .map(|ring| ring.ordered())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn for_each_point(&self, index: usize, f: impl FnMut(&Trail)) {
+        if let Some(ring) = self.rings.get(index) {
+            ring.for_each(f);
+        }
+    }
+}
+
+// Blue -> cyan -> green -> yellow -> red for t in [0, 1]
+fn heat_color(t: f64) -> u32 {
+    let t = if t.is_finite() { t.clamp(0.0, 1.0) } else { 0.0 };
+    let (r, g, b) = if t < 0.25 {
+        (0.0, t / 0.25, 1.0)
+    } else if t < 0.5 {
+        (0.0, 1.0, 1.0 - (t - 0.25) / 0.25)
+    } else if t < 0.75 {
+        ((t - 0.5) / 0.25, 1.0, 0.0)
+    } else {
+        (1.0, 1.0 - (t - 0.75) / 0.25, 0.0)
+    };
+    (((r * 255.0) as u32) << 16) | (((g * 255.0) as u32) << 8) | ((b * 255.0) as u32)
+}
+
+impl Universe {
+    fn kinetic_energy(&self, p: &Particle) -> f64 {
+        let mass = if self.mass_calculation { p.mass } else { self.default_mass };
+        0.5 * mass * (p.vel.x * p.vel.x + p.vel.y * p.vel.y)
+    }
+
+    // Records a trail point for every particle whose sampling interval has elapsed
+    pub(crate) fn record_trails(&mut self) {
+        self.trails.rings.resize_with(self.particles.len(), TrailRing::default);
+
+        let scale = match self.trail_color_mode {
+            TrailColorMode::Particle => 1.0,
+            TrailColorMode::Speed => {
+                self.particles
+                    .iter()
+                    .map(|p| p.vel.magnitude())
+                    .fold(0.0, f64::max)
+            }
+            TrailColorMode::Energy => {
+                self.particles
+                    .iter()
+                    .map(|p| self.kinetic_energy(p))
+                    .fold(0.0, f64::max)
+            }
+            TrailColorMode::Charge => {
+                self.particles
+                    .iter()
+                    .map(|p| p.charge.abs())
+                    .fold(0.0, f64::max)
+            }
+        };
+        let scale = if scale > 0.0 { scale } else { 1.0 };
+
+        for i in 0..self.particles.len() {
+            let p = &self.particles[i];
+            let interval = p.trail_interval.unwrap_or(self.trail_interval).max(1);
+            let capacity = p.trail_length.map_or(self.trail_length, |l| l as usize);
+            let ring = &mut self.trails.rings[i];
+            ring.steps_since_sample += 1;
+            if ring.steps_since_sample < interval {
+                continue;
+            }
+            ring.steps_since_sample = 0;
+
+            let color = match self.trail_color_mode {
+                TrailColorMode::Particle => p.color,
+                TrailColorMode::Speed => heat_color(p.vel.magnitude() / scale),
+                TrailColorMode::Energy => heat_color(self.kinetic_energy(p) / scale),
+                TrailColorMode::Charge => {
+                    let level = (((p.charge.abs() / scale) * 255.0) as u32).min(255);
+                    if p.charge > 0.0 {
+                        level << 16
+                    } else if p.charge < 0.0 {
+                        level
+                    } else {
+                        0x808080
+                    }
+                }
+            };
+            self.trails.rings[i].push(Trail { pos: p.pos, color }, capacity);
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    // Number of points kept per trail (particles may override it)
+    pub fn set_trail_length(&mut self, length: usize) {
+        self.edit_settings(|u| {
+            u.trail_length = length;
+        });
+    }
+
+    pub fn get_trail_length(&self) -> usize {
+        self.trail_length
+    }
+
+    // Record a trail point every `interval` time steps (particles may override it)
+    pub fn set_trail_interval(&mut self, interval: u32) {
+        self.edit_settings(|u| {
+            u.trail_interval = interval.max(1);
+        });
+    }
+
+    pub fn get_trail_interval(&self) -> u32 {
+        self.trail_interval
+    }
+
+    pub fn set_trail_color_mode(&mut self, mode: TrailColorMode) {
+        self.edit_settings(|u| {
+            u.trail_color_mode = mode;
+        });
+    }
+
+    pub fn get_trail_color_mode(&self) -> TrailColorMode {
+        self.trail_color_mode
+    }
+
+    // Per-particle trail length; pass undefined to follow the global setting again
+    pub fn update_particle_trail_length(&mut self, index: usize, length: Option<u32>) {
+        self.edit_particle(index, |p| {
+            p.trail_length = length;
+        });
+    }
+
+    // Per-particle sampling interval; pass undefined to follow the global setting again
+    pub fn update_particle_trail_interval(&mut self, index: usize, interval: Option<u32>) {
+        self.edit_particle(index, |p| {
+            p.trail_interval = interval;
+        });
+    }
+
+    pub fn get_particle_trail(&self, index: usize) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.trails.get(index)).unwrap()
+    }
+
+    pub fn clear_trails(&mut self) {
+        self.trails.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vec2;
+
+    fn point(x: f64) -> Trail {
+        Trail { pos: Vec2::new(x, 0.0), color: 0 }
+    }
+
+    fn xs(ring: &TrailRing) -> Vec<f64> {
+        ring.ordered()
+            .iter()
+            .map(|p| p.pos.x)
+            .collect()
+    }
+
+    #[test]
+    fn ring_keeps_the_newest_points_oldest_first() {
+        let mut ring = TrailRing::default();
+        ring.push(point(0.0), 4);
+        // allocated once, up front
+        assert_eq!(ring.points.capacity(), 4);
+        for x in 1..10 {
+            ring.push(point(x as f64), 4);
+        }
+        assert_eq!(xs(&ring), vec![6.0, 7.0, 8.0, 9.0]);
+        assert_eq!(ring.points.capacity(), 4);
+
+        let mut visited = Vec::new();
+        ring.for_each(|p| visited.push(p.pos.x));
+        assert_eq!(visited, xs(&ring));
+    }
+
+    #[test]
+    fn ring_follows_length_changes() {
+        let mut ring = TrailRing::default();
+        for x in 0..6 {
+            ring.push(point(x as f64), 4);
+        }
+        // growing after wrapping keeps the order and appends
+        ring.push(point(6.0), 6);
+        ring.push(point(7.0), 6);
+        assert_eq!(xs(&ring), vec![2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        ring.push(point(8.0), 6);
+        assert_eq!(xs(&ring), vec![3.0, 4.0, 5.0, 6.0, 7.0, 8.0]);
+
+        // shrinking drops the oldest points
+        ring.push(point(9.0), 3);
+        assert_eq!(xs(&ring), vec![7.0, 8.0, 9.0]);
+        ring.push(point(10.0), 0);
+        assert!(ring.points.is_empty());
+    }
+
+    #[test]
+    fn trails_are_sampled_every_interval() {
+        let mut universe = Universe::new_empty();
+        universe.add_particle_simple(0.0, 0.0, 10.0, 0.0, 1.0);
+        universe.add_particle_simple(500.0, 0.0, 10.0, 0.0, 1.0);
+        universe.set_trail_interval(3);
+        universe.update_particle_trail_interval(1, Some(2));
+        for _ in 0..12 {
+            universe.time_step(0.016);
+        }
+        assert_eq!(universe.trails.get(0).len(), 4);
+        assert_eq!(universe.trails.get(1).len(), 6);
+
+        // the first sample is taken on the interval-th step, not the first
+        universe.clear_trails();
+        universe.time_step(0.016);
+        universe.time_step(0.016);
+        assert!(universe.trails.get(0).is_empty());
+        universe.time_step(0.016);
+        assert_eq!(universe.trails.get(0).len(), 1);
+    }
+}